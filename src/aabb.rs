@@ -0,0 +1,111 @@
+use crate::vec3::Point3;
+use crate::ray::Ray;
+use crate::interval::Interval;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub x: Interval,
+    pub y: Interval,
+    pub z: Interval,
+}
+
+impl Aabb {
+
+    pub fn new(x: Interval, y: Interval, z: Interval) -> Aabb {
+        Aabb { x, y, z }
+    }
+
+    pub fn empty() -> Aabb {
+        Aabb::new(Interval::empty(), Interval::empty(), Interval::empty())
+    }
+
+    // Box with a and b as opposite corners, in any order. Flat boxes are
+    // padded a little so the slab test still reports hits on them.
+    pub fn from_points(a: &Point3, b: &Point3) -> Aabb {
+        let bbox = Aabb {
+            x: Interval::new(a.x.min(b.x), a.x.max(b.x)),
+            y: Interval::new(a.y.min(b.y), a.y.max(b.y)),
+            z: Interval::new(a.z.min(b.z), a.z.max(b.z)),
+        };
+        bbox.pad_to_minimums()
+    }
+
+    fn pad_to_minimums(&self) -> Aabb {
+        let delta = 0.0001;
+        let pad = |axis: &Interval| if axis.size() < delta { axis.expand(delta) } else { *axis };
+
+        Aabb::new(pad(&self.x), pad(&self.y), pad(&self.z))
+    }
+
+    // Grown by `delta` along every axis, half of it on each side.
+    pub fn expand(&self, delta: f64) -> Aabb {
+        Aabb::new(self.x.expand(delta), self.y.expand(delta), self.z.expand(delta))
+    }
+
+    pub fn surrounding(a: &Aabb, b: &Aabb) -> Aabb {
+        Aabb {
+            x: Interval::enclosing(&a.x, &b.x),
+            y: Interval::enclosing(&a.y, &b.y),
+            z: Interval::enclosing(&a.z, &b.z),
+        }
+    }
+
+    pub fn axis_interval(&self, n: usize) -> &Interval {
+        match n {
+            1 => &self.y,
+            2 => &self.z,
+            _ => &self.x,
+        }
+    }
+
+    pub fn longest_axis(&self) -> usize {
+        if self.x.size() > self.y.size() {
+            if self.x.size() > self.z.size() { 0 } else { 2 }
+        } else if self.y.size() > self.z.size() {
+            1
+        } else {
+            2
+        }
+    }
+
+    pub fn centroid(&self, n: usize) -> f64 {
+        let axis = self.axis_interval(n);
+        0.5 * (axis.min + axis.max)
+    }
+
+    // Slab test: the ray overlaps the box if the parameter ranges where it is
+    // inside each pair of axis aligned planes have a common intersection.
+    pub fn hit(&self, r: &Ray, ray_t: Interval) -> bool {
+        self.intersect(r, ray_t).is_some()
+    }
+
+    // The part of `ray_t` where the ray is inside the box, if any.
+    pub fn intersect(&self, r: &Ray, ray_t: Interval) -> Option<Interval> {
+        let origin = [r.origin.x, r.origin.y, r.origin.z];
+        let direction = [r.direction.x, r.direction.y, r.direction.z];
+        let mut ray_t = ray_t;
+
+        for axis in 0..3 {
+            let ax = self.axis_interval(axis);
+            let adinv = 1.0 / direction[axis];
+
+            let t0 = (ax.min - origin[axis]) * adinv;
+            let t1 = (ax.max - origin[axis]) * adinv;
+
+            let (t_near, t_far) = if t0 < t1 { (t0, t1) } else { (t1, t0) };
+
+            if t_near > ray_t.min {
+                ray_t.min = t_near;
+            }
+            if t_far < ray_t.max {
+                ray_t.max = t_far;
+            }
+
+            if ray_t.max <= ray_t.min {
+                return None;
+            }
+        }
+        Some(ray_t)
+    }
+
+}
@@ -0,0 +1,147 @@
+use crate::color::Color;
+use crate::sampler::Sampler;
+use crate::framebuffer::Framebuffer;
+
+// Running state of one pixel across render passes. The sampler carries on
+// from where the previous pass left it, so splitting a render into passes
+// doesn't change the result.
+pub struct Pixel {
+    pub sum: Color,
+    pub samples: u32,
+    // Running mean and sum of squared deviations of the sample luminance,
+    // updated with Welford's algorithm.
+    pub mean: f64,
+    pub m2: f64,
+    // Set once adaptive sampling decides the pixel has enough samples.
+    pub converged: bool,
+    pub sampler: Sampler,
+}
+
+impl Pixel {
+
+    pub fn add(&mut self, color: Color) {
+        let luminance = color.luminance();
+
+        self.sum = &self.sum + color;
+        self.samples += 1;
+
+        let delta = luminance - self.mean;
+        self.mean += delta / self.samples as f64;
+        self.m2 += delta * (luminance - self.mean);
+    }
+
+    // Standard error of the mean luminance relative to the mean itself, with
+    // dark pixels judged as if they had a luminance of 0.01.
+    pub fn relative_error(&self) -> f64 {
+        if self.samples < 2 {
+            return f64::INFINITY;
+        }
+        let n = self.samples as f64;
+        let variance = self.m2 / (n - 1.0);
+
+        (variance / n).sqrt() / self.mean.max(0.01)
+    }
+
+    pub fn estimate(&self) -> Color {
+        if self.samples == 0 {
+            Color::new(0.0, 0.0, 0.0)
+        } else {
+            &self.sum * (1.0 / self.samples as f64)
+        }
+    }
+
+}
+
+// Sum of the samples taken so far for every pixel.
+pub struct Accumulator {
+    pub width: u32,
+    pub height: u32,
+    // Seed of the pixel samplers.
+    pub seed: u64,
+    // Samples per pixel of the passes rendered so far, pixels stopped by
+    // adaptive sampling may have less.
+    pub done: u32,
+    pub pixels: Vec<Pixel>,
+}
+
+impl Accumulator {
+
+    pub fn new(width: u32, height: u32, seed: u64) -> Accumulator {
+        let pixels = (0..height)
+            .flat_map(|j| (0..width).map(move |i| (i, j)))
+            .map(|(i, j)| Pixel {
+                sum: Color::new(0.0, 0.0, 0.0),
+                samples: 0,
+                mean: 0.0,
+                m2: 0.0,
+                converged: false,
+                sampler: Sampler::for_pixel(seed, i, j),
+            })
+            .collect();
+
+        Accumulator {
+            width,
+            height,
+            seed,
+            done: 0,
+            pixels,
+        }
+    }
+
+    // Average of the samples of every pixel.
+    pub fn estimate(&self) -> Framebuffer {
+        let mut buffer = Framebuffer::new(self.width, self.height);
+
+        for (index, pixel) in self.pixels.iter().enumerate() {
+            let index = index as u32;
+            buffer.set(index % self.width, index / self.width, pixel.estimate());
+        }
+        buffer
+    }
+
+    // Number of samples of every pixel relative to `max_samples`, blue for
+    // none through green to red for all of them.
+    pub fn sample_heatmap(&self, max_samples: u32) -> Framebuffer {
+        let mut buffer = Framebuffer::new(self.width, self.height);
+
+        for (index, pixel) in self.pixels.iter().enumerate() {
+            let index = index as u32;
+            let t = (pixel.samples as f64 / max_samples.max(1) as f64).clamp(0.0, 1.0);
+            let color = if t < 0.5 {
+                Color::new(0.0, 2.0 * t, 1.0 - 2.0 * t)
+            } else {
+                Color::new(2.0 * t - 1.0, 2.0 - 2.0 * t, 0.0)
+            };
+            buffer.set(index % self.width, index / self.width, color);
+        }
+        buffer
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pixel_statistics() {
+        let mut pixel = Accumulator::new(1, 1, 0).pixels.remove(0);
+        let samples = [0.5, 1.5, 0.25, 3.0, 0.75];
+
+        assert_eq!(pixel.relative_error(), f64::INFINITY);
+
+        for x in samples {
+            pixel.add(Color::new(x, x, x));
+        }
+
+        let n = samples.len() as f64;
+        let mean = samples.iter().sum::<f64>() / n;
+        let variance = samples.iter().map(|x| (x - mean) * (x - mean)).sum::<f64>() / (n - 1.0);
+
+        assert_eq!(pixel.samples, 5);
+        assert!((pixel.estimate().x - mean).abs() < 1e-12);
+        assert!((pixel.mean - mean).abs() < 1e-12);
+        assert!((pixel.relative_error() - (variance / n).sqrt() / mean).abs() < 1e-12);
+    }
+
+}
@@ -0,0 +1,110 @@
+// Values changing over time, for motion blur. They are given at keyframes
+// and interpolated in between.
+
+use crate::vec3::Vec3;
+
+pub trait Interpolate {
+    // Value a fraction `t` of the way from `a` to `b`.
+    fn interpolate(a: &Self, b: &Self, t: f64) -> Self;
+}
+
+impl Interpolate for Vec3 {
+
+    fn interpolate(a: &Vec3, b: &Vec3, t: f64) -> Vec3 {
+        (1.0 - t) * a + t * b
+    }
+
+}
+
+// Keyframes sorted by time. The value is held before the first and after
+// the last one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keyframes<T> {
+    keys: Vec<(f64, T)>,
+}
+
+impl<T: Interpolate + Clone> Keyframes<T> {
+
+    // None without any keyframe.
+    pub fn new(mut keys: Vec<(f64, T)>) -> Option<Keyframes<T>> {
+        if keys.is_empty() {
+            return None;
+        }
+        keys.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Some(Keyframes { keys })
+    }
+
+    pub fn constant(value: T) -> Keyframes<T> {
+        Keyframes { keys: vec![(0.0, value)] }
+    }
+
+    // From `start` at time 0 to `end` at time 1.
+    pub fn linear(start: T, end: T) -> Keyframes<T> {
+        Keyframes { keys: vec![(0.0, start), (1.0, end)] }
+    }
+
+    pub fn is_constant(&self) -> bool {
+        self.keys.len() == 1
+    }
+
+    pub fn at(&self, time: f64) -> T {
+        let next = self.keys.partition_point(|(t, _)| *t <= time);
+        if next == 0 {
+            return self.keys[0].1.clone();
+        }
+        if next == self.keys.len() {
+            return self.keys[next - 1].1.clone();
+        }
+
+        let (t0, a) = &self.keys[next - 1];
+        let (t1, b) = &self.keys[next];
+        T::interpolate(a, b, (time - t0) / (t1 - t0))
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &T> {
+        self.keys.iter().map(|(_, value)| value)
+    }
+
+    // The keyframe times with `steps` evenly spaced times inserted in every
+    // interval between them, for bounding values that aren't interpolated
+    // linearly.
+    pub fn sample_times(&self, steps: u32) -> Vec<f64> {
+        let mut times = vec![self.keys[0].0];
+        for pair in self.keys.windows(2) {
+            let (t0, t1) = (pair[0].0, pair[1].0);
+            times.extend((1..=steps + 1).map(|i| t0 + (t1 - t0) * i as f64 / (steps + 1) as f64));
+        }
+        times
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keyframes() {
+        let keys = vec![
+            (1.0, Vec3::new(2.0, 0.0, 0.0)),
+            (0.0, Vec3::new(0.0, 0.0, 0.0)),
+            (3.0, Vec3::new(2.0, 4.0, 0.0)),
+        ];
+        let path = Keyframes::new(keys).unwrap();
+
+        assert_eq!(path.at(-1.0), Vec3::new(0.0, 0.0, 0.0));
+        assert_eq!(path.at(0.25), Vec3::new(0.5, 0.0, 0.0));
+        assert_eq!(path.at(1.0), Vec3::new(2.0, 0.0, 0.0));
+        assert_eq!(path.at(2.5), Vec3::new(2.0, 3.0, 0.0));
+        assert_eq!(path.at(10.0), Vec3::new(2.0, 4.0, 0.0));
+        assert_eq!(path.sample_times(1), vec![0.0, 0.5, 1.0, 2.0, 3.0]);
+
+        let fixed = Keyframes::constant(Vec3::new(1.0, 1.0, 1.0));
+        assert!(fixed.is_constant());
+        assert_eq!(fixed.at(0.7), Vec3::new(1.0, 1.0, 1.0));
+        assert_eq!(fixed.sample_times(8), vec![0.0]);
+
+        assert!(Keyframes::<Vec3>::new(vec![]).is_none());
+    }
+
+}
@@ -0,0 +1,159 @@
+// Arbitrary output variables, per-pixel data about the first surface seen
+// through every pixel, for denoising, compositing and debugging.
+
+use crate::color::Color;
+use crate::framebuffer::Framebuffer;
+
+use clap::ValueEnum;
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Aov {
+    // World space shading normal, facing the camera.
+    Normal,
+    // Distance from the camera, MISS_DEPTH where nothing was hit.
+    Depth,
+    Albedo,
+    MaterialId,
+    ObjectId,
+}
+
+// Depth of the pixels where the camera rays escape the scene.
+pub const MISS_DEPTH: f64 = 1e10;
+
+// Normal, depth and albedo are averaged over several jittered samples, the
+// IDs come from the ray through the pixel center. Scalars are stored in all
+// three channels.
+pub struct Aovs {
+    pub normal: Framebuffer,
+    pub depth: Framebuffer,
+    pub albedo: Framebuffer,
+    pub material_id: Framebuffer,
+    pub object_id: Framebuffer,
+}
+
+impl Aovs {
+
+    pub fn new(width: u32, height: u32) -> Aovs {
+        Aovs {
+            normal: Framebuffer::new(width, height),
+            depth: Framebuffer::new(width, height),
+            albedo: Framebuffer::new(width, height),
+            material_id: Framebuffer::new(width, height),
+            object_id: Framebuffer::new(width, height),
+        }
+    }
+
+    // The raw values, as written to HDR formats.
+    pub fn get(&self, aov: Aov) -> &Framebuffer {
+        match aov {
+            Aov::Normal => &self.normal,
+            Aov::Depth => &self.depth,
+            Aov::Albedo => &self.albedo,
+            Aov::MaterialId => &self.material_id,
+            Aov::ObjectId => &self.object_id,
+        }
+    }
+
+    // The values mapped to colors that make sense in an 8-bit image: normals
+    // from [-1, 1] to [0, 1], depth from white near the camera to black at the
+    // farthest hit, and a distinct color per ID.
+    pub fn visualize(&self, aov: Aov) -> Framebuffer {
+        let source = self.get(aov);
+        let far = match aov {
+            Aov::Depth => Self::pixels(source).map(|c| c.x).filter(|&d| d < MISS_DEPTH).fold(0.0, f64::max),
+            _ => 0.0,
+        };
+
+        let mut buffer = Framebuffer::new(source.width, source.height);
+        for j in 0..source.height {
+            for i in 0..source.width {
+                let c = source.get(i, j);
+                let color = match aov {
+                    Aov::Normal => 0.5 * (c + Color::new(1.0, 1.0, 1.0)),
+                    Aov::Depth if c.x >= MISS_DEPTH || far <= 0.0 => Color::new(0.0, 0.0, 0.0),
+                    Aov::Depth => {
+                        let d = 1.0 - c.x / far;
+                        Color::new(d, d, d)
+                    },
+                    Aov::Albedo => c.clone(),
+                    Aov::MaterialId | Aov::ObjectId => id_color(c.x as u32),
+                };
+                buffer.set(i, j, color);
+            }
+        }
+        buffer
+    }
+
+    fn pixels(buffer: &Framebuffer) -> impl Iterator<Item = &Color> {
+        (0..buffer.height).flat_map(move |j| (0..buffer.width).map(move |i| buffer.get(i, j)))
+    }
+
+}
+
+// Black for no ID, and a color picked by hashing the ID otherwise so that
+// neighbouring IDs look different.
+fn id_color(id: u32) -> Color {
+    if id == 0 {
+        return Color::new(0.0, 0.0, 0.0);
+    }
+    let h = id.wrapping_mul(0x9e3779b1);
+    let channel = |shift: u32| 0.2 + 0.8 * ((h >> shift) & 0xff) as f64 / 255.0;
+
+    Color::new(channel(8), channel(16), channel(24))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_visualize_depth() {
+        let mut aovs = Aovs::new(3, 1);
+        aovs.depth.set(0, 0, Color::new(1.0, 1.0, 1.0));
+        aovs.depth.set(1, 0, Color::new(4.0, 4.0, 4.0));
+        aovs.depth.set(2, 0, Color::new(MISS_DEPTH, MISS_DEPTH, MISS_DEPTH));
+
+        // Normalized by the farthest hit, misses left out and shown black.
+        let image = aovs.visualize(Aov::Depth);
+        assert_eq!(image.get(0, 0), &Color::new(0.75, 0.75, 0.75));
+        assert_eq!(image.get(1, 0), &Color::new(0.0, 0.0, 0.0));
+        assert_eq!(image.get(2, 0), &Color::new(0.0, 0.0, 0.0));
+
+        // Nothing hit at all.
+        let mut aovs = Aovs::new(1, 1);
+        aovs.depth.set(0, 0, Color::new(MISS_DEPTH, MISS_DEPTH, MISS_DEPTH));
+        assert_eq!(aovs.visualize(Aov::Depth).get(0, 0), &Color::new(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_visualize_normal() {
+        let mut aovs = Aovs::new(2, 1);
+        aovs.normal.set(0, 0, Color::new(1.0, 0.0, -1.0));
+        aovs.normal.set(1, 0, Color::new(-0.6, 0.8, 0.0));
+
+        let image = aovs.visualize(Aov::Normal);
+        assert_eq!(image.get(0, 0), &Color::new(1.0, 0.5, 0.0));
+        assert!((image.get(1, 0) - Color::new(0.2, 0.9, 0.5)).length() < 1e-12);
+    }
+
+    #[test]
+    fn test_id_color() {
+        assert_eq!(id_color(0), Color::new(0.0, 0.0, 0.0));
+
+        let colors: Vec<Color> = (1..=256).map(id_color).collect();
+        for (a, color) in colors.iter().enumerate() {
+            assert!([color.x, color.y, color.z].iter().all(|c| (0.2..=1.0).contains(c)));
+            for other in &colors[a + 1..] {
+                assert!((color - other).length() > 1e-3);
+            }
+        }
+
+        // The ID AOVs go through it.
+        let mut aovs = Aovs::new(2, 1);
+        aovs.object_id.set(1, 0, Color::new(7.0, 7.0, 7.0));
+        let image = aovs.visualize(Aov::ObjectId);
+        assert_eq!(image.get(0, 0), &Color::new(0.0, 0.0, 0.0));
+        assert_eq!(image.get(1, 0), &id_color(7));
+    }
+
+}
@@ -0,0 +1,192 @@
+use crate::vec3::Vec3;
+use crate::color::Color;
+use crate::ray::Ray;
+
+use image::{ImageResult, Rgb32FImage};
+
+use std::f64::consts::PI;
+use std::path::Path;
+
+// Radiance arriving along rays that escape the scene.
+pub trait Background: Send + Sync {
+    fn color(&self, r: &Ray) -> Color;
+}
+
+pub struct SolidBackground {
+    color: Color,
+}
+
+impl SolidBackground {
+
+    pub fn new(color: Color) -> SolidBackground {
+        SolidBackground {
+            color,
+        }
+    }
+
+}
+
+impl Background for SolidBackground {
+
+    fn color(&self, _r: &Ray) -> Color {
+        self.color.clone()
+    }
+
+}
+
+// Vertical blend from `bottom` straight down to `top` straight up.
+pub struct GradientBackground {
+    bottom: Color,
+    top: Color,
+}
+
+impl GradientBackground {
+
+    pub fn new(bottom: Color, top: Color) -> GradientBackground {
+        GradientBackground {
+            bottom,
+            top,
+        }
+    }
+
+    // The white to light blue sky.
+    pub fn sky() -> GradientBackground {
+        GradientBackground::new(Color::new(1.0, 1.0, 1.0), Color::new(0.5, 0.7, 1.0))
+    }
+
+}
+
+impl Background for GradientBackground {
+
+    fn color(&self, r: &Ray) -> Color {
+        let unit_direction = r.direction.unit_vector();
+        let a = 0.5 * (unit_direction.y + 1.0);
+        (1.0 - a) * &self.bottom + (a * &self.top)
+    }
+
+}
+
+// Equirectangular (latitude/longitude) environment map, with +Y up at the
+// top row of the image and the seam behind -X.
+pub struct EnvironmentMap {
+    image: Rgb32FImage,
+    // Rotation around the Y axis in degrees.
+    rotation: f64,
+    intensity: f64,
+}
+
+impl EnvironmentMap {
+
+    pub fn new(image: Rgb32FImage, rotation: f64, intensity: f64) -> EnvironmentMap {
+        EnvironmentMap {
+            image,
+            rotation,
+            intensity,
+        }
+    }
+
+    // Reads any image format the image crate supports, notably Radiance .hdr
+    // and OpenEXR files which keep the full dynamic range.
+    pub fn load(path: &Path, rotation: f64, intensity: f64) -> ImageResult<EnvironmentMap> {
+        let image = image::open(path)?.to_rgb32f();
+        Ok(EnvironmentMap::new(image, rotation, intensity))
+    }
+
+    fn texel(&self, x: i64, y: i64) -> Color {
+        let width = self.image.width() as i64;
+        let height = self.image.height() as i64;
+
+        // Wrap around horizontally, clamp at the poles.
+        let x = x.rem_euclid(width) as u32;
+        let y = y.clamp(0, height - 1) as u32;
+
+        let p = self.image.get_pixel(x, y);
+        Color::new(p[0] as f64, p[1] as f64, p[2] as f64)
+    }
+
+    // Bilinear lookup, u and v in [0, 1].
+    fn sample(&self, u: f64, v: f64) -> Color {
+        let x = u * self.image.width() as f64 - 0.5;
+        let y = v * self.image.height() as f64 - 0.5;
+
+        let x0 = x.floor();
+        let y0 = y.floor();
+        let tx = x - x0;
+        let ty = y - y0;
+        let (x0, y0) = (x0 as i64, y0 as i64);
+
+        let top = (1.0 - tx) * self.texel(x0, y0) + tx * self.texel(x0 + 1, y0);
+        let bottom = (1.0 - tx) * self.texel(x0, y0 + 1) + tx * self.texel(x0 + 1, y0 + 1);
+
+        (1.0 - ty) * top + ty * bottom
+    }
+
+}
+
+impl Background for EnvironmentMap {
+
+    fn color(&self, r: &Ray) -> Color {
+        if self.image.width() == 0 || self.image.height() == 0 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+
+        let d: Vec3 = r.direction.unit_vector();
+        let phi = (-d.z).atan2(d.x) + PI + self.rotation.to_radians();
+        let theta = d.y.clamp(-1.0, 1.0).acos();
+
+        let u = (phi / (2.0 * PI)).rem_euclid(1.0);
+        let v = theta / PI;
+
+        self.intensity * self.sample(u, v)
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vec3::Point3;
+
+    // Horizontal ray looking at the given u coordinate of an unrotated map.
+    fn horizontal_ray(u: f64) -> Ray {
+        let phi = 2.0 * PI * u - PI;
+        Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(phi.cos(), 0.0, -phi.sin()))
+    }
+
+    fn assert_color(e: Color, c: Color) {
+        assert!((&e - &c).length() < 1e-9, "expected {:?} found {:?}", e, c);
+    }
+
+    #[test]
+    fn test_environment_map_lookup() {
+        // Red, green, blue and white columns, the bottom row twice as bright.
+        let colors = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0], [1.0, 1.0, 1.0]];
+        let image = Rgb32FImage::from_fn(4, 2, |x, y| {
+            let scale = (y + 1) as f32;
+            let c = colors[x as usize];
+            image::Rgb([c[0] * scale, c[1] * scale, c[2] * scale])
+        });
+        let map = EnvironmentMap::new(image.clone(), 0.0, 2.0);
+
+        // The horizon falls between both rows, at 1.5 times the top row.
+        let data: [(f64, Color); 4] = [
+            (0.125, Color::new(3.0, 0.0, 0.0)),
+            (0.375, Color::new(0.0, 3.0, 0.0)),
+            (0.625, Color::new(0.0, 0.0, 3.0)),
+            (0.875, Color::new(3.0, 3.0, 3.0)),
+        ];
+        for (u, e) in data {
+            assert_color(e, map.color(&horizontal_ray(u)));
+        }
+
+        // The poles are clamped to the top and bottom rows.
+        let up = map.color(&Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0)));
+        let down = map.color(&Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, -1.0, 0.0)));
+        assert_color(2.0 * up, down);
+
+        // A quarter turn brings the next column into view.
+        let rotated = EnvironmentMap::new(image, 90.0, 2.0);
+        assert_color(Color::new(0.0, 0.0, 3.0), rotated.color(&horizontal_ray(0.375)));
+    }
+
+}
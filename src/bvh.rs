@@ -0,0 +1,172 @@
+use crate::hittable::{Hittable, HitRecord};
+use crate::hittable_list::HittableList;
+use crate::ray::Ray;
+use crate::interval::Interval;
+use crate::aabb::Aabb;
+
+use std::cmp::Ordering;
+
+pub struct BvhNode {
+    left: Box<dyn Hittable>,
+    right: Option<Box<dyn Hittable>>,
+    bbox: Aabb,
+}
+
+impl BvhNode {
+
+    // Builds the hierarchy by splitting the objects at the median of their
+    // centroids along the longest axis of the enclosing box.
+    pub fn new(mut objects: HittableList) -> BvhNode {
+
+        let bbox = objects.bounding_box();
+
+        match objects.len() {
+            0 | 1 => {
+                let left: Box<dyn Hittable> = match objects.pop() {
+                    Some(object) => object,
+                    None => Box::new(objects),
+                };
+                BvhNode { left, right: None, bbox }
+            },
+            2 => {
+                let right = objects.pop();
+                let left = objects.pop().unwrap();
+                BvhNode { left, right, bbox }
+            },
+            n => {
+                let axis = bbox.longest_axis();
+                let mid = n / 2;
+
+                objects.select_nth_unstable_by(mid, |a, b| Self::box_compare(a.as_ref(), b.as_ref(), axis));
+                let upper = objects.split_off(mid);
+
+                BvhNode {
+                    left: Box::new(BvhNode::new(objects)),
+                    right: Some(Box::new(BvhNode::new(upper))),
+                    bbox,
+                }
+            },
+        }
+    }
+
+    fn box_compare(a: &dyn Hittable, b: &dyn Hittable, axis: usize) -> Ordering {
+        let a_centroid = a.bounding_box().centroid(axis);
+        let b_centroid = b.bounding_box().centroid(axis);
+        a_centroid.total_cmp(&b_centroid)
+    }
+
+}
+
+impl Hittable for BvhNode {
+
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord> {
+        if !self.bbox.hit(r, ray_t) {
+            return None;
+        }
+
+        let hit_left = self.left.hit(r, ray_t);
+
+        let closest_so_far = match &hit_left {
+            Some(hr) => hr.t,
+            None => ray_t.max,
+        };
+
+        let hit_right = match &self.right {
+            Some(right) => right.hit(r, Interval::new(ray_t.min, closest_so_far)),
+            None => None,
+        };
+
+        hit_right.or(hit_left)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vec3::{Vec3, Point3};
+    use crate::sphere::Sphere;
+    use crate::material::Lambertian;
+    use crate::color::Color;
+    use crate::sampler::Sampler;
+
+    use std::sync::Arc;
+
+    fn build(spheres: &[(Point3, f64)]) -> HittableList {
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let mut world: HittableList = vec![];
+
+        for (center, radius) in spheres {
+            world.push(Box::new(Sphere::new(center.clone(), *radius, material.clone())));
+        }
+        world
+    }
+
+    fn random_spheres(count: usize, sampler: &mut Sampler) -> Vec<(Point3, f64)> {
+        (0..count).map(|_| {
+            (Vec3::random_interval(Interval::new(-10.0, 10.0), sampler),
+             Vec3::random_double_interval(&Interval::new(0.1, 1.5), sampler))
+        }).collect()
+    }
+
+    fn assert_same_hits(linear: &HittableList, bvh: &BvhNode, rays: usize, sampler: &mut Sampler) {
+        for _ in 0..rays {
+            let origin = Vec3::random_interval(Interval::new(-20.0, 20.0), sampler);
+            let r = Ray::new(origin, Vec3::random_unit_vector(sampler));
+
+            let expected = linear.hit(&r, Interval::new(0.001, f64::INFINITY));
+            let result = bvh.hit(&r, Interval::new(0.001, f64::INFINITY));
+
+            match (expected, result) {
+                (Some(e), Some(h)) => {
+                    assert_eq!(e.t, h.t);
+                    assert_eq!(e.p, h.p);
+                    assert_eq!(e.normal, h.normal);
+                },
+                (None, None) => (),
+                (e, h) => panic!("[BVH] Mismatch for {:?} {:?}: linear {} bvh {}", r.origin, r.direction, e.is_some(), h.is_some()),
+            }
+        }
+    }
+
+    #[test]
+    fn test_bvh_matches_linear_traversal() {
+        let mut sampler = Sampler::new(0);
+
+        for count in [1, 2, 3, 17, 500] {
+            let spheres = random_spheres(count, &mut sampler);
+            let linear = build(&spheres);
+            let bvh = BvhNode::new(build(&spheres));
+
+            assert_eq!(linear.bounding_box(), bvh.bounding_box());
+            assert_same_hits(&linear, &bvh, 2000, &mut sampler);
+        }
+    }
+
+    #[test]
+    fn test_bvh_overlapping_objects() {
+        // Concentric and coincident spheres make the closest hit depend on
+        // the traversal order if the ray interval is not narrowed properly.
+        let spheres: Vec<(Point3, f64)> = (1..=50)
+            .map(|i| (Point3::new(0.0, 0.0, 0.0), i as f64 * 0.2))
+            .chain((0..50).map(|_| (Point3::new(1.0, 1.0, 1.0), 1.0)))
+            .collect();
+        let linear = build(&spheres);
+        let bvh = BvhNode::new(build(&spheres));
+
+        assert_same_hits(&linear, &bvh, 2000, &mut Sampler::new(0));
+    }
+
+    #[test]
+    fn test_bvh_empty() {
+        let bvh = BvhNode::new(vec![]);
+        let r = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+
+        assert!(bvh.hit(&r, Interval::new(0.001, f64::INFINITY)).is_none());
+    }
+
+}
@@ -1,11 +1,27 @@
 use crate::vec3::{Vec3, Point3};
-use crate::hittable::Hittable;
+use crate::hittable::{Hittable, HitRecord};
 use crate::hittable_list::HittableList;
 use crate::ray::Ray;
 use crate::color::Color;
 use crate::interval::Interval;
+use crate::background::{Background, GradientBackground};
+use crate::sampler::Sampler;
+use crate::framebuffer::Framebuffer;
+use crate::accumulator::{Accumulator, Pixel};
+use crate::aov::{Aovs, MISS_DEPTH};
+use crate::medium::Medium;
 
-use image::{ImageBuffer, RgbImage, Rgb};
+use rayon::prelude::*;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+// Samples per pixel of render_aovs.
+const AOV_SAMPLES: u32 = 16;
+
+// Samples per pass of checkpointed renders that don't set pass_samples,
+// checkpoints are only saved between passes.
+pub const CHECKPOINT_PASS_SAMPLES: u32 = 16;
 
 pub struct Camera {
     pub aspect_ratio: f64,
@@ -18,151 +34,705 @@ pub struct Camera {
     pub vup: Vec3,
     pub defocus_angle: f64,
     pub focus_dist: f64,
+    // Number of worker threads used by render, 0 means one per available core.
+    pub threads: usize,
+    pub background: Arc<dyn Background>,
+    // Renders with the same seed are identical.
+    pub seed: u64,
+    // Samples added to every pixel per pass of render_progressive, 0 renders
+    // all of them in one pass, or passes of min_samples when adaptive.
+    pub pass_samples: u32,
+    // Adaptive sampling stops sampling a pixel once it has min_samples and
+    // its relative error is below the threshold, samples_per_pixel is then
+    // the maximum. A threshold of 0 disables it.
+    pub noise_threshold: f64,
+    pub min_samples: u32,
+    // Rays are traced at times spread over the interval the shutter is open,
+    // moving objects are blurred along their path during it.
+    pub shutter_open: f64,
+    pub shutter_close: f64,
+    // Volumes the rays go through on their way between the surfaces of the
+    // world. They are kept here rather than in the world: a ray collides with
+    // them anywhere inside, not at a surface, and shadow rays are attenuated
+    // by them instead of blocked.
+    pub media: Vec<Box<dyn Medium>>,
     image_height: u32,
-    pixel_samples_scale: f64,
     center: Point3,
     pixel00_loc: Point3,
     pixel_delta_u: Vec3,
     pixel_delta_v: Vec3,
-    u: Vec3,
-    v: Vec3,
-    w: Vec3,
     defocus_disk_u: Vec3,
     defocus_disk_v: Vec3,
 }
 
 impl Camera {
 
+    #[allow(clippy::too_many_arguments)]
     pub fn new(aspect_ratio: f64, image_width: u32, samples_per_pixel: u32, max_depth: u32, vfov: f64, lookfrom: Point3, lookat: Point3, vup: Vec3, defocus_angle: f64, focus_dist: f64) -> Camera {
 
-        let image_height: u32 = if image_width as f64 / aspect_ratio < 1.0 { 1 } else { (image_width as f64 / aspect_ratio) as u32 };
+        let mut camera = Camera {
+            aspect_ratio,
+            image_width,
+            samples_per_pixel,
+            max_depth,
+            vfov,
+            lookfrom,
+            lookat,
+            vup,
+            defocus_angle,
+            focus_dist,
+            threads: 0,
+            background: Arc::new(GradientBackground::sky()),
+            seed: 0,
+            pass_samples: 0,
+            noise_threshold: 0.0,
+            min_samples: 16,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+            media: vec![],
+            image_height: 0,
+            center: Point3::new(0.0, 0.0, 0.0),
+            pixel00_loc: Point3::new(0.0, 0.0, 0.0),
+            pixel_delta_u: Vec3::new(0.0, 0.0, 0.0),
+            pixel_delta_v: Vec3::new(0.0, 0.0, 0.0),
+            defocus_disk_u: Vec3::new(0.0, 0.0, 0.0),
+            defocus_disk_v: Vec3::new(0.0, 0.0, 0.0),
+        };
+        camera.initialize();
+        camera
+
+    }
+
+    // Recomputes the derived viewport state from the public fields. Call it
+    // after changing any of them on an existing camera.
+    pub fn initialize(&mut self) {
+
+        let image_width = self.image_width;
+        let image_height: u32 = if image_width as f64 / self.aspect_ratio < 1.0 { 1 } else { (image_width as f64 / self.aspect_ratio) as u32 };
 
         // Camera
 
-        let camera_center = lookfrom.clone();
-        let theta = vfov.to_radians();
+        let camera_center = self.lookfrom.clone();
+        let theta = self.vfov.to_radians();
         let h = (theta/2.0).tan();
-        let viewport_height = 2.0 * h * focus_dist;
+        let viewport_height = 2.0 * h * self.focus_dist;
         let viewport_width = viewport_height * (image_width as f64 / image_height as f64);
 
         // Calculate the u,v,w unit basis vectors for the camera coordinate frame.
-        let w = (&lookfrom - &lookat).unit_vector();
-        let u = Vec3::cross(&vup, &w).unit_vector();
+        let w = (&self.lookfrom - &self.lookat).unit_vector();
+        let u = Vec3::cross(&self.vup, &w).unit_vector();
         let v = Vec3::cross(&w, &u);
-        
+
         // Calculate the vectors across the horizontal and down the vertical viewport edges
 
         let viewport_u = viewport_width * &u;
         let viewport_v = viewport_height * -&v;
 
         // Calculate the horizontal and vertical delta vectors from pixel to pixel
-        
+
         let pixel_delta_u = &viewport_u / image_width as f64;
         let pixel_delta_v = &viewport_v / image_height as f64;
 
         // Calculate the location of the upper left pixel.
 
-        let viewport_upper_left = &camera_center - (focus_dist * &w) - &viewport_u/2.0_f64 - &viewport_v/2.0_f64;
+        let viewport_upper_left = &camera_center - (self.focus_dist * &w) - &viewport_u/2.0_f64 - &viewport_v/2.0_f64;
         let pixel00_loc = &viewport_upper_left + 0.5 * (&pixel_delta_u + &pixel_delta_v);
 
         // Calculate the camera defocus disk basis vectors.
-        let defocus_radius = focus_dist * (defocus_angle / 2.0).to_radians().tan();
-        let defocus_disk_u = &u * defocus_radius;
-        let defocus_disk_v = &v * defocus_radius;
-
-        Camera {
-            aspect_ratio: aspect_ratio,
-            image_width: image_width,
-            samples_per_pixel: samples_per_pixel,
-            max_depth: max_depth,
-            vfov: vfov,
-            lookfrom: lookfrom,
-            lookat: lookat,
-            vup: vup,
-            defocus_angle: defocus_angle,
-            focus_dist: focus_dist,
-            image_height: image_height,
-            pixel_samples_scale: 1.0 / samples_per_pixel as f64,
-            center: camera_center,
-            pixel00_loc: pixel00_loc,
-            pixel_delta_u: pixel_delta_u,
-            pixel_delta_v: pixel_delta_v,
-            u: u,
-            v: v,
-            w: w,
-            defocus_disk_u: defocus_disk_u,
-            defocus_disk_v: defocus_disk_v,
-        }
-
-    }
-
-    pub fn render(&self, world: &HittableList) {
-
-        let mut buffer: RgbImage = RgbImage::new(self.image_width, self.image_height);
-
-        for j in 0..self.image_height {
-            eprint!("\rScanlines remaining: {}", self.image_height - j);
-            for i in 0..self.image_width {
-                let mut color = Color::new(0.0, 0.0, 0.0);
-
-                for _ in 0..self.samples_per_pixel {
-                    let r = self.get_ray(i, j);
-                    color = color + Self::ray_color(&r, &world, self.max_depth);
+        let defocus_radius = self.focus_dist * (self.defocus_angle / 2.0).to_radians().tan();
+
+        self.image_height = image_height;
+        self.center = camera_center;
+        self.pixel00_loc = pixel00_loc;
+        self.pixel_delta_u = pixel_delta_u;
+        self.pixel_delta_v = pixel_delta_v;
+        self.defocus_disk_u = &u * defocus_radius;
+        self.defocus_disk_v = &v * defocus_radius;
+
+    }
+
+    // Emissive objects in `lights` are also sampled directly at every
+    // diffuse bounce, they must be part of the world as well and wrapped in
+    // `Identified` with an object ID no other object of the world shares.
+    pub fn render(&self, world: &dyn Hittable, lights: &HittableList) -> Framebuffer {
+        self.render_progressive(world, lights, |_| {}).estimate()
+    }
+
+    // Renders the whole image in passes, handing the accumulation to
+    // `snapshot` after every pass but the last. Without adaptive sampling the
+    // result is the same whatever the number of passes.
+    pub fn render_progressive(&self, world: &dyn Hittable, lights: &HittableList, snapshot: impl FnMut(&Accumulator)) -> Accumulator {
+        let accumulator = Accumulator::new(self.image_width, self.image_height, self.seed);
+        self.resume(world, lights, accumulator, snapshot)
+    }
+
+    // Whether the accumulation of an earlier render, for instance read from a
+    // checkpoint, can be resumed by this camera.
+    pub fn can_resume(&self, accumulator: &Accumulator) -> bool {
+        accumulator.width == self.image_width && accumulator.height == self.image_height && accumulator.seed == self.seed
+    }
+
+    // Carries on with the passes of an earlier render, the result is the same
+    // as if it was never interrupted.
+    pub fn resume(&self, world: &dyn Hittable, lights: &HittableList, mut accumulator: Accumulator, mut snapshot: impl FnMut(&Accumulator)) -> Accumulator {
+
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(self.threads)
+            .build()
+            .expect("failed to build the render thread pool");
+
+        let adaptive = self.noise_threshold > 0.0;
+        let pass_samples = match self.pass_samples {
+            0 if adaptive => self.min_samples.max(1),
+            0 => self.samples_per_pixel,
+            n => n,
+        };
+
+        while accumulator.done < self.samples_per_pixel {
+            let done = accumulator.done;
+            let samples = pass_samples.min(self.samples_per_pixel - done);
+            pool.install(|| self.render_pass(&mut accumulator, world, lights, samples, done));
+            accumulator.done += samples;
+
+            let mut active = accumulator.pixels.len();
+            if adaptive {
+                for pixel in accumulator.pixels.iter_mut() {
+                    pixel.converged = pixel.converged
+                        || (pixel.samples >= self.min_samples && pixel.relative_error() < self.noise_threshold);
                 }
-                color = color * self.pixel_samples_scale;
-                buffer.put_pixel(i, j, color.write_color());
+                active = accumulator.pixels.iter().filter(|pixel| !pixel.converged).count();
+            }
+
+            if accumulator.done < self.samples_per_pixel && active > 0 {
+                snapshot(&accumulator);
+            } else {
+                break;
             }
         }
 
-        let _ = buffer.save("img.png");
-        eprintln!("Done");
+        eprintln!("\nDone");
+        accumulator
     }
 
+    // Adds up to `samples` samples to every pixel that hasn't converged,
+    // `done` is only used to report progress.
+    fn render_pass(&self, accumulator: &mut Accumulator, world: &dyn Hittable, lights: &HittableList, samples: u32, done: u32) {
+        let remaining = AtomicU32::new(self.image_height);
 
-    fn ray_color(r: &Ray, world: &HittableList, depth: u32) -> Color {
-        
-        if depth <= 0 {
-            return Color::new(0.0, 0.0, 0.0);
+        // Each scanline is an independent unit of work, the pool hands them
+        // out to idle workers.
+        accumulator.pixels.par_chunks_mut(self.image_width as usize).enumerate().for_each(|(j, scanline)| {
+            for (i, pixel) in scanline.iter_mut().enumerate().filter(|(_, pixel)| !pixel.converged) {
+                self.render_pixel(i as u32, j as u32, pixel, samples, world, lights);
+            }
+
+            let left = remaining.fetch_sub(1, Ordering::Relaxed) - 1;
+            eprint!("\rSamples {}/{}, scanlines remaining: {:<6}", done + samples, self.samples_per_pixel, left);
+        });
+    }
+
+    fn render_pixel(&self, i: u32, j: u32, pixel: &mut Pixel, samples: u32, world: &dyn Hittable, lights: &HittableList) {
+        for _ in 0..samples {
+            let r = self.get_ray(i, j, &mut pixel.sampler);
+            let color = self.ray_color(&r, world, lights, self.max_depth, None, &mut pixel.sampler);
+            pixel.add(color);
+        }
+    }
+
+    // First hit data of every pixel, see Aovs.
+    pub fn render_aovs(&self, world: &dyn Hittable) -> Aovs {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(self.threads)
+            .build()
+            .expect("failed to build the render thread pool");
+
+        let scanlines: Vec<Vec<[Color; 5]>> = pool.install(|| {
+            (0..self.image_height).into_par_iter().map(|j| {
+                (0..self.image_width).map(|i| self.aov_pixel(i, j, world)).collect()
+            }).collect()
+        });
+
+        let mut aovs = Aovs::new(self.image_width, self.image_height);
+        for (j, scanline) in scanlines.into_iter().enumerate() {
+            for (i, [normal, depth, albedo, material_id, object_id]) in scanline.into_iter().enumerate() {
+                let (i, j) = (i as u32, j as u32);
+                aovs.normal.set(i, j, normal);
+                aovs.depth.set(i, j, depth);
+                aovs.albedo.set(i, j, albedo);
+                aovs.material_id.set(i, j, material_id);
+                aovs.object_id.set(i, j, object_id);
+            }
         }
+        aovs
+    }
 
-        match world.hit(&r, Interval::new(0.001, f64::INFINITY)) {
-            Some(hr) => {
-                match hr.material.scatter(&r, &hr) {
-                    Some((attenuation, scattered)) => {
-                        attenuation * &Self::ray_color(&scattered, &world, depth - 1)
-                    },
-                    None => Color::new(0.0, 0.0, 0.0),
+    fn aov_pixel(&self, i: u32, j: u32, world: &dyn Hittable) -> [Color; 5] {
+        let mut sampler = Sampler::for_pixel(self.seed, i, j);
+        let mut normal = Color::new(0.0, 0.0, 0.0);
+        let mut depth = 0.0;
+        let mut hits = 0;
+        let mut albedo = Color::new(0.0, 0.0, 0.0);
+        let mut ids = (0, 0);
+
+        for sample in 0..AOV_SAMPLES {
+            let r = if sample == 0 {
+                self.ray_through(i, j, &Vec3::new(0.0, 0.0, 0.0), &mut sampler)
+            } else {
+                self.get_ray(i, j, &mut sampler)
+            };
+
+            if let Some(mut hr) = world.hit(&r, Interval::new(0.001, f64::INFINITY)) {
+                hr.perturb_normal();
+                normal = normal + &hr.normal;
+                depth += hr.t * r.direction.length();
+                hits += 1;
+                albedo = albedo + hr.material.albedo(&hr);
+                if sample == 0 {
+                    ids = (hr.material_id, hr.object_id);
                 }
-            },
-            None => {
-                let unit_direction = r.direction.unit_vector();
-                let a = 0.5 * (unit_direction.y + 1.0);
-                (1.0 - a) * Color::new(1.0, 1.0, 1.0) + (a * Color::new(0.5, 0.7, 1.0))
-            },
+            }
+        }
+
+        // Depth is only averaged over the hits, blending with the distance
+        // of a miss would be meaningless.
+        let scale = 1.0 / AOV_SAMPLES as f64;
+        let depth = if hits > 0 { depth / hits as f64 } else { MISS_DEPTH };
+        let (material_id, object_id) = (ids.0 as f64, ids.1 as f64);
+
+        [normal * scale,
+         Color::new(depth, depth, depth),
+         albedo * scale,
+         Color::new(material_id, material_id, material_id),
+         Color::new(object_id, object_id, object_id)]
+    }
+
+    // `bsdf_pdf` is the density the ray was sampled with at the previous
+    // bounce, None for camera rays and specular bounces whose light paths
+    // can't be found by sampling the lights.
+    #[allow(clippy::too_many_arguments)]
+    fn ray_color(&self, r: &Ray, world: &dyn Hittable, lights: &HittableList, depth: u32, bsdf_pdf: Option<f64>, sampler: &mut Sampler) -> Color {
+
+        if depth == 0 {
+            return Color::new(0.0, 0.0, 0.0);
         }
+
+        // A medium may scatter the ray before it reaches the surface.
+        let hit = world.hit(r, Interval::new(0.001, f64::INFINITY));
+        let t_max = hit.as_ref().map_or(f64::INFINITY, |hr| hr.t);
+        let mut hr = match self.sample_media(r, Interval::new(0.001, t_max), sampler).or(hit) {
+            Some(hr) => hr,
+            None => return self.background.color(r),
+        };
+        hr.perturb_normal();
+
+        // Emission found by following the BSDF was also reachable through
+        // light sampling at the previous bounce if it comes from one of the
+        // lights, weight both strategies. Media and the emissive surfaces
+        // left out of the lights are never sampled as lights.
+        let mut emitted = hr.material.emitted(&hr);
+        if let Some(bsdf_pdf) = bsdf_pdf.filter(|_| !hr.in_medium) {
+            if is_light(r, &hr, lights) {
+                let light_pdf = lights.pdf_value(&r.origin, &r.direction, r.time);
+                emitted = power_heuristic(bsdf_pdf, light_pdf) * emitted;
+            }
+        }
+
+        let srec = match hr.material.scatter(r, &hr, sampler) {
+            Some(srec) => srec,
+            None => return emitted,
+        };
+
+        let direct = match srec.pdf {
+            Some(_) => self.sample_lights(r, &hr, world, lights, sampler),
+            None => Color::new(0.0, 0.0, 0.0),
+        };
+
+        if !hr.same_side(&srec.scattered.direction) {
+            return emitted + direct;
+        }
+
+        emitted + direct + srec.attenuation * &self.ray_color(&srec.scattered, world, lights, depth - 1, srec.pdf, sampler)
+    }
+
+    // Next event estimation, light arriving at the hit point from a direction
+    // picked on one of the lights.
+    fn sample_lights(&self, r: &Ray, hr: &HitRecord, world: &dyn Hittable, lights: &HittableList, sampler: &mut Sampler) -> Color {
+        if lights.is_empty() {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+
+        let direction = lights.random(&hr.p, r.time, sampler);
+        let light_pdf = lights.pdf_value(&hr.p, &direction, r.time);
+        let f = hr.material.eval(r, hr, &direction);
+
+        if light_pdf <= 0.0 || f.near_zero() || !hr.same_side(&direction) {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+
+        // The first surface along the shadow ray is the one that gets seen.
+        // Unless it is one of the lights it occludes them, even when it is
+        // emissive itself: its light is only gathered by following the BSDF.
+        let shadow_ray = Ray::with_time(hr.p.clone(), direction.clone(), r.time);
+        let light = match world.hit(&shadow_ray, Interval::new(0.001, f64::INFINITY)) {
+            Some(light) if is_light(&shadow_ray, &light, lights) => light,
+            _ => return Color::new(0.0, 0.0, 0.0),
+        };
+
+        let bsdf_pdf = hr.material.pdf(r, hr, &direction);
+        let weight = power_heuristic(light_pdf, bsdf_pdf) / light_pdf;
+        let transmittance = self.transmittance(&shadow_ray, Interval::new(0.001, light.t), sampler);
+
+        (weight * transmittance) * (f * &light.material.emitted(&light))
+    }
+
+    // Closest point where one of the media scatters the ray. Sampling each
+    // medium only up to the closest point found so far picks the first
+    // collision among all of them. Media whose box the ray misses are skipped.
+    fn sample_media(&self, r: &Ray, ray_t: Interval, sampler: &mut Sampler) -> Option<HitRecord> {
+        let mut closest: Option<HitRecord> = None;
+        for medium in &self.media {
+            let t_max = closest.as_ref().map_or(ray_t.max, |hr| hr.t);
+            let ray_t = Interval::new(ray_t.min, t_max);
+            if !medium.bounding_box().hit(r, ray_t) {
+                continue;
+            }
+            if let Some(hr) = medium.sample(r, ray_t, sampler) {
+                closest = Some(hr);
+            }
+        }
+        closest
+    }
+
+    fn transmittance(&self, r: &Ray, ray_t: Interval, sampler: &mut Sampler) -> f64 {
+        self.media.iter()
+            .filter(|medium| medium.bounding_box().hit(r, ray_t))
+            .map(|medium| medium.transmittance(r, ray_t, sampler))
+            .product()
+    }
+
+    fn get_ray(&self, i: u32, j: u32, sampler: &mut Sampler) -> Ray {
+        let offset = Self::sample_square(sampler);
+        self.ray_through(i, j, &offset, sampler)
     }
 
-    fn get_ray(&self, i: u32, j: u32) -> Ray {
-        let offset = Self::sample_square();
-        let pixel_sample = &self.pixel00_loc 
+    // Ray through the given offset from the center of pixel i, j.
+    fn ray_through(&self, i: u32, j: u32, offset: &Vec3, sampler: &mut Sampler) -> Ray {
+        let pixel_sample = &self.pixel00_loc
                                 + ((i as f64 + offset.x) * &self.pixel_delta_u)
                                 + ((j as f64 + offset.y) * &self.pixel_delta_v);
         let ray_origin = if self.defocus_angle <= 0.0 {
                 self.center.clone()
             } else {
-                self.defocus_disk_sample()
+                self.defocus_disk_sample(sampler)
             };
         let ray_direction = pixel_sample - &ray_origin;
-        Ray::new(ray_origin, ray_direction)
+        Ray::with_time(ray_origin, ray_direction, self.sample_time(sampler))
     }
 
-    fn sample_square() -> Vec3 {
-        Vec3::new(Vec3::random_double() - 0.5, Vec3::random_double() - 0.5, 0.0)
+    // Uniform over the shutter interval. Nothing is drawn when the shutter
+    // is instantaneous, so renders without motion blur are unchanged.
+    fn sample_time(&self, sampler: &mut Sampler) -> f64 {
+        if self.shutter_close > self.shutter_open {
+            self.shutter_open + Vec3::random_double(sampler) * (self.shutter_close - self.shutter_open)
+        } else {
+            self.shutter_open
+        }
+    }
+
+    fn sample_square(sampler: &mut Sampler) -> Vec3 {
+        Vec3::new(Vec3::random_double(sampler) - 0.5, Vec3::random_double(sampler) - 0.5, 0.0)
     }
 
-    fn defocus_disk_sample(&self) -> Vec3 {
-        let p = Vec3::random_in_unit_disk();
+    fn defocus_disk_sample(&self, sampler: &mut Sampler) -> Vec3 {
+        let p = Vec3::random_in_unit_disk(sampler);
         &self.center + (p.x * &self.defocus_disk_u) + (p.y * &self.defocus_disk_v)
     }
 
 }
+
+// Whether `hr`, the closest hit along `r` in the world, is on one of the
+// lights: the closest of them along the ray must be the same object. Objects
+// are told apart by their IDs, so surfaces without one never count as lights.
+fn is_light(r: &Ray, hr: &HitRecord, lights: &HittableList) -> bool {
+    hr.object_id != 0 && lights.hit(r, Interval::new(0.001, f64::INFINITY)).is_some_and(|light| light.object_id == hr.object_id)
+}
+
+// Weight of a sample taken with density `a` against another strategy that
+// could have produced it with density `b`.
+fn power_heuristic(a: f64, b: f64) -> f64 {
+    let a2 = a * a;
+    let b2 = b * b;
+    if a2 + b2 == 0.0 { 0.0 } else { a2 / (a2 + b2) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sphere::Sphere;
+    use crate::material::{Lambertian, Dielectric, DiffuseLight, Isotropic};
+    use crate::hittable::Identified;
+    use crate::medium::{ConstantMedium, GridMedium, Emission};
+    use crate::voxel::VoxelGrid;
+    use crate::aabb::Aabb;
+    use crate::background::SolidBackground;
+
+    fn scene() -> (HittableList, Camera) {
+        let world: HittableList = vec![
+            Box::new(Sphere::new(Point3::new(0.0, -100.5, -1.0), 100.0, Arc::new(Lambertian::new(Color::new(0.8, 0.8, 0.0))))),
+            Box::new(Sphere::new(Point3::new(0.0, 0.0, -1.0), 0.5, Arc::new(Dielectric::new(1.5)))),
+        ];
+        let camera = Camera::new(1.0, 16, 8, 8, 90.0,
+                                 Point3::new(0.0, 0.0, 0.0), Point3::new(0.0, 0.0, -1.0), Vec3::new(0.0, 1.0, 0.0),
+                                 2.0, 1.0);
+        (world, camera)
+    }
+
+    fn render(seed: u64, threads: usize) -> Framebuffer {
+        let (world, mut camera) = scene();
+        camera.seed = seed;
+        camera.threads = threads;
+        camera.render(&world, &vec![])
+    }
+
+    #[test]
+    fn test_render_is_reproducible() {
+        let image = render(42, 1);
+
+        assert_eq!(image, render(42, 1));
+        assert_eq!(image, render(42, 4));
+        assert_ne!(image, render(43, 1));
+    }
+
+    #[test]
+    fn test_progressive_matches_single_pass() {
+        let (world, camera) = scene();
+        let mut snapshots = 0;
+
+        let mut progressive = scene().1;
+        progressive.pass_samples = 3;
+
+        let image = progressive.render_progressive(&world, &vec![], |accumulator| {
+            snapshots += 1;
+            assert!(accumulator.pixels.iter().all(|pixel| pixel.samples == 3 * snapshots));
+        }).estimate();
+
+        assert_eq!(snapshots, 2);
+        assert_eq!(image, camera.render(&world, &vec![]));
+    }
+
+    #[test]
+    fn test_resume_matches_uninterrupted() {
+        for noise_threshold in [0.0, 0.02] {
+            let (world, mut camera) = scene();
+            camera.samples_per_pixel = 64;
+            camera.pass_samples = 8;
+            camera.min_samples = 8;
+            camera.noise_threshold = noise_threshold;
+
+            // Interrupt the render after its third pass by saving a checkpoint.
+            let mut checkpoint = vec![];
+            let expected = camera.render_progressive(&world, &vec![], |accumulator| {
+                if accumulator.done == 24 {
+                    crate::checkpoint::write(accumulator, &mut checkpoint).unwrap();
+                }
+            }).estimate();
+
+            let accumulator = crate::checkpoint::read(&mut checkpoint.as_slice()).unwrap();
+            assert!(camera.can_resume(&accumulator));
+
+            let resumed = camera.resume(&world, &vec![], accumulator, |_| {}).estimate();
+            assert_eq!(resumed, expected);
+        }
+    }
+
+    #[test]
+    fn test_aovs() {
+        let material = Arc::new(Lambertian::new(Color::new(0.8, 0.4, 0.0)));
+        let world: HittableList = vec![
+            Box::new(Identified::new(Sphere::new(Point3::new(0.0, 0.0, -1.0), 0.5, material), 7, 3)),
+        ];
+        let camera = Camera::new(1.0, 17, 1, 8, 90.0,
+                                 Point3::new(0.0, 0.0, 0.0), Point3::new(0.0, 0.0, -1.0), Vec3::new(0.0, 1.0, 0.0),
+                                 0.0, 1.0);
+
+        let aovs = camera.render_aovs(&world);
+
+        // The center pixel looks right at the sphere.
+        assert!((aovs.normal.get(8, 8) - Vec3::new(0.0, 0.0, 1.0)).length() < 0.05);
+        assert!((aovs.depth.get(8, 8).x - 0.5).abs() < 0.01);
+        assert!((aovs.albedo.get(8, 8) - Color::new(0.8, 0.4, 0.0)).length() < 1e-9);
+        assert_eq!(aovs.material_id.get(8, 8).x, 3.0);
+        assert_eq!(aovs.object_id.get(8, 8).x, 7.0);
+
+        // Nothing in the corners.
+        assert_eq!(aovs.normal.get(0, 0), &Vec3::new(0.0, 0.0, 0.0));
+        assert_eq!(aovs.depth.get(0, 0).x, MISS_DEPTH);
+        assert_eq!(aovs.albedo.get(0, 0), &Color::new(0.0, 0.0, 0.0));
+        assert_eq!(aovs.object_id.get(0, 0).x, 0.0);
+    }
+
+    #[test]
+    fn test_diffuse_light() {
+        // A light filling the view in the dark, seen by camera rays and not
+        // sampled as a light.
+        let (_, mut camera) = scene();
+        let world: HittableList = vec![
+            Box::new(Sphere::new(Point3::new(0.0, 0.0, -1.0), 0.5, Arc::new(DiffuseLight::new(Color::new(2.0, 1.0, 0.5))))),
+        ];
+        camera.background = Arc::new(SolidBackground::new(Color::new(0.0, 0.0, 0.0)));
+        camera.defocus_angle = 0.0;
+        camera.initialize();
+
+        let image = camera.render(&world, &vec![]);
+        assert_eq!(image.get(8, 8), &Color::new(2.0, 1.0, 0.5));
+        assert_eq!(image.get(0, 0), &Color::new(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_emissive_occluder() {
+        // A point on the floor under a light, with an emissive sphere left
+        // out of the lights hiding it completely.
+        let lamp = || Identified::new(Sphere::new(Point3::new(0.0, 10.0, 0.0), 1.0, Arc::new(DiffuseLight::new(Color::new(50.0, 50.0, 50.0)))), 1, 1);
+        let glow = Identified::new(Sphere::new(Point3::new(0.0, 5.0, 0.0), 2.0, Arc::new(DiffuseLight::new(Color::new(1.0, 1.0, 1.0)))), 2, 2);
+        let floor = || Identified::new(Sphere::new(Point3::new(0.0, -100.0, 0.0), 100.0, Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)))), 3, 3);
+        let lights: HittableList = vec![Box::new(lamp())];
+        let (_, camera) = scene();
+        let mut sampler = Sampler::new(0);
+
+        let direct = |world: &HittableList, sampler: &mut Sampler| {
+            let r = Ray::new(Point3::new(0.5, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+            let hr = world.hit(&r, Interval::new(0.001, f64::INFINITY)).unwrap();
+            (0..100).map(|_| camera.sample_lights(&r, &hr, world, &lights, sampler).x).sum::<f64>()
+        };
+
+        let open: HittableList = vec![Box::new(floor()), Box::new(lamp())];
+        assert!(direct(&open, &mut sampler) > 0.0);
+
+        // Light sampling doesn't pick up the glow, only the BSDF paths do.
+        let occluded: HittableList = vec![Box::new(floor()), Box::new(lamp()), Box::new(glow)];
+        assert_eq!(direct(&occluded, &mut sampler), 0.0);
+
+        // Nor does it when the glow coincides with the light and is found
+        // first, being hit at the same distance doesn't make it the light.
+        let coinciding = Identified::new(Sphere::new(Point3::new(0.0, 10.0, 0.0), 1.0, Arc::new(DiffuseLight::new(Color::new(1.0, 1.0, 1.0)))), 2, 2);
+        let hidden: HittableList = vec![Box::new(floor()), Box::new(coinciding), Box::new(lamp())];
+        assert_eq!(direct(&hidden, &mut sampler), 0.0);
+    }
+
+    #[test]
+    fn test_shutter() {
+        let (_, mut camera) = scene();
+        camera.shutter_open = 0.25;
+        camera.shutter_close = 0.75;
+        let mut sampler = Sampler::new(0);
+
+        let times: Vec<f64> = (0..1000).map(|_| camera.get_ray(3, 5, &mut sampler).time).collect();
+        assert!(times.iter().all(|t| (0.25..=0.75).contains(t)));
+        assert!(times.iter().any(|t| *t < 0.3) && times.iter().any(|t| *t > 0.7));
+        assert!((times.iter().sum::<f64>() / 1000.0 - 0.5).abs() < 0.02);
+
+        camera.shutter_close = 0.25;
+        assert!((0..10).all(|_| camera.get_ray(3, 5, &mut sampler).time == 0.25));
+    }
+
+    #[test]
+    fn test_absorbing_medium() {
+        // A black fog around the camera, everything scattered is lost and
+        // only the light going through reaches the white background.
+        let (_, mut camera) = scene();
+        let phase = Arc::new(Isotropic::new(Color::new(0.0, 0.0, 0.0)));
+        let boundary = Sphere::new(Point3::new(0.0, 0.0, 0.0), 1.0, phase.clone());
+        camera.media.push(Box::new(ConstantMedium::new(boundary, 0.7, phase)));
+        camera.background = Arc::new(SolidBackground::new(Color::new(1.0, 1.0, 1.0)));
+        camera.samples_per_pixel = 64;
+
+        let image = camera.render(&vec![], &vec![]);
+        let mut sum = 0.0;
+        for j in 0..image.height {
+            for i in 0..image.width {
+                sum += image.get(i, j).x;
+            }
+        }
+        let average = sum / (image.width * image.height) as f64;
+        assert!((average - (-0.7_f64).exp()).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_glowing_medium() {
+        // So thick that every ray collides right away, picking up the
+        // emission and then absorbed.
+        let (_, mut camera) = scene();
+        let bounds = Aabb::from_points(&Point3::new(-1.0, -1.0, -1.0), &Point3::new(1.0, 1.0, 1.0));
+        let grid = Arc::new(VoxelGrid::new(1, 1, 1, vec![1.0]).unwrap());
+        let emission = Emission::Scaled { grid: grid.clone(), color: Color::new(0.5, 0.25, 1.0) };
+        let phase = Arc::new(Isotropic::new(Color::new(0.0, 0.0, 0.0)));
+        camera.media.push(Box::new(GridMedium::new(bounds, grid, 100.0, phase, Some(emission))));
+
+        let image = camera.render(&vec![], &vec![]);
+        for j in 0..image.height {
+            for i in 0..image.width {
+                assert!((image.get(i, j) - Color::new(0.5, 0.25, 1.0)).length() < 1e-9);
+            }
+        }
+    }
+
+    // Fails the test as soon as it is queried.
+    struct Untouchable(Aabb);
+
+    impl Medium for Untouchable {
+
+        fn sample(&self, _r: &Ray, _ray_t: Interval, _sampler: &mut Sampler) -> Option<HitRecord> {
+            panic!("sampled a medium the ray misses");
+        }
+
+        fn transmittance(&self, _r: &Ray, _ray_t: Interval, _sampler: &mut Sampler) -> f64 {
+            panic!("computed the transmittance of a medium the ray misses");
+        }
+
+        fn bounding_box(&self) -> Aabb {
+            self.0
+        }
+
+    }
+
+    #[test]
+    fn test_media_bounds() {
+        // A box off to the side and one behind the end of the ray.
+        let (_, mut camera) = scene();
+        camera.media.push(Box::new(Untouchable(Aabb::from_points(&Point3::new(5.0, -1.0, -1.0), &Point3::new(6.0, 1.0, 1.0)))));
+        camera.media.push(Box::new(Untouchable(Aabb::from_points(&Point3::new(-1.0, -1.0, -6.0), &Point3::new(1.0, 1.0, -5.0)))));
+        let fog = Arc::new(Isotropic::new(Color::new(0.5, 0.5, 0.5)));
+        camera.media.push(Box::new(ConstantMedium::new(Sphere::new(Point3::new(0.0, 0.0, -2.0), 1.0, fog.clone()), 1.0, fog)));
+
+        let r = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let mut sampler = Sampler::new(0);
+        assert!((camera.transmittance(&r, Interval::new(0.001, 4.0), &mut sampler) - (-2.0_f64).exp()).abs() < 1e-9);
+        for _ in 0..100 {
+            if let Some(hr) = camera.sample_media(&r, Interval::new(0.001, 4.0), &mut sampler) {
+                assert!((1.0..=3.0).contains(&hr.t));
+            }
+        }
+    }
+
+    #[test]
+    fn test_adaptive_sampling() {
+        let (world, mut camera) = scene();
+        camera.samples_per_pixel = 256;
+        camera.min_samples = 8;
+        camera.noise_threshold = 0.02;
+
+        let accumulator = camera.render_progressive(&world, &vec![], |_| {});
+        let samples: Vec<u32> = accumulator.pixels.iter().map(|pixel| pixel.samples).collect();
+
+        // The smooth sky stops early while the glass sphere keeps sampling.
+        assert!(samples.iter().all(|n| (8..=256).contains(n)));
+        assert_eq!(samples[0], 8);
+        assert!(samples.iter().any(|n| *n > 8));
+        assert!(samples.iter().sum::<u32>() < 16 * 16 * 256 / 8);
+
+        for pixel in &accumulator.pixels {
+            assert!(pixel.samples == 256 || pixel.relative_error() < 0.02);
+        }
+    }
+
+}
@@ -0,0 +1,160 @@
+// Render checkpoints, the whole state of an Accumulator in a little endian
+// binary file so an interrupted render can be resumed exactly.
+//
+//     "RTCK", version: u32, width: u32, height: u32, seed: u64, done: u32
+//     for every pixel, row by row:
+//         sum: 3 x f64, samples: u32, mean: f64, m2: f64, converged: u8,
+//         sampler state: u64, sampler increment: u64
+
+use crate::accumulator::{Accumulator, Pixel};
+use crate::color::Color;
+use crate::sampler::Sampler;
+
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+const MAGIC: &[u8; 4] = b"RTCK";
+const VERSION: u32 = 1;
+
+// The file is written next to `path` first and then renamed over it, so a
+// crash while saving leaves the previous checkpoint intact.
+pub fn save(accumulator: &Accumulator, path: &Path) -> io::Result<()> {
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+
+    let mut out = BufWriter::new(File::create(&temporary)?);
+    write(accumulator, &mut out)?;
+    out.into_inner()?.sync_all()?;
+
+    fs::rename(&temporary, path)
+}
+
+pub fn load(path: &Path) -> io::Result<Accumulator> {
+    read(&mut BufReader::new(File::open(path)?))
+}
+
+pub fn write(accumulator: &Accumulator, out: &mut impl Write) -> io::Result<()> {
+    out.write_all(MAGIC)?;
+    out.write_all(&VERSION.to_le_bytes())?;
+    out.write_all(&accumulator.width.to_le_bytes())?;
+    out.write_all(&accumulator.height.to_le_bytes())?;
+    out.write_all(&accumulator.seed.to_le_bytes())?;
+    out.write_all(&accumulator.done.to_le_bytes())?;
+
+    for pixel in &accumulator.pixels {
+        for component in [pixel.sum.x, pixel.sum.y, pixel.sum.z] {
+            out.write_all(&component.to_le_bytes())?;
+        }
+        out.write_all(&pixel.samples.to_le_bytes())?;
+        out.write_all(&pixel.mean.to_le_bytes())?;
+        out.write_all(&pixel.m2.to_le_bytes())?;
+        out.write_all(&[pixel.converged as u8])?;
+
+        let (state, increment) = pixel.sampler.state();
+        out.write_all(&state.to_le_bytes())?;
+        out.write_all(&increment.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+pub fn read(input: &mut impl Read) -> io::Result<Accumulator> {
+    let mut magic = [0u8; 4];
+    input.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid("not a render checkpoint"));
+    }
+    let version = read_u32(input)?;
+    if version != VERSION {
+        return Err(invalid(&format!("unsupported checkpoint version {}", version)));
+    }
+
+    let width = read_u32(input)?;
+    let height = read_u32(input)?;
+    let seed = read_u64(input)?;
+    let done = read_u32(input)?;
+
+    // Not preallocated, a corrupted header could ask for any size.
+    let mut pixels = vec![];
+    for _ in 0..width as usize * height as usize {
+        let sum = Color::new(read_f64(input)?, read_f64(input)?, read_f64(input)?);
+        let samples = read_u32(input)?;
+        let mean = read_f64(input)?;
+        let m2 = read_f64(input)?;
+
+        let mut converged = [0u8; 1];
+        input.read_exact(&mut converged)?;
+
+        let state = read_u64(input)?;
+        let increment = read_u64(input)?;
+
+        pixels.push(Pixel {
+            sum,
+            samples,
+            mean,
+            m2,
+            converged: converged[0] != 0,
+            sampler: Sampler::from_state(state, increment),
+        });
+    }
+
+    Ok(Accumulator {
+        width,
+        height,
+        seed,
+        done,
+        pixels,
+    })
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn read_u32(input: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    input.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64(input: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0u8; 8];
+    input.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_f64(input: &mut impl Read) -> io::Result<f64> {
+    Ok(f64::from_bits(read_u64(input)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let mut accumulator = Accumulator::new(3, 2, 11);
+        accumulator.done = 4;
+        for (index, pixel) in accumulator.pixels.iter_mut().enumerate() {
+            for k in 0..=index {
+                pixel.add(Color::new(k as f64, 0.5, 1e10));
+                pixel.sampler.random_double();
+            }
+        }
+        accumulator.pixels[2].converged = true;
+
+        let mut bytes = vec![];
+        write(&accumulator, &mut bytes).unwrap();
+        let restored = read(&mut bytes.as_slice()).unwrap();
+
+        assert_eq!((restored.width, restored.height, restored.seed, restored.done), (3, 2, 11, 4));
+        for (e, p) in accumulator.pixels.iter().zip(restored.pixels.iter()) {
+            assert_eq!((&e.sum, e.samples, e.mean, e.m2, e.converged, &e.sampler),
+                       (&p.sum, p.samples, p.mean, p.m2, p.converged, &p.sampler));
+        }
+
+        assert!(read(&mut &bytes[..bytes.len() - 1]).is_err());
+        assert!(read(&mut &b"PNG?"[..]).is_err());
+    }
+
+}
@@ -0,0 +1,304 @@
+use raytracer::camera::{Camera, CHECKPOINT_PASS_SAMPLES};
+use raytracer::output::OutputFormat;
+use raytracer::tonemap::{ToneMap, ToneMapOperator};
+use raytracer::aov::Aov;
+use raytracer::denoise::Denoiser;
+
+use clap::{Parser, ValueEnum};
+
+use std::path::{Path, PathBuf};
+
+/// Toy raytracer
+#[derive(Parser)]
+#[command(version)]
+pub struct Args {
+    /// Scene description file, the built-in random spheres scene is rendered when omitted
+    pub scene: Option<PathBuf>,
+
+    /// Output image path, repeat it to write several files such as a PNG and an EXR
+    #[arg(short, long, default_value = "img.png")]
+    pub output: Vec<PathBuf>,
+
+    /// Output image format of every output, deduced from their extension when omitted
+    #[arg(short, long, value_enum)]
+    pub format: Option<OutputFormat>,
+
+    /// Also write an output variable, as NAME=PATH with NAME one of normal, depth, albedo, material-id or object-id
+    #[arg(long, value_parser = aov_output)]
+    pub aov: Vec<(Aov, PathBuf)>,
+
+    /// Denoise the image with a filter guided by the normal, depth and albedo AOVs
+    #[arg(long)]
+    pub denoise: bool,
+
+    /// Number of passes of the denoising filter, each one doubles its reach
+    #[arg(long, default_value_t = 5, value_parser = clap::value_parser!(u32).range(1..))]
+    pub denoise_iterations: u32,
+
+    /// Denoising strength, higher values remove more noise but also more detail
+    #[arg(long, default_value_t = 1.0, value_parser = positive)]
+    pub denoise_strength: f64,
+
+    /// Tone mapping operator for 8-bit outputs
+    #[arg(short, long, value_enum, default_value = "clamp")]
+    pub tonemap: ToneMapOperator,
+
+    /// Exposure adjustment in stops for 8-bit outputs
+    #[arg(short, long, default_value_t = 0.0, allow_negative_numbers = true)]
+    pub exposure: f64,
+
+    /// Radiance mapped to white by the extended-reinhard operator
+    #[arg(long, default_value_t = 4.0, value_parser = positive)]
+    pub white_point: f64,
+
+    /// Image width in pixels
+    #[arg(short, long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub width: Option<u32>,
+
+    /// Image aspect ratio (width / height)
+    #[arg(short, long, value_parser = positive)]
+    pub aspect_ratio: Option<f64>,
+
+    /// Samples per pixel
+    #[arg(short, long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub samples_per_pixel: Option<u32>,
+
+    /// Maximum number of ray bounces
+    #[arg(short = 'd', long)]
+    pub max_depth: Option<u32>,
+
+    /// Number of render threads, 0 uses every available core
+    #[arg(short = 'j', long)]
+    pub threads: Option<usize>,
+
+    /// Render progressively in passes of this many samples per pixel, writing the outputs as they improve
+    #[arg(short, long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub pass_samples: Option<u32>,
+
+    /// Stop sampling pixels once the relative error of their mean falls below this threshold, samples per pixel is then the maximum
+    #[arg(short, long, value_parser = non_negative)]
+    pub noise_threshold: Option<f64>,
+
+    /// Samples every pixel gets before adaptive sampling can stop it
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub min_samples: Option<u32>,
+
+    /// Also write the number of samples of every pixel as a heatmap image
+    #[arg(long)]
+    pub sample_heatmap: Option<PathBuf>,
+
+    /// Save the render state to this file along with every snapshot and at the end, rendering in passes of 16 samples unless --pass-samples is given
+    #[arg(long)]
+    pub checkpoint: Option<PathBuf>,
+
+    /// Continue the render saved in this checkpoint, with the same scene and settings
+    #[arg(long)]
+    pub resume: Option<PathBuf>,
+
+    /// Minimum number of seconds between two progressive snapshots
+    #[arg(long, default_value_t = 5.0, value_parser = non_negative)]
+    pub snapshot_interval: f64,
+
+    /// Random seed, renders with the same seed and settings are identical
+    #[arg(long)]
+    pub seed: Option<u64>,
+}
+
+impl Args {
+
+    // Applies the command line overrides on top of the scene camera.
+    pub fn apply(&self, camera: &mut Camera) {
+        if let Some(width) = self.width {
+            camera.image_width = width;
+        }
+        if let Some(aspect_ratio) = self.aspect_ratio {
+            camera.aspect_ratio = aspect_ratio;
+        }
+        if let Some(samples_per_pixel) = self.samples_per_pixel {
+            camera.samples_per_pixel = samples_per_pixel;
+        }
+        if let Some(max_depth) = self.max_depth {
+            camera.max_depth = max_depth;
+        }
+        if let Some(threads) = self.threads {
+            camera.threads = threads;
+        }
+        if let Some(seed) = self.seed {
+            camera.seed = seed;
+        }
+        if let Some(pass_samples) = self.pass_samples {
+            camera.pass_samples = pass_samples;
+        }
+        if let Some(noise_threshold) = self.noise_threshold {
+            camera.noise_threshold = noise_threshold;
+        }
+        if let Some(min_samples) = self.min_samples {
+            camera.min_samples = min_samples;
+        }
+        if self.checkpoint.is_some() && camera.pass_samples == 0 {
+            camera.pass_samples = CHECKPOINT_PASS_SAMPLES;
+        }
+        camera.initialize();
+    }
+
+    pub fn denoiser(&self) -> Option<Denoiser> {
+        if !self.denoise {
+            return None;
+        }
+        let default = Denoiser::default();
+        Some(Denoiser {
+            iterations: self.denoise_iterations,
+            color_sigma: default.color_sigma * self.denoise_strength,
+            ..default
+        })
+    }
+
+    pub fn tone_map(&self) -> ToneMap {
+        ToneMap::new(self.tonemap, self.exposure, self.white_point)
+    }
+
+    // The heatmap format, checked along with the other outputs before
+    // rendering.
+    pub fn heatmap_format(&self) -> Result<Option<(&Path, OutputFormat)>, String> {
+        match &self.sample_heatmap {
+            Some(path) => OutputFormat::from_path(path)
+                .map(|format| Some((path.as_path(), format)))
+                .ok_or_else(|| format!("can't deduce the image format of '{}'", path.display())),
+            None => Ok(None),
+        }
+    }
+
+    // Every output path along with the format to write it in.
+    pub fn outputs(&self) -> Result<Vec<(&Path, OutputFormat)>, String> {
+        self.output.iter().map(|path| {
+            let format = match self.format {
+                Some(format) => Some(format),
+                None => OutputFormat::from_path(path),
+            };
+            format
+                .map(|format| (path.as_path(), format))
+                .ok_or_else(|| format!("can't deduce the image format of '{}', use --format", path.display()))
+        }).collect()
+    }
+
+    // Every AOV output along with the format to write it in, deduced from the
+    // path alone since --format is meant for the beauty outputs.
+    pub fn aov_outputs(&self) -> Result<Vec<(Aov, &Path, OutputFormat)>, String> {
+        self.aov.iter().map(|(aov, path)| {
+            OutputFormat::from_path(path)
+                .map(|format| (*aov, path.as_path(), format))
+                .ok_or_else(|| format!("can't deduce the image format of '{}'", path.display()))
+        }).collect()
+    }
+
+}
+
+fn positive(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(x) if x > 0.0 && x.is_finite() => Ok(x),
+        _ => Err(format!("'{}' is not a positive number", s)),
+    }
+}
+
+fn non_negative(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(x) if x >= 0.0 && x.is_finite() => Ok(x),
+        _ => Err(format!("'{}' is not a non negative number", s)),
+    }
+}
+
+fn aov_output(s: &str) -> Result<(Aov, PathBuf), String> {
+    let (name, path) = s.split_once('=').ok_or_else(|| format!("expected NAME=PATH, found '{}'", s))?;
+    let aov = Aov::from_str(name, true)?;
+    Ok((aov, PathBuf::from(path)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use raytracer::vec3::{Vec3, Point3};
+
+    fn parse(args: &[&str]) -> Args {
+        Args::try_parse_from([&["raytracer"], args].concat()).unwrap()
+    }
+
+    fn camera() -> Camera {
+        Camera::new(2.0, 200, 10, 10, 90.0,
+                    Point3::new(0.0, 0.0, 0.0), Point3::new(0.0, 0.0, -1.0), Vec3::new(0.0, 1.0, 0.0),
+                    0.0, 1.0)
+    }
+
+    #[test]
+    fn test_outputs() {
+        let args = parse(&["-o", "a.png", "-o", "b.EXR", "-o", "c.Jpeg"]);
+        let formats: Vec<OutputFormat> = args.outputs().unwrap().into_iter().map(|(_, format)| format).collect();
+        assert_eq!(formats, vec![OutputFormat::Png, OutputFormat::Exr, OutputFormat::Jpeg]);
+
+        // The default output.
+        assert_eq!(parse(&[]).outputs().unwrap(), vec![(Path::new("img.png"), OutputFormat::Png)]);
+
+        let args = parse(&["-o", "image.tga"]);
+        assert_eq!(args.outputs().unwrap_err(), "can't deduce the image format of 'image.tga', use --format");
+
+        // --format applies to every output whatever its extension, but not
+        // to the AOVs.
+        let args = parse(&["-o", "image.tga", "-o", "b.png", "--format", "pfm", "--aov", "depth=depth.exr"]);
+        assert_eq!(args.outputs().unwrap(), vec![(Path::new("image.tga"), OutputFormat::Pfm), (Path::new("b.png"), OutputFormat::Pfm)]);
+        assert_eq!(args.aov_outputs().unwrap(), vec![(Aov::Depth, Path::new("depth.exr"), OutputFormat::Exr)]);
+
+        let args = parse(&["--format", "png", "--aov", "depth=depth"]);
+        assert_eq!(args.aov_outputs().unwrap_err(), "can't deduce the image format of 'depth'");
+    }
+
+    #[test]
+    fn test_apply() {
+        // Nothing given, the scene camera is kept.
+        let mut scene = camera();
+        parse(&[]).apply(&mut scene);
+        assert_eq!((scene.image_width, scene.aspect_ratio, scene.samples_per_pixel, scene.max_depth), (200, 2.0, 10, 10));
+
+        // What is given overrides the scene, the rest is kept.
+        let mut scene = camera();
+        parse(&["-w", "64", "-s", "4", "-j", "3", "--seed", "9"]).apply(&mut scene);
+        assert_eq!((scene.image_width, scene.aspect_ratio, scene.samples_per_pixel, scene.max_depth), (64, 2.0, 4, 10));
+        assert_eq!((scene.threads, scene.seed), (3, 9));
+
+        let mut scene = camera();
+        parse(&["--aspect-ratio", "0.5", "--max-depth", "0"]).apply(&mut scene);
+        assert_eq!((scene.image_width, scene.aspect_ratio, scene.max_depth), (200, 0.5, 0));
+
+        // Checkpoints need passes, the default size is used unless one is
+        // given.
+        let mut scene = camera();
+        parse(&["--checkpoint", "render.ck"]).apply(&mut scene);
+        assert_eq!(scene.pass_samples, CHECKPOINT_PASS_SAMPLES);
+        let mut scene = camera();
+        parse(&["--checkpoint", "render.ck", "--pass-samples", "4"]).apply(&mut scene);
+        assert_eq!(scene.pass_samples, 4);
+    }
+
+    #[test]
+    fn test_invalid_values() {
+        let rejected: [&[&str]; 9] = [
+            &["--width", "0"],
+            &["--width", "-3"],
+            &["--samples-per-pixel", "0"],
+            &["--aspect-ratio", "0"],
+            &["--aspect-ratio", "-1.5"],
+            &["--aspect-ratio", "inf"],
+            &["--aspect-ratio", "wide"],
+            &["--noise-threshold", "-0.1"],
+            &["--aov", "depth"],
+        ];
+
+        for args in rejected {
+            assert!(Args::try_parse_from([&["raytracer"], args].concat()).is_err(), "{:?} was accepted", args);
+        }
+
+        assert_eq!(positive("0.5"), Ok(0.5));
+        assert!(positive("NaN").is_err());
+        assert_eq!(non_negative("0"), Ok(0.0));
+        assert!(non_negative("-1").is_err());
+    }
+
+}
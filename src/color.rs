@@ -1,6 +1,4 @@
-
 use crate::vec3::Vec3;
-use crate::interval::Interval;
 
 use image::Rgb;
 
@@ -8,27 +6,104 @@ pub type Color = Vec3;
 
 impl Color {
 
+    // Quantizes a linear display color in [0, 1], after tone mapping, to
+    // 8-bit sRGB.
     pub fn write_color(&self) -> Rgb<u8> {
 
-        let interval = Interval::new(0.0, 0.999);
+        let quantize = |x: f64| (255.0 * Color::linear_to_srgb(x.clamp(0.0, 1.0))).round() as u8;
+
+        Rgb([quantize(self.x), quantize(self.y), quantize(self.z)])
+    }
 
-        let r = 256.0 * interval.clamp(Color::linear_to_gamma(self.x));
-        let g = 256.0 * interval.clamp(Color::linear_to_gamma(self.y));
-        let b = 256.0 * interval.clamp(Color::linear_to_gamma(self.z));
+    // Relative luminance of a linear Rec. 709 color.
+    pub fn luminance(&self) -> f64 {
+        0.2126 * self.x + 0.7152 * self.y + 0.0722 * self.z
+    }
 
-        let ri: u8 = r.floor() as u8;
-        let gi: u8 = g.floor() as u8;
-        let bi: u8 = b.floor() as u8;
+    // The piecewise sRGB OETF from IEC 61966-2-1.
+    pub fn linear_to_srgb(linear_component: f64) -> f64 {
 
-        Rgb { 0: [ri, gi, bi] }
+        if linear_component <= 0.0031308 {
+            12.92 * linear_component
+        } else {
+            1.055 * linear_component.powf(1.0 / 2.4) - 0.055
+        }
     }
 
-    fn linear_to_gamma(linear_component: f64) -> f64 {
+    // Inverse of linear_to_srgb, for 8-bit images read as textures.
+    pub fn srgb_to_linear(srgb_component: f64) -> f64 {
 
-        if linear_component > 0.0 {
-            return linear_component.sqrt();
+        if srgb_component <= 0.04045 {
+            srgb_component / 12.92
         } else {
-            return 0.0;
+            ((srgb_component + 0.055) / 1.055).powf(2.4)
         }
     }
+
+    // Hue of the light of a black body at `kelvin`, from Planck's law at
+    // wavelengths standing for the red, green and blue primaries. Relative
+    // to a black body at 6500 K, which is white, and scaled to a largest
+    // component of 1.
+    pub fn blackbody(kelvin: f64) -> Color {
+        if kelvin <= 0.0 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+
+        // Logarithm of the spectral radiance, up to a constant, at a
+        // wavelength in nanometers. Computed in log space so that low
+        // temperatures don't underflow.
+        let log_planck = |nm: f64, kelvin: f64| {
+            let x = 1.4388e7 / (nm * kelvin);
+            -5.0 * nm.ln() - (x + (-(-x).exp()).ln_1p())
+        };
+        let log_relative = |nm: f64| log_planck(nm, kelvin) - log_planck(nm, 6500.0);
+
+        let (r, g, b) = (log_relative(600.0), log_relative(550.0), log_relative(450.0));
+        let max = r.max(g).max(b);
+        Color::new((r - max).exp(), (g - max).exp(), (b - max).exp())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_linear_to_srgb() {
+        let data: [(f64, f64); 6] = [
+            (0.0, 0.0),
+            (0.001, 0.01292),
+            (0.0031308, 0.040449936),
+            (0.18, 0.461356129500),
+            (0.5, 0.735356983052),
+            (1.0, 1.0),
+        ];
+        for (linear, srgb) in data {
+            assert!((Color::linear_to_srgb(linear) - srgb).abs() < 1e-9, "srgb({}) = {}", linear, Color::linear_to_srgb(linear));
+            assert!((Color::srgb_to_linear(srgb) - linear).abs() < 1e-9, "linear({}) = {}", srgb, Color::srgb_to_linear(srgb));
+        }
+    }
+
+    #[test]
+    fn test_blackbody() {
+        assert!((Color::blackbody(6500.0) - Color::new(1.0, 1.0, 1.0)).length() < 1e-9);
+
+        // Red hot, and getting bluer as it heats up.
+        let red = Color::blackbody(1200.0);
+        assert_eq!(red.x, 1.0);
+        assert!(red.y < 0.5 && red.z < red.y);
+        let blue = Color::blackbody(20000.0);
+        assert_eq!(blue.z, 1.0);
+        assert!(blue.x < blue.y);
+
+        assert_eq!(Color::blackbody(0.0), Color::new(0.0, 0.0, 0.0));
+        assert!(Color::blackbody(10.0).x == 1.0 && Color::blackbody(10.0).z == 0.0);
+    }
+
+    #[test]
+    fn test_write_color() {
+        assert_eq!(Color::new(0.0, 0.5, 1.0).write_color(), Rgb([0, 188, 255]));
+        assert_eq!(Color::new(-1.0, 0.18, 7.0).write_color(), Rgb([0, 118, 255]));
+    }
+
 }
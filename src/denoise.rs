@@ -0,0 +1,276 @@
+// Edge-avoiding à-trous wavelet filter (Dammertz et al. 2010), which blurs
+// the render noise away while keeping the edges found in the normal, depth
+// and albedo AOVs.
+
+use crate::color::Color;
+use crate::vec3::Vec3;
+use crate::framebuffer::Framebuffer;
+use crate::aov::{Aovs, MISS_DEPTH};
+
+use rayon::prelude::*;
+
+// B3 spline, the 5 taps of the filter in each direction.
+const KERNEL: [f64; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Denoiser {
+    // Every iteration doubles the distance between the taps, 5 of them cover
+    // a 125 pixels wide footprint.
+    pub iterations: u32,
+    // How different two colors, compressed to [0, 1), can be and still be
+    // averaged. Halved at every iteration so that the coarse levels only
+    // smooth what the first ones left.
+    pub color_sigma: f64,
+    pub normal_sigma: f64,
+    // Relative to the depth of the pixel and to the distance between the
+    // two pixels.
+    pub depth_sigma: f64,
+    pub albedo_sigma: f64,
+    // Pixels brighter than this many times their brightest neighbour are
+    // fireflies, replaced by the average of their neighbours before
+    // filtering. 0 disables it.
+    pub firefly_ratio: f64,
+}
+
+impl Default for Denoiser {
+
+    fn default() -> Denoiser {
+        Denoiser::new(5, 0.3)
+    }
+
+}
+
+impl Denoiser {
+
+    pub fn new(iterations: u32, color_sigma: f64) -> Denoiser {
+        Denoiser {
+            iterations,
+            color_sigma,
+            normal_sigma: 0.3,
+            depth_sigma: 0.05,
+            albedo_sigma: 0.1,
+            firefly_ratio: 4.0,
+        }
+    }
+
+    // The image is divided by the albedo before filtering and multiplied
+    // back afterwards, so textures are not blurred along with the noise.
+    pub fn denoise(&self, image: &Framebuffer, aovs: &Aovs) -> Framebuffer {
+        let (width, height) = (image.width, image.height);
+        let pixels = |buffer: &Framebuffer| -> Vec<Color> {
+            (0..height).flat_map(|j| (0..width).map(move |i| (i, j))).map(|(i, j)| buffer.get(i, j).clone()).collect()
+        };
+
+        let guide = Guide {
+            width,
+            height,
+            normal: pixels(&aovs.normal),
+            depth: pixels(&aovs.depth).iter().map(|d| d.x).collect(),
+            albedo: pixels(&aovs.albedo),
+        };
+
+        let divisors: Vec<Color> = guide.albedo.iter().map(|a| {
+            Color::new(divisor(a.x), divisor(a.y), divisor(a.z))
+        }).collect();
+
+        let mut irradiance: Vec<Color> = pixels(image).iter().zip(&divisors).map(|(c, d)| {
+            Color::new(c.x / d.x, c.y / d.y, c.z / d.z)
+        }).collect();
+
+        if self.firefly_ratio > 0.0 {
+            irradiance = self.remove_fireflies(&irradiance, width, height);
+        }
+
+        for iteration in 0..self.iterations {
+            irradiance = self.filter(&irradiance, &guide, iteration);
+        }
+
+        let mut denoised = Framebuffer::new(width, height);
+        for (index, (c, d)) in irradiance.iter().zip(&divisors).enumerate() {
+            let index = index as u32;
+            denoised.set(index % width, index / width, c * d);
+        }
+        denoised
+    }
+
+    fn remove_fireflies(&self, pixels: &[Color], width: u32, height: u32) -> Vec<Color> {
+        (0..pixels.len()).into_par_iter().map(|index| {
+            let (i, j) = ((index as u32 % width) as i64, (index as u32 / width) as i64);
+            let mut brightest = 0.0_f64;
+            let mut sum = Color::new(0.0, 0.0, 0.0);
+            let mut count = 0;
+
+            for dj in -1..=1 {
+                for di in -1..=1 {
+                    let (x, y) = (i + di, j + dj);
+                    if (di, dj) != (0, 0) && (0..width as i64).contains(&x) && (0..height as i64).contains(&y) {
+                        let neighbour = &pixels[(y * width as i64 + x) as usize];
+                        brightest = brightest.max(neighbour.luminance());
+                        sum = sum + neighbour;
+                        count += 1;
+                    }
+                }
+            }
+
+            let c = &pixels[index];
+            if count > 0 && c.luminance() > self.firefly_ratio * brightest {
+                sum / count as f64
+            } else {
+                c.clone()
+            }
+        }).collect()
+    }
+
+    fn filter(&self, pixels: &[Color], guide: &Guide, iteration: u32) -> Vec<Color> {
+        let step = 1i64 << iteration;
+        let color_sigma = self.color_sigma / (1u64 << iteration) as f64;
+        let (width, height) = (guide.width as i64, guide.height as i64);
+
+        (0..pixels.len()).into_par_iter().map(|p| {
+            let (i, j) = (p as i64 % width, p as i64 / width);
+            let color_p = compress(&pixels[p]);
+
+            let mut sum = Color::new(0.0, 0.0, 0.0);
+            let mut total = 0.0;
+
+            for (dj, kj) in KERNEL.iter().enumerate() {
+                for (di, ki) in KERNEL.iter().enumerate() {
+                    let x = i + (di as i64 - 2) * step;
+                    let y = j + (dj as i64 - 2) * step;
+                    if !(0..width).contains(&x) || !(0..height).contains(&y) {
+                        continue;
+                    }
+                    let q = (y * width + x) as usize;
+                    let distance = (((x - i) * (x - i) + (y - j) * (y - j)) as f64).sqrt();
+
+                    let weight = ki * kj
+                        * gaussian((&compress(&pixels[q]) - &color_p).length_squared(), color_sigma)
+                        * gaussian((&guide.normal[q] - &guide.normal[p]).length_squared(), self.normal_sigma)
+                        * gaussian((&guide.albedo[q] - &guide.albedo[p]).length_squared(), self.albedo_sigma)
+                        * self.depth_weight(guide.depth[p], guide.depth[q], distance);
+
+                    sum = sum + weight * &pixels[q];
+                    total += weight;
+                }
+            }
+
+            // The center tap always has a weight of at least 9 / 64.
+            sum / total
+        }).collect()
+    }
+
+    fn depth_weight(&self, p: f64, q: f64, distance: f64) -> f64 {
+        match (p >= MISS_DEPTH, q >= MISS_DEPTH) {
+            (true, true) => 1.0,
+            (false, false) => (-(p - q).abs() / (self.depth_sigma * p * distance).max(1e-8)).exp(),
+            _ => 0.0,
+        }
+    }
+
+}
+
+// The feature buffers the filter is guided by.
+struct Guide {
+    width: u32,
+    height: u32,
+    normal: Vec<Vec3>,
+    depth: Vec<f64>,
+    albedo: Vec<Color>,
+}
+
+// Black albedo, the background or a light, is left alone.
+fn divisor(albedo: f64) -> f64 {
+    if albedo > 0.01 { albedo } else { 1.0 }
+}
+
+// Color differences are measured after a Reinhard curve, so that they mean
+// the same in the shadows and in the highlights.
+fn compress(c: &Color) -> Color {
+    Color::new(c.x / (1.0 + c.x), c.y / (1.0 + c.y), c.z / (1.0 + c.z))
+}
+
+fn gaussian(distance_squared: f64, sigma: f64) -> f64 {
+    (-distance_squared / (sigma * sigma)).exp()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampler::Sampler;
+
+    const SIZE: u32 = 32;
+
+    // A floor facing the camera, split down the middle between two normals
+    // when `edge` is set, under a uniform light with noise added.
+    fn noisy(edge: bool, seed: u64) -> (Framebuffer, Framebuffer, Aovs) {
+        let mut sampler = Sampler::new(seed);
+        let mut clean = Framebuffer::new(SIZE, SIZE);
+        let mut image = Framebuffer::new(SIZE, SIZE);
+        let mut aovs = Aovs::new(SIZE, SIZE);
+
+        for j in 0..SIZE {
+            for i in 0..SIZE {
+                let left = edge && i < SIZE / 2;
+                let (value, normal) = if left {
+                    (0.8, Vec3::new(1.0, 0.0, 0.0))
+                } else {
+                    (0.2, Vec3::new(0.0, 0.0, 1.0))
+                };
+                let noise = value * (sampler.random_double() - 0.5);
+
+                clean.set(i, j, Color::new(value, value, value));
+                image.set(i, j, Color::new(value + noise, value + noise, value + noise));
+                aovs.normal.set(i, j, normal);
+                aovs.depth.set(i, j, Color::new(2.0, 2.0, 2.0));
+                aovs.albedo.set(i, j, Color::new(0.5, 0.5, 0.5));
+            }
+        }
+        (clean, image, aovs)
+    }
+
+    fn error(a: &Framebuffer, b: &Framebuffer) -> f64 {
+        let mut sum = 0.0;
+        for j in 0..a.height {
+            for i in 0..a.width {
+                sum += (a.get(i, j) - b.get(i, j)).length_squared();
+            }
+        }
+        (sum / (a.width * a.height) as f64).sqrt()
+    }
+
+    #[test]
+    fn test_flat_noise() {
+        let (clean, image, aovs) = noisy(false, 1);
+
+        assert_eq!(Denoiser::new(0, 0.3).denoise(&image, &aovs), image);
+
+        let denoised = Denoiser::default().denoise(&image, &aovs);
+        assert!(error(&denoised, &clean) < 0.25 * error(&image, &clean));
+    }
+
+    #[test]
+    fn test_edges_are_kept() {
+        let (clean, image, aovs) = noisy(true, 2);
+        let denoised = Denoiser::default().denoise(&image, &aovs);
+
+        assert!(error(&denoised, &clean) < 0.25 * error(&image, &clean));
+        // Nothing bleeds across the edge: the bright column next to it keeps
+        // its average, and the dark one every pixel.
+        let bright = (0..SIZE).map(|j| denoised.get(SIZE / 2 - 1, j).x).sum::<f64>() / SIZE as f64;
+        assert!((bright - 0.8).abs() < 0.05);
+        for j in 0..SIZE {
+            assert!((denoised.get(SIZE / 2, j).x - 0.2).abs() < 0.02);
+        }
+    }
+
+    #[test]
+    fn test_fireflies() {
+        let (clean, mut image, aovs) = noisy(false, 3);
+        image.set(10, 10, Color::new(500.0, 500.0, 500.0));
+
+        let denoised = Denoiser::default().denoise(&image, &aovs);
+        assert!((denoised.get(10, 10).x - 0.2).abs() < 0.05);
+        assert!(error(&denoised, &clean) < 0.05);
+    }
+
+}
@@ -0,0 +1,180 @@
+use crate::hittable::{Hittable, HitRecord, Face, area_light_pdf};
+use crate::vec3::{Vec3, Point3};
+use crate::ray::Ray;
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::aabb::Aabb;
+use crate::onb::Onb;
+use crate::sampler::Sampler;
+
+use std::sync::Arc;
+
+// Disk of the given radius around `center`, facing along `normal`. u is the
+// angle around the normal and v the distance from the center over the
+// radius.
+pub struct Disk {
+    pub center: Point3,
+    pub radius: f64,
+    pub material: Arc<dyn Material>,
+    frame: Onb,
+}
+
+impl Disk {
+
+    pub fn new(center: Point3, normal: Vec3, radius: f64, material: Arc<dyn Material>) -> Disk {
+        Disk {
+            center,
+            radius,
+            material,
+            frame: Onb::new(&normal),
+        }
+    }
+
+    pub fn normal(&self) -> &Vec3 {
+        &self.frame.w
+    }
+
+    // Coordinates of the point at `offset` from the center along the u and
+    // v axes of the frame, and the derivatives of the point with respect to
+    // the texture coordinates there. dpdu vanishes at the center.
+    fn uv(&self, offset: &Vec3) -> (f64, f64, Vec3, Vec3) {
+        let pi = std::f64::consts::PI;
+        let x = Vec3::dot(offset, &self.frame.u);
+        let y = Vec3::dot(offset, &self.frame.v);
+        let r = (x * x + y * y).sqrt();
+
+        let phi = y.atan2(x);
+        let phi = if phi < 0.0 { phi + 2.0 * pi } else { phi };
+
+        let dpdu = 2.0 * pi * (-y * &self.frame.u + x * &self.frame.v);
+        let dpdv = if r > 0.0 {
+            self.radius / r * (x * &self.frame.u + y * &self.frame.v)
+        } else {
+            self.radius * &self.frame.u
+        };
+        (phi / (2.0 * pi), r / self.radius, dpdu, dpdv)
+    }
+
+}
+
+impl Hittable for Disk {
+
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord> {
+        let normal = self.normal();
+        let denom = Vec3::dot(normal, &r.direction);
+        if denom.abs() < 1e-8 {
+            return None;
+        }
+
+        let t = Vec3::dot(normal, &(&self.center - &r.origin)) / denom;
+        if !ray_t.surrounds(t) {
+            return None;
+        }
+
+        let p = r.at(t);
+        let offset = &p - &self.center;
+        if offset.length_squared() > self.radius * self.radius {
+            return None;
+        }
+
+        let (u, v, dpdu, dpdv) = self.uv(&offset);
+        let mut hr = HitRecord::new(p, normal.clone(), self.material.clone(), t, u, v, Face::Front);
+        hr.set_face_normal(r, normal);
+        hr.set_uv_derivatives(dpdu, dpdv);
+        Some(hr)
+    }
+
+    // The extent along each axis is the radius times the sine of the angle
+    // between the axis and the normal.
+    fn bounding_box(&self) -> Aabb {
+        let n = self.normal();
+        let extent = |c: f64| self.radius * (1.0 - c * c).max(0.0).sqrt();
+        let e = Vec3::new(extent(n.x), extent(n.y), extent(n.z));
+
+        Aabb::from_points(&(&self.center - &e), &(&self.center + &e))
+    }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3, time: f64) -> f64 {
+        match self.hit(&Ray::with_time(origin.clone(), direction.clone(), time), Interval::new(0.001, f64::INFINITY)) {
+            Some(hr) => area_light_pdf(hr.t, direction, self.normal(), std::f64::consts::PI * self.radius * self.radius),
+            None => 0.0,
+        }
+    }
+
+    fn random(&self, origin: &Point3, _time: f64, sampler: &mut Sampler) -> Vec3 {
+        let r = self.radius * Vec3::random_double(sampler).sqrt();
+        let phi = 2.0 * std::f64::consts::PI * Vec3::random_double(sampler);
+
+        let p = &self.center + self.frame.transform(&Vec3::new(r * phi.cos(), r * phi.sin(), 0.0));
+        p - origin
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::color::Color;
+
+    #[test]
+    fn test_disk() {
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let disk = Disk::new(Point3::new(1.0, 2.0, 3.0), Vec3::new(0.0, 2.0, 0.0), 2.0, material);
+        let ray_t = Interval::new(0.001, f64::INFINITY);
+
+        let bbox = disk.bounding_box();
+        assert_eq!((bbox.x.min, bbox.x.max), (-1.0, 3.0));
+        assert_eq!((bbox.z.min, bbox.z.max), (1.0, 5.0));
+
+        let r = Ray::new(Point3::new(2.0, 5.0, 3.0), Vec3::new(0.0, -1.0, 0.0));
+        let hr = disk.hit(&r, ray_t).unwrap();
+        assert_eq!(hr.t, 3.0);
+        assert!(matches!(hr.face, Face::Front));
+        assert_eq!(hr.normal, Vec3::new(0.0, 1.0, 0.0));
+        assert!((hr.v - 0.5).abs() < 1e-12);
+
+        // Just outside the rim, and from below.
+        assert!(disk.hit(&Ray::new(Point3::new(3.01, 5.0, 3.0), Vec3::new(0.0, -1.0, 0.0)), ray_t).is_none());
+        let hr = disk.hit(&Ray::new(Point3::new(1.0, 0.0, 4.0), Vec3::new(0.0, 1.0, 0.0)), ray_t).unwrap();
+        assert!(matches!(hr.face, Face::Back));
+
+        // The derivatives match finite differences of the hit point.
+        let at = |u: f64, v: f64| {
+            let phi = 2.0 * std::f64::consts::PI * u;
+            &disk.center + disk.frame.transform(&Vec3::new(2.0 * v * phi.cos(), 2.0 * v * phi.sin(), 0.0))
+        };
+        let mut sampler = Sampler::new(0);
+        for _ in 0..100 {
+            let (u, v) = (0.01 + 0.98 * sampler.random_double(), 0.01 + 0.98 * sampler.random_double());
+            let p = at(u, v);
+            let hr = disk.hit(&Ray::new(&p + Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0)), ray_t).unwrap();
+            assert!((hr.u - u).abs() < 1e-9 && (hr.v - v).abs() < 1e-9);
+
+            let h = 1e-6;
+            let dpdu = (at(u + h, v) - at(u - h, v)) / (2.0 * h);
+            let dpdv = (at(u, v + h) - at(u, v - h)) / (2.0 * h);
+            assert!((&hr.dpdu - &dpdu).length() < 1e-6 * dpdu.length());
+            assert!((&hr.dpdv - &dpdv).length() < 1e-6 * dpdv.length());
+        }
+    }
+
+    #[test]
+    fn test_light_sampling() {
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let disk = Disk::new(Point3::new(0.0, 0.0, -1.0), Vec3::new(0.0, 0.0, 1.0), 1.0, material);
+        let origin = Point3::new(0.0, 0.0, 0.0);
+
+        let mut sampler = Sampler::new(1);
+        let (mut sum, n) = (0.0, 100000);
+        for _ in 0..n {
+            let direction = disk.random(&origin, 0.0, &mut sampler);
+            sum += 1.0 / disk.pdf_value(&origin, &direction, 0.0);
+        }
+
+        // A cone with a half angle of 45 degrees.
+        let solid_angle = 2.0 * std::f64::consts::PI * (1.0 - 0.5_f64.sqrt());
+        assert!((sum / n as f64 - solid_angle).abs() < 0.01 * solid_angle);
+    }
+
+}
@@ -0,0 +1,49 @@
+use crate::color::Color;
+use crate::tonemap::ToneMap;
+
+use image::{Rgb, RgbImage, Rgb32FImage};
+
+// Linear radiance of every pixel, as rendered. Nothing is clamped until the
+// image is converted for an 8-bit output.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Framebuffer {
+    pub width: u32,
+    pub height: u32,
+    pixels: Vec<Color>,
+}
+
+impl Framebuffer {
+
+    pub fn new(width: u32, height: u32) -> Framebuffer {
+        Framebuffer {
+            width,
+            height,
+            pixels: vec![Color::new(0.0, 0.0, 0.0); width as usize * height as usize],
+        }
+    }
+
+    pub fn get(&self, i: u32, j: u32) -> &Color {
+        &self.pixels[self.index(i, j)]
+    }
+
+    pub fn set(&mut self, i: u32, j: u32, color: Color) {
+        let index = self.index(i, j);
+        self.pixels[index] = color;
+    }
+
+    fn index(&self, i: u32, j: u32) -> usize {
+        j as usize * self.width as usize + i as usize
+    }
+
+    pub fn to_rgb8(&self, tone_map: &ToneMap) -> RgbImage {
+        RgbImage::from_fn(self.width, self.height, |i, j| tone_map.apply(self.get(i, j)).write_color())
+    }
+
+    pub fn to_rgb32f(&self) -> Rgb32FImage {
+        Rgb32FImage::from_fn(self.width, self.height, |i, j| {
+            let c = self.get(i, j);
+            Rgb([c.x as f32, c.y as f32, c.z as f32])
+        })
+    }
+
+}
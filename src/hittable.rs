@@ -2,6 +2,9 @@ use crate::vec3::{Vec3, Point3};
 use crate::ray::Ray;
 use crate::interval::Interval;
 use crate::material::Material;
+use crate::aabb::Aabb;
+use crate::sampler::Sampler;
+use crate::onb::Onb;
 
 use std::sync::Arc;
 
@@ -12,25 +15,146 @@ pub enum Face {
 
 pub struct HitRecord {
     pub p: Point3,
+    // Shading normal, the one materials use. It may be interpolated or
+    // perturbed and so differ from the geometric normal of the surface, but
+    // both always face the same side.
     pub normal: Vec3,
+    pub geometric_normal: Vec3,
     pub material: Arc<dyn Material>,
     pub t: f64,
+    pub u: f64,
+    pub v: f64,
+    // Derivatives of the hit point with respect to u and v, zero when the
+    // surface has no usable parameterization there.
+    pub dpdu: Vec3,
+    pub dpdv: Vec3,
+    // Unit tangent along dpdu and bitangent towards dpdv, orthogonal to the
+    // normal. Some arbitrary frame around the normal without derivatives.
+    pub tangent: Vec3,
+    pub bitangent: Vec3,
     pub face: Face,
+    // Set for points inside a medium, scattered by a phase function rather
+    // than off a surface.
+    pub in_medium: bool,
+    // Set by Identified for the ID AOVs, 0 when the object has none.
+    pub object_id: u32,
+    pub material_id: u32,
 }
 
-pub trait Hittable {
+pub trait Hittable: Send + Sync {
     fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord>;
+    fn bounding_box(&self) -> Aabb;
+
+    // Solid angle density of random() choosing `direction` from `origin`.
+    // Objects that can't be sampled as lights keep the default of zero.
+    fn pdf_value(&self, _origin: &Point3, _direction: &Vec3, _time: f64) -> f64 {
+        0.0
+    }
+
+    // Direction from `origin` towards a random point of the object.
+    fn random(&self, _origin: &Point3, _time: f64, _sampler: &mut Sampler) -> Vec3 {
+        Vec3::new(1.0, 0.0, 0.0)
+    }
+}
+
+// pdf_value of a flat object whose points are sampled uniformly over its
+// `area`, converted to solid angle for a direction reaching it at `t` where
+// the surface has the `normal`, of any length.
+pub fn area_light_pdf(t: f64, direction: &Vec3, normal: &Vec3, area: f64) -> f64 {
+    let distance_squared = t * t * direction.length_squared();
+    let cosine = (Vec3::dot(direction, normal) / (direction.length() * normal.length())).abs();
+
+    if cosine < 1e-8 {
+        return 0.0;
+    }
+    distance_squared / (cosine * area)
+}
+
+// Lets an object be shared, for instance between the world and the lights.
+impl<T: Hittable + ?Sized> Hittable for Arc<T> {
+
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord> {
+        self.as_ref().hit(r, ray_t)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.as_ref().bounding_box()
+    }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3, time: f64) -> f64 {
+        self.as_ref().pdf_value(origin, direction, time)
+    }
+
+    fn random(&self, origin: &Point3, time: f64, sampler: &mut Sampler) -> Vec3 {
+        self.as_ref().random(origin, time, sampler)
+    }
+
+}
+
+// Tags the hits of an object with the object and material IDs written to
+// the ID AOVs.
+pub struct Identified<H> {
+    object: H,
+    object_id: u32,
+    material_id: u32,
+}
+
+impl<H> Identified<H> {
+
+    pub fn new(object: H, object_id: u32, material_id: u32) -> Identified<H> {
+        Identified {
+            object,
+            object_id,
+            material_id,
+        }
+    }
+
+}
+
+impl<H: Hittable> Hittable for Identified<H> {
+
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord> {
+        let mut hr = self.object.hit(r, ray_t)?;
+        hr.object_id = self.object_id;
+        hr.material_id = self.material_id;
+        Some(hr)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.object.bounding_box()
+    }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3, time: f64) -> f64 {
+        self.object.pdf_value(origin, direction, time)
+    }
+
+    fn random(&self, origin: &Point3, time: f64, sampler: &mut Sampler) -> Vec3 {
+        self.object.random(origin, time, sampler)
+    }
+
 }
 
 impl HitRecord {
-    pub fn new(p: Point3, normal: Vec3, material: Arc<dyn Material>, t: f64, face: Face) -> HitRecord {
-        HitRecord {
+    pub fn new(p: Point3, normal: Vec3, material: Arc<dyn Material>, t: f64, u: f64, v: f64, face: Face) -> HitRecord {
+        let mut hr = HitRecord {
             p,
+            geometric_normal: normal.clone(),
             normal,
             material,
             t,
+            u,
+            v,
+            dpdu: Vec3::new(0.0, 0.0, 0.0),
+            dpdv: Vec3::new(0.0, 0.0, 0.0),
+            tangent: Vec3::new(0.0, 0.0, 0.0),
+            bitangent: Vec3::new(0.0, 0.0, 0.0),
             face,
-        }
+            in_medium: false,
+            object_id: 0,
+            material_id: 0,
+        };
+        hr.update_tangent_frame();
+        hr
     }
 
     pub fn set_face_normal(&mut self, r: &Ray, outward_normal: &Vec3) {
@@ -39,6 +163,56 @@ impl HitRecord {
                                     } else {
                                         (Face::Back, -outward_normal)
                                     };
+        self.geometric_normal = self.normal.clone();
+        self.update_tangent_frame();
+    }
+
+    // Replaces the shading normal, flipped if needed to stay on the side of
+    // the geometric normal.
+    pub fn set_shading_normal(&mut self, normal: Vec3) {
+        self.normal = if Vec3::dot(&normal, &self.geometric_normal) < 0.0 { -normal } else { normal };
+        self.update_tangent_frame();
+    }
+
+    // Lets the material perturb the shading normal, see
+    // Material::shading_normal. Done once per hit before shading.
+    pub fn perturb_normal(&mut self) {
+        if let Some(normal) = self.material.shading_normal(self) {
+            self.set_shading_normal(normal);
+        }
+    }
+
+    // Whether the shading and geometric normals agree on the side of the
+    // surface `direction` goes to. Paths where they disagree would leak light
+    // through the surface. There is no surface inside a medium.
+    pub fn same_side(&self, direction: &Vec3) -> bool {
+        if self.in_medium {
+            return true;
+        }
+        Vec3::dot(direction, &self.normal) * Vec3::dot(direction, &self.geometric_normal) > 0.0
+    }
+
+    pub fn set_uv_derivatives(&mut self, dpdu: Vec3, dpdv: Vec3) {
+        self.dpdu = dpdu;
+        self.dpdv = dpdv;
+        self.update_tangent_frame();
+    }
+
+    // Rebuilds the tangent and bitangent, to be called whenever the normal
+    // changes. The bitangent is flipped when needed to follow dpdv, so the
+    // frame may be left handed.
+    pub fn update_tangent_frame(&mut self) {
+        let projected = &self.dpdu - Vec3::dot(&self.dpdu, &self.normal) * &self.normal;
+
+        self.tangent = if projected.length_squared() > 1e-16 {
+            projected.unit_vector()
+        } else {
+            Onb::new(&self.normal).u
+        };
+        self.bitangent = Vec3::cross(&self.normal, &self.tangent);
+        if Vec3::dot(&self.bitangent, &self.dpdv) < 0.0 {
+            self.bitangent = -&self.bitangent;
+        }
     }
 
 }
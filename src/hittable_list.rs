@@ -1,26 +1,47 @@
 use crate::hittable::{Hittable, HitRecord};
 use crate::ray::Ray;
 use crate::interval::Interval;
+use crate::aabb::Aabb;
+use crate::vec3::{Vec3, Point3};
+use crate::sampler::Sampler;
 
 pub type HittableList = Vec<Box<dyn Hittable>>;
 
 impl Hittable for HittableList {
 
-    fn hit(self: &Self, r: &Ray, ray_t: Interval) -> Option<HitRecord> {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord> {
         let mut closest_so_far = ray_t.max;
         let mut result: Option<HitRecord> = None;
 
         for object in self {
-            match object.hit(r, Interval::new(ray_t.min, closest_so_far)) {
-                Some(hit) => {
-                    closest_so_far = hit.t;
-                    result = Some(hit);
-                },
-                None => (),
+            if let Some(hit) = object.hit(r, Interval::new(ray_t.min, closest_so_far)) {
+                closest_so_far = hit.t;
+                result = Some(hit);
             }
         }
         result
     }
 
+    fn bounding_box(&self) -> Aabb {
+        self.iter().fold(Aabb::empty(), |bbox, object| Aabb::surrounding(&bbox, &object.bounding_box()))
+    }
+
+    // Sampling picks one of the objects uniformly.
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3, time: f64) -> f64 {
+        if self.is_empty() {
+            return 0.0;
+        }
+        let sum: f64 = self.iter().map(|object| object.pdf_value(origin, direction, time)).sum();
+        sum / self.len() as f64
+    }
+
+    fn random(&self, origin: &Point3, time: f64, sampler: &mut Sampler) -> Vec3 {
+        if self.is_empty() {
+            return Vec3::new(1.0, 0.0, 0.0);
+        }
+        let index = ((Vec3::random_double(sampler) * self.len() as f64) as usize).min(self.len() - 1);
+        self[index].random(origin, time, sampler)
+    }
+
 }
 
@@ -1,18 +1,23 @@
 
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Interval {
     pub min: f64,
     pub max: f64,
 }
 
-impl Interval {
+impl Default for Interval {
 
-    pub fn default() -> Interval {
+    fn default() -> Interval {
         Interval {
             min: -f64::INFINITY,
             max: f64::INFINITY,
         }
     }
 
+}
+
+impl Interval {
+
     pub fn new(min: f64, max: f64) -> Interval {
         Interval {
             min,
@@ -20,17 +25,41 @@ impl Interval {
         }
     }
 
+    pub fn empty() -> Interval {
+        Interval {
+            min: f64::INFINITY,
+            max: -f64::INFINITY,
+        }
+    }
+
+    pub fn enclosing(a: &Interval, b: &Interval) -> Interval {
+        Interval {
+            min: a.min.min(b.min),
+            max: a.max.max(b.max),
+        }
+    }
+
+    pub fn size(&self) -> f64 {
+        self.max - self.min
+    }
+
+    pub fn expand(&self, delta: f64) -> Interval {
+        let padding = delta / 2.0;
+        Interval::new(self.min - padding, self.max + padding)
+    }
+
     pub fn surrounds(&self, x: f64) -> bool {
         self.min < x && x < self.max
     }
 
     pub fn clamp(&self, x: f64) -> f64 {
         if x < self.min {
-            return self.min;
+            self.min
         } else if x > self.max {
-            return self.max;
+            self.max
+        } else {
+            x
         }
-        x
     }
 }
 
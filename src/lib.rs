@@ -0,0 +1,34 @@
+pub mod vec3;
+pub mod color;
+pub mod ray;
+pub mod hittable;
+pub mod sphere;
+pub mod hittable_list;
+pub mod interval;
+pub mod camera;
+pub mod material;
+pub mod aabb;
+pub mod bvh;
+pub mod scene;
+pub mod output;
+pub mod triangle;
+pub mod quad;
+pub mod disk;
+pub mod plane;
+pub mod transform;
+pub mod animation;
+pub mod mesh;
+pub mod obj;
+pub mod background;
+pub mod onb;
+pub mod sampler;
+pub mod framebuffer;
+pub mod tonemap;
+pub mod accumulator;
+pub mod checkpoint;
+pub mod aov;
+pub mod denoise;
+pub mod perlin;
+pub mod texture;
+pub mod medium;
+pub mod voxel;
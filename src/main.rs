@@ -1,42 +1,174 @@
-mod vec3;
-mod color;
-mod ray;
-mod hittable;
-mod sphere;
-mod hittable_list;
-mod interval;
-mod camera;
-mod material;
-
-use vec3::{Vec3, Point3};
-use color::Color;
-use hittable_list::HittableList;
-use sphere::Sphere;
-use camera::Camera;
-use material::{Lambertian, Metal, Dielectric};
-use interval::Interval;
 
+mod cli;
+
+use raytracer::vec3::{Vec3, Point3};
+use raytracer::color::Color;
+use raytracer::hittable_list::HittableList;
+use raytracer::sphere::Sphere;
+use raytracer::plane::Plane;
+use raytracer::camera::Camera;
+use raytracer::material::{Lambertian, Metal, Dielectric};
+use raytracer::interval::Interval;
+use raytracer::bvh::BvhNode;
+use raytracer::scene::Scene;
+use raytracer::output::{self, OutputFormat};
+use raytracer::framebuffer::Framebuffer;
+use raytracer::accumulator::Accumulator;
+use raytracer::checkpoint;
+use raytracer::tonemap::ToneMap;
+use raytracer::sampler::Sampler;
+use cli::Args;
+
+use clap::Parser;
+
+use std::path::Path;
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
 use std::sync::Arc;
 
-fn main() {
+fn main() -> ExitCode {
+
+    let args = Args::parse();
+
+    let outputs = match args.outputs().and_then(|outputs| Ok((outputs, args.heatmap_format()?, args.aov_outputs()?))) {
+        Ok(outputs) => outputs,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return ExitCode::from(2);
+        },
+    };
+
+    let Scene { world, lights, mut camera } = match &args.scene {
+        Some(path) => match Scene::load(path) {
+            Ok(scene) => scene,
+            Err(e) => {
+                eprintln!("error: {}: {}", path.display(), e);
+                return ExitCode::FAILURE;
+            },
+        },
+        None => final_scene(args.seed.unwrap_or(0)),
+    };
+
+    args.apply(&mut camera);
+
+    let world = BvhNode::new(world);
+
+    let (outputs, heatmap, aov_outputs) = outputs;
+    let tone_map = args.tone_map();
+    let denoiser = args.denoiser();
+
+    // Needed up front by the denoiser, which also cleans up the snapshots.
+    let aovs = if denoiser.is_some() || !aov_outputs.is_empty() {
+        Some(camera.render_aovs(&world))
+    } else {
+        None
+    };
+    let image = |accumulator: &Accumulator| match (&denoiser, &aovs) {
+        (Some(denoiser), Some(aovs)) => denoiser.denoise(&accumulator.estimate(), aovs),
+        _ => accumulator.estimate(),
+    };
+
+    // Snapshots are only taken when rendering in several passes.
+    let interval = Duration::from_secs_f64(args.snapshot_interval);
+    let mut last = Instant::now();
+
+    let snapshot = |accumulator: &Accumulator| {
+        if last.elapsed() >= interval {
+            save(&image(accumulator), &outputs, &tone_map);
+            save_checkpoint(accumulator, args.checkpoint.as_deref());
+            last = Instant::now();
+        }
+    };
+
+    let accumulator = match &args.resume {
+        Some(path) => {
+            let accumulator = match checkpoint::load(path) {
+                Ok(accumulator) => accumulator,
+                Err(e) => {
+                    eprintln!("error: {}: {}", path.display(), e);
+                    return ExitCode::FAILURE;
+                },
+            };
+            if !camera.can_resume(&accumulator) {
+                eprintln!("error: {}: the checkpoint was saved with a different image size or seed", path.display());
+                return ExitCode::FAILURE;
+            }
+            camera.resume(&world, &lights, accumulator, snapshot)
+        },
+        None => camera.render_progressive(&world, &lights, snapshot),
+    };
+
+    let mut ok = save(&image(&accumulator), &outputs, &tone_map);
+    ok &= save_checkpoint(&accumulator, args.checkpoint.as_deref());
+
+    if let Some(heatmap) = heatmap {
+        ok &= save(&accumulator.sample_heatmap(camera.samples_per_pixel), &[heatmap], &ToneMap::default());
+    }
+
+    if let Some(aovs) = &aovs {
+        // HDR formats get the raw values, 8-bit ones a visualization.
+        for (aov, path, format) in aov_outputs {
+            ok &= if format.is_hdr() {
+                save(aovs.get(aov), &[(path, format)], &ToneMap::default())
+            } else {
+                save(&aovs.visualize(aov), &[(path, format)], &ToneMap::default())
+            };
+        }
+    }
+
+    if ok {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+
+}
+
+fn save_checkpoint(accumulator: &Accumulator, path: Option<&Path>) -> bool {
+    if let Some(path) = path {
+        if let Err(e) = checkpoint::save(accumulator, path) {
+            eprintln!("error: {}: {}", path.display(), e);
+            return false;
+        }
+    }
+    true
+}
+
+// Writes the image to every output, reporting the ones that fail.
+fn save(image: &Framebuffer, outputs: &[(&Path, OutputFormat)], tone_map: &ToneMap) -> bool {
+    let mut ok = true;
+
+    for (path, format) in outputs {
+        if let Err(e) = output::save(image, path, *format, tone_map) {
+            eprintln!("error: {}: {}", path.display(), e);
+            ok = false;
+        }
+    }
+    ok
+}
+
+// The layout of the random spheres is picked with the render seed.
+fn final_scene(seed: u64) -> Scene {
+
+    let mut sampler = Sampler::new(seed);
 
     // World
 
     let mut world: HittableList = vec![];
 
-    world.push(Box::new(Sphere::new(Point3::new( 0.0, -100.5, -1.0), 100.0, Arc::new(Lambertian::new(Color::new(0.8, 0.8, 0.0))))));
+    world.push(Box::new(Plane::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), Arc::new(Lambertian::new(Color::new(0.8, 0.8, 0.0))))));
 
     for a in -11..11 {
         for b in -11..11 {
-            let choose_mat = Vec3::random_double();
-            let center = Point3::new(a as f64 + 0.9 * Vec3::random_double(), 
+            let choose_mat = Vec3::random_double(&mut sampler);
+            let center = Point3::new(a as f64 + 0.9 * Vec3::random_double(&mut sampler), 
                                         0.2,
-                                        b as f64 + 0.9 * Vec3::random_double());
+                                        b as f64 + 0.9 * Vec3::random_double(&mut sampler));
             if (&center - Point3::new(4.0, 0.2, 0.0)).length() > 0.9 {
                 world.push(Box::new(Sphere::new(center, 0.2,
                     match choose_mat {
-                        _x if choose_mat < 0.8 => Arc::new(Lambertian::new(Vec3::random() * Vec3::random())),
-                        _x if choose_mat >= 0.8 && choose_mat < 0.95 => Arc::new(Metal::new(Vec3::random_interval(Interval::new(0.5, 1.0)), Vec3::random_double_interval(&Interval::new(0.5, 1.0)))),
+                        _x if choose_mat < 0.8 => Arc::new(Lambertian::new(Vec3::random(&mut sampler) * Vec3::random(&mut sampler))),
+                        _x if (0.8..0.95).contains(&choose_mat) => Arc::new(Metal::new(Vec3::random_interval(Interval::new(0.5, 1.0), &mut sampler), Vec3::random_double_interval(&Interval::new(0.5, 1.0), &mut sampler))),
                         _ => Arc::new(Dielectric::new(1.5)),
                     })));
             }
@@ -51,6 +183,6 @@ fn main() {
     
     let camera = Camera::new(16.0 / 9.0, 400, 500, 50, 20.0, Point3::new(13.0, 2.0, 3.0), Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), 0.6, 10.0);
 
-    camera.render(&world);
+    Scene { world, lights: vec![], camera }
 
 }
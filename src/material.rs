@@ -1,87 +1,166 @@
 use crate::color::Color;
 use crate::ray::Ray;
 use crate::hittable::{HitRecord, Face};
-use crate::vec3::Vec3;
+use crate::vec3::{Vec3, Point3};
+use crate::sampler::Sampler;
+use crate::texture::{Texture, SolidColor};
+use crate::onb::Onb;
 
-pub trait Material {
-    fn scatter(self: &Self, r_in: &Ray, hr: &HitRecord) -> Option<(Color, Ray)>;
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+pub struct ScatterRecord {
+    // Throughput of the scattered ray, the BSDF times the cosine divided by
+    // the pdf of the sampled direction.
+    pub attenuation: Color,
+    pub scattered: Ray,
+    // Solid angle density the direction was sampled with, None for specular
+    // scattering which can't be evaluated for any other direction.
+    pub pdf: Option<f64>,
+}
+
+pub trait Material: Send + Sync {
+    fn scatter(&self, r_in: &Ray, hr: &HitRecord, sampler: &mut Sampler) -> Option<ScatterRecord>;
+
+    // BSDF times the cosine with the normal for light leaving along
+    // `direction`, used when sampling lights explicitly. Zero for specular
+    // materials.
+    fn eval(&self, _r_in: &Ray, _hr: &HitRecord, _direction: &Vec3) -> Color {
+        Color::new(0.0, 0.0, 0.0)
+    }
+
+    // Density with which scatter would have chosen `direction`.
+    fn pdf(&self, _r_in: &Ray, _hr: &HitRecord, _direction: &Vec3) -> f64 {
+        0.0
+    }
+
+    // Base color at the hit point, written to the albedo AOV.
+    fn albedo(&self, _hr: &HitRecord) -> Color {
+        Color::new(0.0, 0.0, 0.0)
+    }
+
+    // Light emitted from the hit point, most materials don't emit any.
+    fn emitted(&self, _hr: &HitRecord) -> Color {
+        Color::new(0.0, 0.0, 0.0)
+    }
+
+    // Perturbed shading normal at the hit point, None keeps the normal of
+    // the surface. Applied before any of the other methods see the hit.
+    fn shading_normal(&self, _hr: &HitRecord) -> Option<Vec3> {
+        None
+    }
 }
 
 pub struct Lambertian {
-    albedo: Color,
+    albedo: Arc<dyn Texture>,
 }
 
 impl Lambertian {
 
     pub fn new(albedo: Color) -> Lambertian {
+        Lambertian::textured(Arc::new(SolidColor::new(albedo)))
+    }
+
+    pub fn textured(albedo: Arc<dyn Texture>) -> Lambertian {
         Lambertian {
             albedo,
         }
     }
-    
+
 }
 
 impl Material for Lambertian {
 
-    fn scatter(&self, r_in: &Ray, hr: &HitRecord) -> Option<(Color, Ray)> {
-        let mut scatter_direction = &hr.normal + Vec3::random_unit_vector();
+    fn scatter(&self, r_in: &Ray, hr: &HitRecord, sampler: &mut Sampler) -> Option<ScatterRecord> {
+        // Offsetting the normal by a random unit vector samples directions
+        // proportionally to the cosine, which cancels the lambertian BSDF.
+        let mut scatter_direction = &hr.normal + Vec3::random_unit_vector(sampler);
 
         if scatter_direction.near_zero() {
             scatter_direction = hr.normal.clone();
         }
 
-        let scattered = Ray::new(hr.p.clone(), scatter_direction);
-        let attenuation = self.albedo.clone();
+        let pdf = self.pdf(r_in, hr, &scatter_direction);
+        let scattered = Ray::with_time(hr.p.clone(), scatter_direction, r_in.time);
+        let attenuation = self.albedo(hr);
+
+        Some(ScatterRecord { attenuation, scattered, pdf: Some(pdf) })
+    }
+
+    fn eval(&self, r_in: &Ray, hr: &HitRecord, direction: &Vec3) -> Color {
+        self.pdf(r_in, hr, direction) * self.albedo(hr)
+    }
+
+    fn albedo(&self, hr: &HitRecord) -> Color {
+        self.albedo.value(hr.u, hr.v, &hr.p)
+    }
 
-        Some((attenuation, scattered))
+    fn pdf(&self, _r_in: &Ray, hr: &HitRecord, direction: &Vec3) -> f64 {
+        let cosine = Vec3::dot(&hr.normal, &direction.unit_vector());
+        cosine.max(0.0) / PI
     }
 
 }
 
 pub struct Metal {
-    albedo: Color,
-    fuzz: f64,
+    albedo: Arc<dyn Texture>,
+    fuzz: Arc<dyn Texture>,
 }
 
 impl Metal {
 
     pub fn new(albedo: Color, fuzz: f64) -> Metal {
+        Metal::textured(Arc::new(SolidColor::new(albedo)), Arc::new(SolidColor::new(Color::new(fuzz, fuzz, fuzz))))
+    }
+
+    pub fn textured(albedo: Arc<dyn Texture>, fuzz: Arc<dyn Texture>) -> Metal {
         Metal {
             albedo,
             fuzz,
         }
     }
-    
+
 }
 impl Material for Metal {
 
-    fn scatter(&self, r_in: &Ray, hr: &HitRecord) -> Option<(Color, Ray)> {
+    fn scatter(&self, r_in: &Ray, hr: &HitRecord, sampler: &mut Sampler) -> Option<ScatterRecord> {
         let reflected = Vec3::reflect(&r_in.direction, &hr.normal);
 
-        let scattered = Ray::new(hr.p.clone(), reflected.unit_vector() + (self.fuzz * Vec3::random_unit_vector()));
-        let attenuation = self.albedo.clone();
+        let fuzz = self.fuzz.scalar(hr.u, hr.v, &hr.p);
+        let scattered = Ray::with_time(hr.p.clone(), reflected.unit_vector() + (fuzz * Vec3::random_unit_vector(sampler)), r_in.time);
+        let attenuation = self.albedo(hr);
 
         if Vec3::dot(&scattered.direction, &hr.normal) > 0.0 {
-            Some((attenuation, scattered))
+            Some(ScatterRecord { attenuation, scattered, pdf: None })
         } else {
             None
         }
     }
 
+    fn albedo(&self, hr: &HitRecord) -> Color {
+        self.albedo.value(hr.u, hr.v, &hr.p)
+    }
+
 }
 
 pub struct Dielectric {
-    refraction_index: f64,
+    refraction_index: Arc<dyn Texture>,
 }
 
 impl Dielectric {
 
     pub fn new(refraction_index: f64) -> Dielectric {
+        Dielectric::textured(Arc::new(SolidColor::new(Color::new(refraction_index, refraction_index, refraction_index))))
+    }
+
+    // The index is read at every hit, so it can vary over the surface, as in
+    // glass of uneven composition.
+    pub fn textured(refraction_index: Arc<dyn Texture>) -> Dielectric {
         Dielectric {
             refraction_index,
         }
     }
-    
+
     fn reflectance(cosine: f64, refraction_index: f64) -> f64 {
         let mut r0 = (1.0 - refraction_index) / (1.0 + refraction_index);
         r0 = r0 * r0;
@@ -91,29 +170,424 @@ impl Dielectric {
 
 impl Material for Dielectric {
 
-    fn scatter(&self, r_in: &Ray, hr: &HitRecord) -> Option<(Color, Ray)> {
+    fn scatter(&self, r_in: &Ray, hr: &HitRecord, sampler: &mut Sampler) -> Option<ScatterRecord> {
         let attenuation = Color::new(1.0, 1.0, 1.0);
 
+        let refraction_index = self.refraction_index.scalar(hr.u, hr.v, &hr.p);
         let ri = match hr.face {
-                    Face::Front => 1.0 / self.refraction_index,
-                    Face::Back => self.refraction_index,
+                    Face::Front => 1.0 / refraction_index,
+                    Face::Back => refraction_index,
         };
 
         let unit_direction = r_in.direction.unit_vector();
         let cos_theta = Vec3::dot(&(-&unit_direction), &hr.normal).min(1.0);
         let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
 
-        let direction = if (ri * sin_theta > 1.0) || (Self::reflectance(cos_theta, ri) > Vec3::random_double()) {
+        let direction = if (ri * sin_theta > 1.0) || (Self::reflectance(cos_theta, ri) > Vec3::random_double(sampler)) {
                             Vec3::reflect(&unit_direction, &hr.normal)
                         } else {
                             Vec3::refract(&unit_direction, &hr.normal, ri)
                         };
 
-        let scattered = Ray::new(hr.p.clone(), direction);
+        let scattered = Ray::with_time(hr.p.clone(), direction, r_in.time);
+
+        Some(ScatterRecord { attenuation, scattered, pdf: None })
+
+    }
+
+    // Clear glass lets everything through.
+    fn albedo(&self, _hr: &HitRecord) -> Color {
+        Color::new(1.0, 1.0, 1.0)
+    }
+
+}
+
+pub struct DiffuseLight {
+    emit: Arc<dyn Texture>,
+}
+
+impl DiffuseLight {
+
+    pub fn new(emit: Color) -> DiffuseLight {
+        DiffuseLight::textured(Arc::new(SolidColor::new(emit)))
+    }
+
+    pub fn textured(emit: Arc<dyn Texture>) -> DiffuseLight {
+        DiffuseLight {
+            emit,
+        }
+    }
+
+}
+
+impl Material for DiffuseLight {
+
+    fn scatter(&self, _r_in: &Ray, _hr: &HitRecord, _sampler: &mut Sampler) -> Option<ScatterRecord> {
+        None
+    }
+
+    fn albedo(&self, hr: &HitRecord) -> Color {
+        let emit = self.emitted(hr);
+        Color::new(emit.x.min(1.0), emit.y.min(1.0), emit.z.min(1.0))
+    }
+
+    fn emitted(&self, hr: &HitRecord) -> Color {
+        self.emit.value(hr.u, hr.v, &hr.p)
+    }
+
+}
+
+// Phase function of a medium scattering light equally in every direction.
+// The albedo is the fraction of the light scattered rather than absorbed.
+pub struct Isotropic {
+    albedo: Arc<dyn Texture>,
+}
+
+impl Isotropic {
 
-        Some((attenuation, scattered))
+    pub fn new(albedo: Color) -> Isotropic {
+        Isotropic::textured(Arc::new(SolidColor::new(albedo)))
+    }
 
+    pub fn textured(albedo: Arc<dyn Texture>) -> Isotropic {
+        Isotropic {
+            albedo,
+        }
     }
 
 }
 
+impl Material for Isotropic {
+
+    fn scatter(&self, r_in: &Ray, hr: &HitRecord, sampler: &mut Sampler) -> Option<ScatterRecord> {
+        let scattered = Ray::with_time(hr.p.clone(), Vec3::random_unit_vector(sampler), r_in.time);
+        let attenuation = self.albedo(hr);
+
+        Some(ScatterRecord { attenuation, scattered, pdf: Some(1.0 / (4.0 * PI)) })
+    }
+
+    fn eval(&self, r_in: &Ray, hr: &HitRecord, direction: &Vec3) -> Color {
+        self.pdf(r_in, hr, direction) * self.albedo(hr)
+    }
+
+    fn pdf(&self, _r_in: &Ray, _hr: &HitRecord, _direction: &Vec3) -> f64 {
+        1.0 / (4.0 * PI)
+    }
+
+    fn albedo(&self, hr: &HitRecord) -> Color {
+        self.albedo.value(hr.u, hr.v, &hr.p)
+    }
+
+}
+
+// Henyey-Greenstein phase function, scattering mostly forward for a positive
+// asymmetry `g` and mostly backward for a negative one. `g` is the average
+// cosine between the incoming and scattered directions, in (-1, 1).
+pub struct HenyeyGreenstein {
+    albedo: Arc<dyn Texture>,
+    g: f64,
+}
+
+impl HenyeyGreenstein {
+
+    pub fn new(albedo: Color, g: f64) -> HenyeyGreenstein {
+        HenyeyGreenstein::textured(Arc::new(SolidColor::new(albedo)), g)
+    }
+
+    pub fn textured(albedo: Arc<dyn Texture>, g: f64) -> HenyeyGreenstein {
+        HenyeyGreenstein {
+            albedo,
+            g,
+        }
+    }
+
+    fn phase(&self, cosine: f64) -> f64 {
+        let g = self.g;
+        let denom = 1.0 + g * g - 2.0 * g * cosine;
+        (1.0 - g * g) / (4.0 * PI * denom * denom.sqrt())
+    }
+
+}
+
+impl Material for HenyeyGreenstein {
+
+    fn scatter(&self, r_in: &Ray, hr: &HitRecord, sampler: &mut Sampler) -> Option<ScatterRecord> {
+        // Inverts the distribution of the cosine with the incoming direction,
+        // which is uniform when g is close to zero.
+        let g = self.g;
+        let xi = Vec3::random_double(sampler);
+        let cosine = if g.abs() < 1e-3 {
+            1.0 - 2.0 * xi
+        } else {
+            let s = (1.0 - g * g) / (1.0 - g + 2.0 * g * xi);
+            ((1.0 + g * g - s * s) / (2.0 * g)).clamp(-1.0, 1.0)
+        };
+        let sine = (1.0 - cosine * cosine).sqrt();
+        let phi = 2.0 * PI * Vec3::random_double(sampler);
+
+        let frame = Onb::new(&r_in.direction);
+        let direction = frame.transform(&Vec3::new(sine * phi.cos(), sine * phi.sin(), cosine));
+        let scattered = Ray::with_time(hr.p.clone(), direction, r_in.time);
+        let attenuation = self.albedo(hr);
+
+        Some(ScatterRecord { attenuation, scattered, pdf: Some(self.phase(cosine)) })
+    }
+
+    fn eval(&self, r_in: &Ray, hr: &HitRecord, direction: &Vec3) -> Color {
+        self.pdf(r_in, hr, direction) * self.albedo(hr)
+    }
+
+    fn pdf(&self, r_in: &Ray, _hr: &HitRecord, direction: &Vec3) -> f64 {
+        self.phase(Vec3::dot(&r_in.direction.unit_vector(), &direction.unit_vector()))
+    }
+
+    fn albedo(&self, hr: &HitRecord) -> Color {
+        self.albedo.value(hr.u, hr.v, &hr.p)
+    }
+
+}
+
+pub enum NormalPerturbation {
+    // Tangent space normals encoded as colors, with x along the tangent, y
+    // along the bitangent and z along the normal. The texture must hold the
+    // raw values rather than sRGB decoded ones.
+    NormalMap(Arc<dyn Texture>),
+    // Height field, the surface being displaced by `scale` times the texture
+    // along its outward normal.
+    Bump { height: Arc<dyn Texture>, scale: f64 },
+}
+
+// Any material with its shading normal perturbed by a normal or bump map.
+pub struct NormalMapped {
+    material: Arc<dyn Material>,
+    perturbation: NormalPerturbation,
+}
+
+impl NormalMapped {
+
+    pub fn new(material: Arc<dyn Material>, perturbation: NormalPerturbation) -> NormalMapped {
+        NormalMapped {
+            material,
+            perturbation,
+        }
+    }
+
+    fn normal_map(hr: &HitRecord, texture: &Arc<dyn Texture>) -> Option<Vec3> {
+        let c = texture.value(hr.u, hr.v, &hr.p);
+        let (x, y, z) = (2.0 * c.x - 1.0, 2.0 * c.y - 1.0, 2.0 * c.z - 1.0);
+        let normal = (x * &hr.tangent) + (y * &hr.bitangent) + (z * &hr.normal);
+
+        if normal.near_zero() {
+            None
+        } else {
+            Some(normal.unit_vector())
+        }
+    }
+
+    // The height is differentiated with forward differences along dpdu and
+    // dpdv, or along the tangent frame for surfaces without derivatives in
+    // which case only solid textures vary.
+    fn bump(hr: &HitRecord, height: &Arc<dyn Texture>, scale: f64) -> Option<Vec3> {
+        const DELTA: f64 = 0.0005;
+
+        let has_derivatives = !hr.dpdu.near_zero() && !hr.dpdv.near_zero();
+        let (dpdu, dpdv, step) = if has_derivatives {
+            (hr.dpdu.clone(), hr.dpdv.clone(), DELTA)
+        } else {
+            (hr.tangent.clone(), hr.bitangent.clone(), 0.0)
+        };
+
+        let h = |du: f64, dv: f64, p: &Point3| scale * height.scalar(hr.u + du, hr.v + dv, p);
+        let h0 = h(0.0, 0.0, &hr.p);
+        let dhdu = (h(step, 0.0, &(&hr.p + DELTA * &dpdu)) - h0) / DELTA;
+        let dhdv = (h(0.0, step, &(&hr.p + DELTA * &dpdv)) - h0) / DELTA;
+
+        let outward = match hr.face {
+            Face::Front => hr.normal.clone(),
+            Face::Back => -&hr.normal,
+        };
+        let normal = Vec3::cross(&(dpdu + dhdu * &outward), &(dpdv + dhdv * &outward));
+
+        if normal.near_zero() {
+            None
+        } else {
+            Some(normal.unit_vector())
+        }
+    }
+
+}
+
+impl Material for NormalMapped {
+
+    fn scatter(&self, r_in: &Ray, hr: &HitRecord, sampler: &mut Sampler) -> Option<ScatterRecord> {
+        self.material.scatter(r_in, hr, sampler)
+    }
+
+    fn eval(&self, r_in: &Ray, hr: &HitRecord, direction: &Vec3) -> Color {
+        self.material.eval(r_in, hr, direction)
+    }
+
+    fn pdf(&self, r_in: &Ray, hr: &HitRecord, direction: &Vec3) -> f64 {
+        self.material.pdf(r_in, hr, direction)
+    }
+
+    fn albedo(&self, hr: &HitRecord) -> Color {
+        self.material.albedo(hr)
+    }
+
+    fn emitted(&self, hr: &HitRecord) -> Color {
+        self.material.emitted(hr)
+    }
+
+    // The caller orients the result to the side of the surface normal.
+    fn shading_normal(&self, hr: &HitRecord) -> Option<Vec3> {
+        match &self.perturbation {
+            NormalPerturbation::NormalMap(texture) => Self::normal_map(hr, texture),
+            NormalPerturbation::Bump { height, scale } => Self::bump(hr, height, *scale),
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::texture::UvChecker;
+    use std::f64::consts::FRAC_1_SQRT_2;
+
+    // Height growing along u.
+    struct Ramp;
+
+    impl Texture for Ramp {
+
+        fn value(&self, u: f64, _v: f64, _p: &Point3) -> Color {
+            Color::new(u, u, u)
+        }
+
+    }
+
+    // Hit on the z = 0 plane, parameterized by x and y, seen from +z or -z.
+    fn hit(material: Arc<dyn Material>, from_above: bool) -> HitRecord {
+        let z = if from_above { 1.0 } else { -1.0 };
+        let r = Ray::new(Point3::new(0.25, 0.5, z), Vec3::new(0.0, 0.0, -z));
+
+        let mut hr = HitRecord::new(Point3::new(0.25, 0.5, 0.0), Vec3::new(0.0, 0.0, 1.0), material, 1.0, 0.25, 0.5, Face::Front);
+        hr.set_face_normal(&r, &Vec3::new(0.0, 0.0, 1.0));
+        hr.set_uv_derivatives(Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        hr.perturb_normal();
+        hr
+    }
+
+    fn normal_mapped(perturbation: NormalPerturbation) -> Arc<dyn Material> {
+        Arc::new(NormalMapped::new(Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))), perturbation))
+    }
+
+    fn assert_vec(e: Vec3, v: &Vec3) {
+        assert!((&e - v).length() < 1e-6, "expected {:?} found {:?}", e, v);
+    }
+
+    #[test]
+    fn test_normal_map() {
+        let map = |c: Color| normal_mapped(NormalPerturbation::NormalMap(Arc::new(SolidColor::new(c))));
+        let s = 0.5 * std::f64::consts::FRAC_1_SQRT_2;
+
+        assert_vec(Vec3::new(0.0, 0.0, 1.0), &hit(map(Color::new(0.5, 0.5, 1.0)), true).normal);
+        assert_vec(Vec3::new(1.0, 0.0, 0.0), &hit(map(Color::new(1.0, 0.5, 0.5)), true).normal);
+
+        let hr = hit(map(Color::new(0.5, 0.5 + s, 0.5 + s)), true);
+        assert_vec(Vec3::new(0.0, 1.0, 1.0).unit_vector(), &hr.normal);
+        assert_vec(Vec3::new(0.0, 0.0, 1.0), &hr.geometric_normal);
+        assert!((Vec3::dot(&hr.tangent, &hr.normal)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_bump_map() {
+        let bumped = normal_mapped(NormalPerturbation::Bump { height: Arc::new(Ramp), scale: 2.0 });
+
+        // The surface z = 2x.
+        let hr = hit(bumped.clone(), true);
+        assert_vec(Vec3::new(-2.0, 0.0, 1.0).unit_vector(), &hr.normal);
+
+        // Seen from below the slope is the same, the normal facing down.
+        let hr = hit(bumped, false);
+        assert_vec(Vec3::new(2.0, 0.0, -1.0).unit_vector(), &hr.normal);
+        assert_vec(Vec3::new(0.0, 0.0, -1.0), &hr.geometric_normal);
+
+        // Only directions between the two normals are rejected, transmitted
+        // ones are fine.
+        assert!(hr.same_side(&Vec3::new(0.0, 0.0, -1.0)));
+        assert!(!hr.same_side(&Vec3::new(-1.0, 0.0, -0.1)));
+        assert!(hr.same_side(&Vec3::new(0.0, 0.0, 1.0)));
+    }
+
+    #[test]
+    fn test_diffuse_light() {
+        let light: Arc<dyn Material> = Arc::new(DiffuseLight::new(Color::new(4.0, 2.0, 0.5)));
+        let mut sampler = Sampler::new(0);
+
+        // Both faces emit the same light and nothing is scattered.
+        for from_above in [true, false] {
+            let hr = hit(light.clone(), from_above);
+            let r = Ray::new(Point3::new(0.25, 0.5, 1.0), Vec3::new(0.0, 0.0, -1.0));
+
+            assert_eq!(light.emitted(&hr), Color::new(4.0, 2.0, 0.5));
+            assert!(light.scatter(&r, &hr, &mut sampler).is_none());
+        }
+
+        // Other materials don't emit.
+        let hr = hit(Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))), true);
+        assert_eq!(hr.material.emitted(&hr), Color::new(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_dielectric() {
+        // Entering at 45 degrees from above, along x.
+        let r = Ray::new(Point3::new(-0.75, 0.5, 1.0), Vec3::new(1.0, 0.0, -1.0));
+        let refracted = |refraction_index: Arc<dyn Texture>| {
+            let glass: Arc<dyn Material> = Arc::new(Dielectric::textured(refraction_index));
+            let mut sampler = Sampler::new(0);
+            // Transmission is far more likely than reflection, keep the first.
+            (0..100).map(|_| glass.scatter(&r, &hit(glass.clone(), true), &mut sampler).unwrap().scattered.direction)
+                    .find(|direction| direction.z < 0.0).unwrap()
+        };
+
+        // Snell's law, sin of the refracted angle is sin(45) / n.
+        let sin_theta = |direction: Vec3| direction.x / direction.length();
+        let index = |n: f64| Arc::new(SolidColor::new(Color::new(n, n, n))) as Arc<dyn Texture>;
+        assert!((sin_theta(refracted(index(1.0))) - FRAC_1_SQRT_2).abs() < 1e-9);
+        assert!((sin_theta(refracted(index(1.5))) - FRAC_1_SQRT_2 / 1.5).abs() < 1e-9);
+
+        // The index is looked up where the ray hits, (0.25, 0.5) is in an
+        // odd square.
+        let checker = Arc::new(UvChecker::new(2.0, index(1.0), index(1.5)));
+        assert!((sin_theta(refracted(checker)) - FRAC_1_SQRT_2 / 1.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_henyey_greenstein() {
+        let r = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -2.0));
+        let mut sampler = Sampler::new(0);
+        let n = 100000;
+
+        for g in [-0.7, 0.0, 0.3, 0.9] {
+            let phase = HenyeyGreenstein::new(Color::new(0.5, 0.5, 0.5), g);
+            let hr = HitRecord::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0), Arc::new(Isotropic::new(Color::new(0.5, 0.5, 0.5))), 1.0, 0.0, 0.0, Face::Front);
+
+            // The average cosine of the scattered directions is g, and the
+            // pdf they come with is the one evaluated for them.
+            let mut cosines = 0.0;
+            for _ in 0..n {
+                let srec = phase.scatter(&r, &hr, &mut sampler).unwrap();
+                let direction = &srec.scattered.direction;
+                assert!((srec.pdf.unwrap() - phase.pdf(&r, &hr, direction)).abs() < 1e-6 * srec.pdf.unwrap());
+                cosines += Vec3::dot(&r.direction.unit_vector(), &direction.unit_vector());
+            }
+            assert!((cosines / n as f64 - g).abs() < 0.01);
+
+            // It integrates to one over the sphere of directions.
+            let sum: f64 = (0..n).map(|_| phase.pdf(&r, &hr, &Vec3::random_unit_vector(&mut sampler))).sum();
+            assert!((4.0 * PI * sum / n as f64 - 1.0).abs() < 0.05);
+        }
+    }
+
+}
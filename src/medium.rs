@@ -0,0 +1,418 @@
+// Participating media, volumes scattering and absorbing light all along the
+// rays going through them instead of at a surface. The camera traces them on
+// top of the surfaces of the world.
+
+use crate::hittable::{Hittable, HitRecord, Face};
+use crate::ray::Ray;
+use crate::interval::Interval;
+use crate::material::{Material, ScatterRecord};
+use crate::sampler::Sampler;
+use crate::vec3::{Vec3, Point3};
+use crate::color::Color;
+use crate::aabb::Aabb;
+use crate::voxel::VoxelGrid;
+
+use std::sync::Arc;
+
+pub trait Medium: Send + Sync {
+    // Point along `r` within `ray_t` where the ray gets scattered or
+    // absorbed, with the phase function as its material. None when the ray
+    // goes through.
+    fn sample(&self, r: &Ray, ray_t: Interval, sampler: &mut Sampler) -> Option<HitRecord>;
+
+    // Fraction of the light going along `r` within `ray_t` that makes it
+    // through, or an unbiased estimate of it.
+    fn transmittance(&self, r: &Ray, ray_t: Interval, sampler: &mut Sampler) -> f64;
+
+    // Box holding the whole volume, rays missing it go through untouched.
+    fn bounding_box(&self) -> Aabb;
+}
+
+// Offset past a crossing of a boundary before looking for the next one.
+const CROSSING_EPSILON: f64 = 1e-4;
+
+// Parts of `ray_t` along `r` inside the closed `boundary`. Crossings of its
+// surface are paired from the start of the ray, so any closed shape works,
+// convex or not.
+fn inside<H: Hittable + ?Sized>(boundary: &H, r: &Ray, ray_t: Interval) -> Vec<Interval> {
+    let mut segments = vec![];
+    let mut t = -f64::INFINITY;
+
+    while let Some(enter) = boundary.hit(r, Interval::new(t, f64::INFINITY)) {
+        let exit = match boundary.hit(r, Interval::new(enter.t + CROSSING_EPSILON, f64::INFINITY)) {
+            Some(exit) => exit.t,
+            None => break,
+        };
+
+        let segment = Interval::new(enter.t.max(ray_t.min), exit.min(ray_t.max));
+        if segment.min < segment.max {
+            segments.push(segment);
+        }
+        if exit >= ray_t.max {
+            break;
+        }
+        t = exit + CROSSING_EPSILON;
+    }
+    segments
+}
+
+// Scattering point at `t` along `r`. The normal only faces the ray, phase
+// functions don't use it.
+fn scattering(r: &Ray, t: f64, phase: &Arc<dyn Material>) -> HitRecord {
+    let mut hr = HitRecord::new(r.at(t), -r.direction.unit_vector(), phase.clone(), t, 0.0, 0.0, Face::Front);
+    hr.in_medium = true;
+    hr
+}
+
+// Homogeneous volume filling a closed boundary, like fog or smoke. Light is
+// scattered or absorbed `density` times per unit of length on average, the
+// albedo of the phase function telling which.
+pub struct ConstantMedium<H> {
+    boundary: H,
+    density: f64,
+    phase: Arc<dyn Material>,
+    bbox: Aabb,
+}
+
+impl<H: Hittable> ConstantMedium<H> {
+
+    pub fn new(boundary: H, density: f64, phase: Arc<dyn Material>) -> ConstantMedium<H> {
+        let bbox = boundary.bounding_box();
+        ConstantMedium {
+            boundary,
+            density,
+            phase,
+            bbox,
+        }
+    }
+
+}
+
+impl<H: Hittable> Medium for ConstantMedium<H> {
+
+    // The distance to the next collision is exponentially distributed, and
+    // carries over from one segment to the next.
+    fn sample(&self, r: &Ray, ray_t: Interval, sampler: &mut Sampler) -> Option<HitRecord> {
+        let segments = inside(&self.boundary, r, ray_t);
+        if segments.is_empty() {
+            return None;
+        }
+
+        let length = r.direction.length();
+        let mut distance = -(1.0 - Vec3::random_double(sampler)).ln() / self.density;
+        for segment in segments {
+            let through = (segment.max - segment.min) * length;
+            if distance < through {
+                return Some(scattering(r, segment.min + distance / length, &self.phase));
+            }
+            distance -= through;
+        }
+        None
+    }
+
+    fn transmittance(&self, r: &Ray, ray_t: Interval, _sampler: &mut Sampler) -> f64 {
+        let length = r.direction.length();
+        let through: f64 = inside(&self.boundary, r, ray_t).iter().map(|segment| (segment.max - segment.min) * length).sum();
+        (-self.density * through).exp()
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+}
+
+// `p` in coordinates going from 0 to 1 across `bounds`, those of the grids.
+fn unit(bounds: &Aabb, p: &Point3) -> Point3 {
+    Point3::new((p.x - bounds.x.min) / bounds.x.size(),
+                (p.y - bounds.y.min) / bounds.y.size(),
+                (p.z - bounds.z.min) / bounds.z.size())
+}
+
+// Light given off by a heterogeneous medium, a grid over its bounds giving
+// the radiance of an optically thick part of it. Every collision picks up
+// the radiance where it happens.
+pub enum Emission {
+    // Radiance `color` times the value of the grid.
+    Scaled { grid: Arc<VoxelGrid>, color: Color },
+    // Black body radiation at the value of the grid times `scale` kelvins.
+    // It grows as the fourth power of the temperature, `intensity` being the
+    // radiance of the brightest component at 1000 K.
+    Blackbody { grid: Arc<VoxelGrid>, scale: f64, intensity: f64 },
+}
+
+impl Emission {
+
+    // Radiance at `p`, in unit cube coordinates.
+    fn radiance(&self, p: &Point3) -> Color {
+        match self {
+            Emission::Scaled { grid, color } => grid.lookup(p).max(0.0) * color,
+            Emission::Blackbody { grid, scale, intensity } => {
+                let kelvin = scale * grid.lookup(p);
+                (intensity * (kelvin / 1000.0).powi(4)) * Color::blackbody(kelvin)
+            },
+        }
+    }
+
+}
+
+// Phase function of a medium that also emits light at its collisions.
+struct Glowing {
+    phase: Arc<dyn Material>,
+    bounds: Aabb,
+    emission: Emission,
+}
+
+impl Material for Glowing {
+
+    fn scatter(&self, r_in: &Ray, hr: &HitRecord, sampler: &mut Sampler) -> Option<ScatterRecord> {
+        self.phase.scatter(r_in, hr, sampler)
+    }
+
+    fn eval(&self, r_in: &Ray, hr: &HitRecord, direction: &Vec3) -> Color {
+        self.phase.eval(r_in, hr, direction)
+    }
+
+    fn pdf(&self, r_in: &Ray, hr: &HitRecord, direction: &Vec3) -> f64 {
+        self.phase.pdf(r_in, hr, direction)
+    }
+
+    fn albedo(&self, hr: &HitRecord) -> Color {
+        self.phase.albedo(hr)
+    }
+
+    fn emitted(&self, hr: &HitRecord) -> Color {
+        self.emission.radiance(&unit(&self.bounds, &hr.p))
+    }
+
+}
+
+// Heterogeneous volume filling an axis aligned box, like a cloud or an
+// explosion, its density being the value of a grid over the box times
+// `scale`. Collisions are found by delta tracking and transmittance is
+// estimated by ratio tracking, both stepping through the box at the rate of
+// the largest density and rejecting the steps where it is thinner.
+pub struct GridMedium {
+    bounds: Aabb,
+    density: Arc<VoxelGrid>,
+    scale: f64,
+    majorant: f64,
+    material: Arc<dyn Material>,
+}
+
+impl GridMedium {
+
+    pub fn new(bounds: Aabb, density: Arc<VoxelGrid>, scale: f64, phase: Arc<dyn Material>, emission: Option<Emission>) -> GridMedium {
+        let material: Arc<dyn Material> = match emission {
+            Some(emission) => Arc::new(Glowing { phase, bounds, emission }),
+            None => phase,
+        };
+
+        GridMedium {
+            bounds,
+            majorant: scale * density.max(),
+            density,
+            scale,
+            material,
+        }
+    }
+
+    fn density_at(&self, p: &Point3) -> f64 {
+        self.scale * self.density.lookup(&unit(&self.bounds, p)).max(0.0)
+    }
+
+    // Next tentative collision after `t`, exponentially distributed at the
+    // rate of the majorant. `length` is the length of the ray direction.
+    fn step(&self, t: f64, length: f64, sampler: &mut Sampler) -> f64 {
+        t - (1.0 - Vec3::random_double(sampler)).ln() / (self.majorant * length)
+    }
+
+}
+
+impl Medium for GridMedium {
+
+    fn sample(&self, r: &Ray, ray_t: Interval, sampler: &mut Sampler) -> Option<HitRecord> {
+        let segment = self.bounds.intersect(r, ray_t)?;
+        if self.majorant <= 0.0 {
+            return None;
+        }
+
+        let length = r.direction.length();
+        let mut t = segment.min;
+        loop {
+            t = self.step(t, length, sampler);
+            if t >= segment.max {
+                return None;
+            }
+            // A real collision with the probability of the density over the
+            // majorant, otherwise a null one and the ray carries on.
+            if Vec3::random_double(sampler) * self.majorant < self.density_at(&r.at(t)) {
+                return Some(scattering(r, t, &self.material));
+            }
+        }
+    }
+
+    // Each tentative collision lets through the fraction of it that is null.
+    fn transmittance(&self, r: &Ray, ray_t: Interval, sampler: &mut Sampler) -> f64 {
+        let segment = match self.bounds.intersect(r, ray_t) {
+            Some(segment) if self.majorant > 0.0 => segment,
+            _ => return 1.0,
+        };
+
+        let length = r.direction.length();
+        let mut transmittance = 1.0;
+        let mut t = segment.min;
+        loop {
+            t = self.step(t, length, sampler);
+            if t >= segment.max {
+                return transmittance;
+            }
+            transmittance *= 1.0 - self.density_at(&r.at(t)) / self.majorant;
+        }
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bounds
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sphere::Sphere;
+    use crate::hittable_list::HittableList;
+    use crate::material::Isotropic;
+
+    fn phase() -> Arc<dyn Material> {
+        Arc::new(Isotropic::new(Color::new(0.5, 0.5, 0.5)))
+    }
+
+    #[test]
+    fn test_constant_medium() {
+        let sphere = Sphere::new(Point3::new(0.0, 0.0, 0.0), 1.0, phase());
+        let fog = ConstantMedium::new(sphere, 0.5, phase());
+        let mut sampler = Sampler::new(0);
+        let ray_t = Interval::new(0.001, f64::INFINITY);
+
+        // Through the center, from outside and from the center, with a
+        // direction that isn't a unit vector.
+        let r = Ray::new(Point3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 2.0));
+        assert!((fog.transmittance(&r, ray_t, &mut sampler) - (-1.0_f64).exp()).abs() < 1e-9);
+        let r = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 2.0, 0.0));
+        assert!((fog.transmittance(&r, ray_t, &mut sampler) - (-0.5_f64).exp()).abs() < 1e-3);
+        assert!((fog.transmittance(&r, Interval::new(0.001, 0.25), &mut sampler) - (-0.25_f64).exp()).abs() < 1e-3);
+
+        let miss = Ray::new(Point3::new(0.0, 2.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        assert_eq!(fog.transmittance(&miss, ray_t, &mut sampler), 1.0);
+        assert!(fog.sample(&miss, ray_t, &mut sampler).is_none());
+
+        // Rays get scattered inside with the probability of not going
+        // through.
+        let r = Ray::new(Point3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 2.0));
+        let n = 100000;
+        let mut scattered = 0;
+        for _ in 0..n {
+            if let Some(hr) = fog.sample(&r, ray_t, &mut sampler) {
+                assert!(hr.in_medium);
+                assert!(hr.p.length() <= 1.0 + 1e-9);
+                assert!((r.at(hr.t) - &hr.p).length() < 1e-9);
+                scattered += 1;
+            }
+        }
+        let expected = 1.0 - (-1.0_f64).exp();
+        assert!((scattered as f64 / n as f64 - expected).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_non_convex_boundary() {
+        let spheres: HittableList = vec![
+            Box::new(Sphere::new(Point3::new(0.0, 0.0, -2.0), 1.0, phase())),
+            Box::new(Sphere::new(Point3::new(0.0, 0.0, 2.0), 1.0, phase())),
+        ];
+        let fog = ConstantMedium::new(spheres, 0.25, phase());
+        let mut sampler = Sampler::new(0);
+
+        // Two units of fog in each sphere, none in the gap between them.
+        let r = Ray::new(Point3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        let segments = inside(&fog.boundary, &r, Interval::new(0.001, f64::INFINITY));
+        assert_eq!(segments.len(), 2);
+        assert!((segments[0].min - 2.0).abs() < 1e-9 && (segments[1].max - 8.0).abs() < 1e-9);
+        assert!((fog.transmittance(&r, Interval::new(0.001, f64::INFINITY), &mut sampler) - (-1.0_f64).exp()).abs() < 1e-9);
+
+        for _ in 0..1000 {
+            if let Some(hr) = fog.sample(&r, Interval::new(0.001, f64::INFINITY), &mut sampler) {
+                assert!(hr.p.z.abs() >= 1.0 - 1e-9);
+            }
+        }
+    }
+
+    fn unit_box() -> Aabb {
+        Aabb::from_points(&Point3::new(-1.0, -1.0, -1.0), &Point3::new(1.0, 1.0, 1.0))
+    }
+
+    #[test]
+    fn test_grid_medium() {
+        // Uniform, the same as a constant medium of density 0.5.
+        let grid = Arc::new(VoxelGrid::new(2, 2, 2, vec![2.0; 8]).unwrap());
+        let cloud = GridMedium::new(unit_box(), grid, 0.25, phase(), None);
+        let mut sampler = Sampler::new(0);
+        let ray_t = Interval::new(0.001, f64::INFINITY);
+        let expected = (-1.0_f64).exp();
+
+        let r = Ray::new(Point3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 2.0));
+        let n = 100000;
+        let (mut transmittance, mut scattered) = (0.0, 0);
+        for _ in 0..n {
+            transmittance += cloud.transmittance(&r, ray_t, &mut sampler);
+            if let Some(hr) = cloud.sample(&r, ray_t, &mut sampler) {
+                assert!(hr.in_medium && hr.p.z.abs() <= 1.0);
+                scattered += 1;
+            }
+        }
+        assert!((transmittance / n as f64 - expected).abs() < 0.01);
+        assert!((scattered as f64 / n as f64 - (1.0 - expected)).abs() < 0.01);
+
+        // Empty on the -x side, where nothing happens.
+        let grid = Arc::new(VoxelGrid::new(2, 1, 1, vec![0.0, 1.0]).unwrap());
+        let cloud = GridMedium::new(unit_box(), grid, 1.0, phase(), None);
+        let r = Ray::new(Point3::new(-0.75, -5.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        for _ in 0..100 {
+            assert_eq!(cloud.transmittance(&r, ray_t, &mut sampler), 1.0);
+            assert!(cloud.sample(&r, ray_t, &mut sampler).is_none());
+        }
+        let r = Ray::new(Point3::new(0.75, -5.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        let transmittance: f64 = (0..n).map(|_| cloud.transmittance(&r, ray_t, &mut sampler)).sum();
+        assert!((transmittance / n as f64 - (-2.0_f64).exp()).abs() < 0.01);
+
+        // Outside of the box, and without any density.
+        let miss = Ray::new(Point3::new(0.0, 2.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        assert_eq!(cloud.transmittance(&miss, ray_t, &mut sampler), 1.0);
+        let empty = GridMedium::new(unit_box(), Arc::new(VoxelGrid::new(1, 1, 1, vec![0.0]).unwrap()), 1.0, phase(), None);
+        assert!(empty.sample(&r, ray_t, &mut sampler).is_none());
+    }
+
+    #[test]
+    fn test_emission() {
+        let density = Arc::new(VoxelGrid::new(1, 1, 1, vec![100.0]).unwrap());
+        let heat = Arc::new(VoxelGrid::new(2, 1, 1, vec![0.0, 2.0]).unwrap());
+        let emission = Emission::Scaled { grid: heat.clone(), color: Color::new(1.0, 0.5, 0.25) };
+        let fire = GridMedium::new(unit_box(), density.clone(), 1.0, phase(), Some(emission));
+        let mut sampler = Sampler::new(0);
+
+        let r = Ray::new(Point3::new(0.75, -5.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        let hr = fire.sample(&r, Interval::new(0.001, f64::INFINITY), &mut sampler).unwrap();
+        assert_eq!(hr.material.emitted(&hr), Color::new(2.0, 1.0, 0.5));
+        assert_eq!(hr.material.albedo(&hr), Color::new(0.5, 0.5, 0.5));
+
+        // A thousand kelvins at the hot end, none at the other.
+        let emission = Emission::Blackbody { grid: heat, scale: 500.0, intensity: 3.0 };
+        let fire = GridMedium::new(unit_box(), density, 1.0, phase(), Some(emission));
+        let hr = fire.sample(&r, Interval::new(0.001, f64::INFINITY), &mut sampler).unwrap();
+        assert!((hr.material.emitted(&hr) - 3.0 * Color::blackbody(1000.0)).length() < 1e-9);
+        let r = Ray::new(Point3::new(-0.75, -5.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        let hr = fire.sample(&r, Interval::new(0.001, f64::INFINITY), &mut sampler).unwrap();
+        assert_eq!(hr.material.emitted(&hr), Color::new(0.0, 0.0, 0.0));
+    }
+
+}
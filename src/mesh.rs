@@ -0,0 +1,259 @@
+use crate::hittable::{Hittable, HitRecord};
+use crate::vec3::{Vec3, Point3};
+use crate::ray::Ray;
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::aabb::Aabb;
+use crate::triangle::{hit_triangle, triangle_bounding_box};
+
+use std::sync::Arc;
+
+// Faces per leaf of the mesh hierarchy.
+const LEAF_SIZE: usize = 4;
+
+// Indexed triangle mesh. The vertex buffers are shared by all faces and the
+// faces are organised in a flat bounding volume hierarchy of their own, so a
+// mesh is a single object for the scene BVH however many triangles it has.
+pub struct Mesh {
+    positions: Vec<Point3>,
+    normals: Vec<Vec3>,
+    uvs: Vec<(f64, f64)>,
+    faces: Vec<[usize; 3]>,
+    material: Arc<dyn Material>,
+    nodes: Vec<MeshNode>,
+}
+
+// Leaves cover faces[start..start + count], interior nodes have count 0 and
+// their children at the next index and at `right`.
+struct MeshNode {
+    bbox: Aabb,
+    start: usize,
+    count: usize,
+    right: usize,
+}
+
+impl Mesh {
+
+    // `normals` and `uvs` are either empty or have one entry per position.
+    // Panics if a face references a vertex that does not exist.
+    pub fn new(positions: Vec<Point3>, normals: Vec<Vec3>, uvs: Vec<(f64, f64)>, faces: Vec<[usize; 3]>, material: Arc<dyn Material>) -> Mesh {
+        assert!(normals.is_empty() || normals.len() == positions.len(), "mesh needs one normal per vertex");
+        assert!(uvs.is_empty() || uvs.len() == positions.len(), "mesh needs one uv per vertex");
+        assert!(faces.iter().flatten().all(|&i| i < positions.len()), "mesh face index out of range");
+
+        let mut mesh = Mesh {
+            positions,
+            normals,
+            uvs,
+            faces,
+            material,
+            nodes: vec![],
+        };
+
+        let count = mesh.faces.len();
+        if count > 0 {
+            mesh.build(0, count);
+        }
+        mesh
+    }
+
+    pub fn material(&self) -> &Arc<dyn Material> {
+        &self.material
+    }
+
+    fn face_points(&self, face: &[usize; 3]) -> [&Point3; 3] {
+        [&self.positions[face[0]], &self.positions[face[1]], &self.positions[face[2]]]
+    }
+
+    // Median split along the longest axis, reordering the faces in place.
+    fn build(&mut self, start: usize, count: usize) -> usize {
+        let bbox = (start..start + count)
+            .fold(Aabb::empty(), |bbox, face| Aabb::surrounding(&bbox, &triangle_bounding_box(self.face_points(&self.faces[face]))));
+
+        let index = self.nodes.len();
+        self.nodes.push(MeshNode { bbox, start, count, right: 0 });
+
+        if count <= LEAF_SIZE {
+            return index;
+        }
+
+        let axis = bbox.longest_axis();
+        let mid = count / 2;
+        let mut faces = self.faces[start..start + count].to_vec();
+        faces.select_nth_unstable_by(mid, |a, b| {
+            let a_centroid = triangle_bounding_box(self.face_points(a)).centroid(axis);
+            let b_centroid = triangle_bounding_box(self.face_points(b)).centroid(axis);
+            a_centroid.total_cmp(&b_centroid)
+        });
+        self.faces[start..start + count].copy_from_slice(&faces);
+
+        self.build(start, mid);
+        let right = self.build(start + mid, count - mid);
+
+        self.nodes[index].count = 0;
+        self.nodes[index].right = right;
+        index
+    }
+
+    fn hit_face(&self, face: usize, r: &Ray, ray_t: Interval) -> Option<HitRecord> {
+        let [a, b, c] = self.faces[face];
+
+        let normals = if self.normals.is_empty() {
+            None
+        } else {
+            Some([&self.normals[a], &self.normals[b], &self.normals[c]])
+        };
+        let uvs = if self.uvs.is_empty() {
+            [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)]
+        } else {
+            [self.uvs[a], self.uvs[b], self.uvs[c]]
+        };
+
+        hit_triangle(r, ray_t, self.face_points(&self.faces[face]), normals, uvs, &self.material)
+    }
+
+}
+
+impl Hittable for Mesh {
+
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+
+        let mut closest_so_far = ray_t.max;
+        let mut result: Option<HitRecord> = None;
+        let mut stack = vec![0];
+
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+
+            if !node.bbox.hit(r, Interval::new(ray_t.min, closest_so_far)) {
+                continue;
+            }
+
+            if node.count > 0 {
+                for face in node.start..node.start + node.count {
+                    if let Some(hit) = self.hit_face(face, r, Interval::new(ray_t.min, closest_so_far)) {
+                        closest_so_far = hit.t;
+                        result = Some(hit);
+                    }
+                }
+            } else {
+                stack.push(node.right);
+                stack.push(index + 1);
+            }
+        }
+        result
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        match self.nodes.first() {
+            Some(root) => root.bbox,
+            None => Aabb::empty(),
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable_list::HittableList;
+    use crate::triangle::Triangle;
+    use crate::material::Lambertian;
+    use crate::color::Color;
+    use crate::sampler::Sampler;
+
+    #[test]
+    fn test_mesh_matches_triangle_list() {
+        let material: Arc<dyn Material> = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+
+        let mut sampler = Sampler::new(0);
+
+        // A randomly displaced height field, so faces share vertices.
+        let n = 20;
+        let mut positions = vec![];
+        for j in 0..=n {
+            for i in 0..=n {
+                positions.push(Point3::new(i as f64 - 10.0, 2.0 * Vec3::random_double(&mut sampler), j as f64 - 10.0));
+            }
+        }
+        let mut faces = vec![];
+        for j in 0..n {
+            for i in 0..n {
+                let a = j * (n + 1) + i;
+                faces.push([a, a + 1, a + n + 1]);
+                faces.push([a + 1, a + n + 2, a + n + 1]);
+            }
+        }
+
+        let triangles: HittableList = faces.iter().map(|face| {
+            let vertices = [positions[face[0]].clone(), positions[face[1]].clone(), positions[face[2]].clone()];
+            Box::new(Triangle::new(vertices, material.clone())) as Box<dyn Hittable>
+        }).collect();
+        let mesh = Mesh::new(positions, vec![], vec![], faces, material.clone());
+
+        assert_eq!(mesh.faces.len(), triangles.len());
+
+        for _ in 0..2000 {
+            let origin = Vec3::random_interval(Interval::new(-15.0, 15.0), &mut sampler);
+            let r = Ray::new(origin, Vec3::random_unit_vector(&mut sampler));
+
+            let expected = triangles.hit(&r, Interval::new(0.001, f64::INFINITY));
+            let result = mesh.hit(&r, Interval::new(0.001, f64::INFINITY));
+
+            match (expected, result) {
+                (Some(e), Some(h)) => {
+                    assert_eq!(e.t, h.t);
+                    assert_eq!(e.normal, h.normal);
+                },
+                (None, None) => (),
+                (e, h) => panic!("[Mesh] Mismatch for {:?} {:?}: list {} mesh {}", r.origin, r.direction, e.is_some(), h.is_some()),
+            }
+        }
+    }
+
+    #[test]
+    fn test_triangle_hit() {
+        let material: Arc<dyn Material> = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let mut triangle = Triangle::new([Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 0.0, 0.0), Point3::new(0.0, 1.0, 0.0)], material);
+        triangle.uvs = [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)];
+
+        let r = Ray::new(Point3::new(0.25, 0.5, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        let hr = triangle.hit(&r, Interval::new(0.001, f64::INFINITY)).unwrap();
+
+        assert_eq!(hr.t, 1.0);
+        assert_eq!(hr.normal, Vec3::new(0.0, 0.0, 1.0));
+        assert_eq!((hr.u, hr.v), (0.25, 0.5));
+
+        let r = Ray::new(Point3::new(0.75, 0.5, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(triangle.hit(&r, Interval::new(0.001, f64::INFINITY)).is_none());
+    }
+
+    #[test]
+    fn test_triangle_uv_frame() {
+        let material: Arc<dyn Material> = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let mut triangle = Triangle::new([Point3::new(0.0, 0.0, 0.0), Point3::new(2.0, 0.0, 0.0), Point3::new(0.0, 4.0, 0.0)], material);
+        // Texture mapped upside down and twice along u.
+        triangle.uvs = [(0.0, 1.0), (1.0, 1.0), (0.0, 0.0)];
+
+        let r = Ray::new(Point3::new(0.5, 0.5, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        let hr = triangle.hit(&r, Interval::new(0.001, f64::INFINITY)).unwrap();
+
+        assert_eq!(hr.dpdu, Vec3::new(2.0, 0.0, 0.0));
+        assert_eq!(hr.dpdv, Vec3::new(0.0, -4.0, 0.0));
+        assert_eq!(hr.tangent, Vec3::new(1.0, 0.0, 0.0));
+        assert_eq!(hr.bitangent, Vec3::new(0.0, -1.0, 0.0));
+
+        // Degenerate UVs still give a frame around the normal.
+        triangle.uvs = [(0.5, 0.5); 3];
+        let hr = triangle.hit(&r, Interval::new(0.001, f64::INFINITY)).unwrap();
+
+        assert_eq!(hr.dpdu, Vec3::new(0.0, 0.0, 0.0));
+        assert!((hr.tangent.length() - 1.0).abs() < 1e-12);
+        assert!(Vec3::dot(&hr.tangent, &hr.normal).abs() < 1e-12);
+        assert!((Vec3::cross(&hr.tangent, &hr.bitangent) - &hr.normal).length() < 1e-12);
+    }
+
+}
@@ -0,0 +1,541 @@
+// Wavefront OBJ and MTL import.
+//
+// Faces are grouped by `g`/`o` group and `usemtl` material, each group
+// becoming a Mesh of its own. Polygons are triangulated as fans, so they are
+// expected to be convex. Statements that have no meaning for the renderer
+// (smoothing groups, lines, free-form geometry...) are ignored.
+
+use crate::vec3::{Vec3, Point3};
+use crate::color::Color;
+use crate::mesh::Mesh;
+use crate::material::{Material, Lambertian, Metal, Dielectric, DiffuseLight};
+use crate::texture::{Texture, ImageTexture, Wrap};
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io(PathBuf, std::io::Error),
+    Parse { path: PathBuf, line: usize, message: String },
+}
+
+impl fmt::Display for ObjError {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ObjError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            ObjError::Parse { path, line, message } => write!(f, "{}:{}: {}", path.display(), line, message),
+        }
+    }
+
+}
+
+impl std::error::Error for ObjError {}
+
+// Loads every group of the OBJ file as a mesh. When `material` is given it is
+// used for all faces and the MTL libraries are not read.
+pub fn load(path: &Path, material: Option<Arc<dyn Material>>) -> Result<Vec<Mesh>, ObjError> {
+    let source = fs::read_to_string(path).map_err(|e| ObjError::Io(path.to_path_buf(), e))?;
+    parse(&source, path, material)
+}
+
+// Parses OBJ source, `path` names it in errors and locates its MTL libraries.
+pub fn parse(source: &str, path: &Path, material: Option<Arc<dyn Material>>) -> Result<Vec<Mesh>, ObjError> {
+    let mut obj = Obj::new(path, material);
+
+    for (index, text) in source.lines().enumerate() {
+        let line = index + 1;
+        let text = strip_comment(text);
+        let mut tokens = text.split_whitespace();
+
+        let keyword = match tokens.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+        let args: Vec<&str> = tokens.collect();
+
+        obj.statement(keyword, &args, text).map_err(|message| ObjError::Parse { path: path.to_path_buf(), line, message })?;
+    }
+
+    Ok(obj.finish())
+}
+
+// One corner of a face: position, texture coordinate and normal indices.
+type Corner = (usize, Option<usize>, Option<usize>);
+
+struct Group {
+    material: Arc<dyn Material>,
+    faces: Vec<[Corner; 3]>,
+}
+
+struct Obj {
+    dir: PathBuf,
+    positions: Vec<Point3>,
+    uvs: Vec<(f64, f64)>,
+    normals: Vec<Vec3>,
+    override_material: Option<Arc<dyn Material>>,
+    materials: HashMap<String, Arc<dyn Material>>,
+    current: Arc<dyn Material>,
+    groups: Vec<Group>,
+}
+
+impl Obj {
+
+    fn new(path: &Path, override_material: Option<Arc<dyn Material>>) -> Obj {
+        let default_material: Arc<dyn Material> = match &override_material {
+            Some(material) => material.clone(),
+            None => Arc::new(Lambertian::new(Color::new(0.8, 0.8, 0.8))),
+        };
+
+        Obj {
+            dir: path.parent().unwrap_or(Path::new("")).to_path_buf(),
+            positions: vec![],
+            uvs: vec![],
+            normals: vec![],
+            override_material,
+            materials: HashMap::new(),
+            current: default_material,
+            groups: vec![],
+        }
+    }
+
+    fn statement(&mut self, keyword: &str, args: &[&str], text: &str) -> Result<(), String> {
+        match keyword {
+            "v" => {
+                let v = parse_floats(args, 3, keyword)?;
+                self.positions.push(Point3::new(v[0], v[1], v[2]));
+            },
+            "vn" => {
+                let v = parse_floats(args, 3, keyword)?;
+                self.normals.push(Vec3::new(v[0], v[1], v[2]).unit_vector());
+            },
+            "vt" => {
+                let v = parse_floats(args, 1, keyword)?;
+                self.uvs.push((v[0], v.get(1).copied().unwrap_or(0.0)));
+            },
+            "f" => {
+                if args.len() < 3 {
+                    return Err(format!("face needs at least three vertices, found {}", args.len()));
+                }
+                let corners = args.iter().map(|arg| self.corner(arg)).collect::<Result<Vec<Corner>, String>>()?;
+
+                let group = self.group();
+                for i in 1..corners.len() - 1 {
+                    group.faces.push([corners[0], corners[i], corners[i + 1]]);
+                }
+            },
+            "g" | "o" => self.start_group(self.current.clone()),
+            "usemtl" => {
+                if self.override_material.is_some() {
+                    return Ok(());
+                }
+                let name = rest_of_line(text, keyword);
+                let material = match self.materials.get(name) {
+                    Some(material) => material.clone(),
+                    None => return Err(format!("undefined material '{}'", name)),
+                };
+                self.start_group(material);
+            },
+            "mtllib" => {
+                if self.override_material.is_some() {
+                    return Ok(());
+                }
+                // Several libraries may be listed, later ones win on clashes.
+                for name in args {
+                    let materials = load_mtl(&self.dir.join(name)).map_err(|e| e.to_string())?;
+                    self.materials.extend(materials);
+                }
+            },
+            _ => (),
+        }
+        Ok(())
+    }
+
+    // Resolves a `v`, `v/vt`, `v//vn` or `v/vt/vn` reference, indices are one
+    // based and negative ones count back from the last element read.
+    fn corner(&self, arg: &str) -> Result<Corner, String> {
+        let mut parts = arg.split('/');
+
+        let position = resolve(parts.next().unwrap_or(""), self.positions.len(), "vertex")?;
+        let uv = match parts.next() {
+            Some(s) if !s.is_empty() => Some(resolve(s, self.uvs.len(), "texture coordinate")?),
+            _ => None,
+        };
+        let normal = match parts.next() {
+            Some(s) if !s.is_empty() => Some(resolve(s, self.normals.len(), "normal")?),
+            _ => None,
+        };
+        if parts.next().is_some() {
+            return Err(format!("invalid face vertex '{}'", arg));
+        }
+
+        Ok((position, uv, normal))
+    }
+
+    fn start_group(&mut self, material: Arc<dyn Material>) {
+        self.current = material.clone();
+        if self.groups.last().is_some_and(|g| g.faces.is_empty()) {
+            self.groups.pop();
+        }
+        self.groups.push(Group { material, faces: vec![] });
+    }
+
+    fn group(&mut self) -> &mut Group {
+        if self.groups.is_empty() {
+            self.start_group(self.current.clone());
+        }
+        self.groups.last_mut().unwrap()
+    }
+
+    // Builds one mesh per group, with its own compact vertex buffers. Normals
+    // and UVs are only kept if every corner of the group has them.
+    fn finish(self) -> Vec<Mesh> {
+        let mut meshes = vec![];
+
+        for group in self.groups.into_iter().filter(|g| !g.faces.is_empty()) {
+            let corners = || group.faces.iter().flatten();
+            let has_uvs = corners().all(|c| c.1.is_some());
+            let has_normals = corners().all(|c| c.2.is_some());
+
+            let mut remap: HashMap<Corner, usize> = HashMap::new();
+            let mut positions = vec![];
+            let mut uvs = vec![];
+            let mut normals = vec![];
+            let mut faces = vec![];
+
+            for face in &group.faces {
+                let mut indices = [0; 3];
+
+                for (k, &corner) in face.iter().enumerate() {
+                    let key = (corner.0, corner.1.filter(|_| has_uvs), corner.2.filter(|_| has_normals));
+
+                    indices[k] = *remap.entry(key).or_insert_with(|| {
+                        positions.push(self.positions[key.0].clone());
+                        if let Some(uv) = key.1 {
+                            uvs.push(self.uvs[uv]);
+                        }
+                        if let Some(normal) = key.2 {
+                            normals.push(self.normals[normal].clone());
+                        }
+                        positions.len() - 1
+                    });
+                }
+                faces.push(indices);
+            }
+
+            meshes.push(Mesh::new(positions, normals, uvs, faces, group.material));
+        }
+        meshes
+    }
+
+}
+
+// Reads an MTL library into materials by name. Emissive materials become
+// diffuse lights, transparent ones dielectrics, mostly specular ones metals
+// and the rest lambertians.
+pub fn load_mtl(path: &Path) -> Result<HashMap<String, Arc<dyn Material>>, ObjError> {
+    let source = fs::read_to_string(path).map_err(|e| ObjError::Io(path.to_path_buf(), e))?;
+    let dir = path.parent().unwrap_or(Path::new(""));
+
+    let mut materials = HashMap::new();
+    let mut current: Option<(String, Mtl)> = None;
+
+    for (index, text) in source.lines().enumerate() {
+        let text = strip_comment(text);
+        let mut tokens = text.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+        let args: Vec<&str> = tokens.collect();
+        let error = |message: String| ObjError::Parse { path: path.to_path_buf(), line: index + 1, message };
+
+        if keyword == "newmtl" {
+            if let Some((name, mtl)) = current.take() {
+                materials.insert(name, mtl.material());
+            }
+            current = Some((rest_of_line(text, keyword).to_string(), Mtl::new()));
+            continue;
+        }
+
+        let mtl = match current.as_mut() {
+            Some((_, mtl)) => mtl,
+            None if ["Kd", "Ks", "Ke", "Ns", "Ni", "d", "Tr", "illum", "map_Kd"].contains(&keyword) => {
+                return Err(error(format!("'{}' before any newmtl", keyword)));
+            },
+            None => continue,
+        };
+
+        match keyword {
+            "Kd" => mtl.kd = parse_color(args.as_slice(), keyword).map_err(error)?,
+            "Ks" => mtl.ks = parse_color(args.as_slice(), keyword).map_err(error)?,
+            "Ke" => mtl.ke = parse_color(args.as_slice(), keyword).map_err(error)?,
+            "Ns" => mtl.ns = parse_floats(&args, 1, keyword).map_err(error)?[0],
+            "Ni" => mtl.ni = parse_floats(&args, 1, keyword).map_err(error)?[0],
+            "d" => mtl.d = parse_floats(&args, 1, keyword).map_err(error)?[0],
+            "Tr" => mtl.d = 1.0 - parse_floats(&args, 1, keyword).map_err(error)?[0],
+            "illum" => mtl.illum = parse_floats(&args, 1, keyword).map_err(error)?[0] as u32,
+            "map_Kd" => {
+                // Options such as -s or -o may precede the file name.
+                let file = args.last().ok_or_else(|| error(String::from("map_Kd is missing a file name")))?;
+                let texture = ImageTexture::load(&dir.join(file), Wrap::Repeat, true).map_err(|e| error(format!("can't read '{}': {}", file, e)))?;
+                mtl.map_kd = Some(Arc::new(texture));
+            },
+            _ => (),
+        }
+    }
+
+    if let Some((name, mtl)) = current.take() {
+        materials.insert(name, mtl.material());
+    }
+    Ok(materials)
+}
+
+struct Mtl {
+    kd: Color,
+    // Replaces kd when present.
+    map_kd: Option<Arc<dyn Texture>>,
+    ks: Color,
+    ke: Color,
+    ns: f64,
+    ni: f64,
+    d: f64,
+    illum: u32,
+}
+
+impl Mtl {
+
+    fn new() -> Mtl {
+        Mtl {
+            kd: Color::new(0.8, 0.8, 0.8),
+            map_kd: None,
+            ks: Color::new(0.0, 0.0, 0.0),
+            ke: Color::new(0.0, 0.0, 0.0),
+            ns: 0.0,
+            ni: 1.0,
+            d: 1.0,
+            illum: 2,
+        }
+    }
+
+    fn material(&self) -> Arc<dyn Material> {
+        let max = |c: &Color| c.x.max(c.y).max(c.z);
+
+        // illum 4, 6, 7 and 9 are the transparent illumination models.
+        if max(&self.ke) > 0.0 {
+            Arc::new(DiffuseLight::new(self.ke.clone()))
+        } else if self.d < 1.0 || [4, 6, 7, 9].contains(&self.illum) {
+            let refraction_index = if self.ni > 1.0 { self.ni } else { 1.5 };
+            Arc::new(Dielectric::new(refraction_index))
+        } else if self.illum == 3 || max(&self.ks) > max(&self.kd) {
+            // Map the Phong exponent to a fuzz radius, sharper highlights
+            // meaning a smoother metal.
+            let fuzz = (2.0 / (self.ns + 2.0)).sqrt().min(1.0);
+            Arc::new(Metal::new(self.ks.clone(), fuzz))
+        } else if let Some(map_kd) = &self.map_kd {
+            Arc::new(Lambertian::textured(map_kd.clone()))
+        } else {
+            Arc::new(Lambertian::new(self.kd.clone()))
+        }
+    }
+
+}
+
+fn strip_comment(text: &str) -> &str {
+    match text.find('#') {
+        Some(i) => &text[..i],
+        None => text,
+    }
+}
+
+// Names may contain spaces, so take everything after the keyword.
+fn rest_of_line<'a>(text: &'a str, keyword: &str) -> &'a str {
+    text.trim_start()[keyword.len()..].trim()
+}
+
+fn resolve(s: &str, len: usize, what: &str) -> Result<usize, String> {
+    let index: i64 = s.parse().map_err(|_| format!("invalid {} index '{}'", what, s))?;
+
+    let resolved = if index > 0 {
+        index - 1
+    } else {
+        len as i64 + index
+    };
+
+    if index == 0 || resolved < 0 || resolved >= len as i64 {
+        return Err(format!("{} index {} out of range, {} defined so far", what, index, len));
+    }
+    Ok(resolved as usize)
+}
+
+fn parse_floats(args: &[&str], min: usize, keyword: &str) -> Result<Vec<f64>, String> {
+    if args.len() < min {
+        return Err(format!("'{}' expects {} numbers, found {}", keyword, min, args.len()));
+    }
+    args.iter().map(|arg| {
+        arg.parse::<f64>().map_err(|_| format!("'{}' expects numbers, found '{}'", keyword, arg))
+    }).collect()
+}
+
+fn parse_color(args: &[&str], keyword: &str) -> Result<Color, String> {
+    if args.first() == Some(&"spectral") || args.first() == Some(&"xyz") {
+        return Err(format!("'{}' only supports RGB colors", keyword));
+    }
+    let v = parse_floats(args, 1, keyword)?;
+    // A single value is a grey level.
+    match v.len() {
+        1 | 2 => Ok(Color::new(v[0], v[0], v[0])),
+        _ => Ok(Color::new(v[0], v[1], v[2])),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ray::Ray;
+    use crate::interval::Interval;
+    use crate::hittable::Hittable;
+    use crate::hittable_list::HittableList;
+
+    fn world(meshes: Vec<Mesh>) -> HittableList {
+        meshes.into_iter().map(|mesh| Box::new(mesh) as Box<dyn Hittable>).collect()
+    }
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("scenes").join(name)
+    }
+
+    #[test]
+    fn test_load_cube() {
+        let world = world(load(&fixture("cube.obj"), None).unwrap());
+
+        // One mesh per material group.
+        assert_eq!(world.len(), 2);
+
+        let bbox = world.bounding_box();
+        for axis in 0..3 {
+            let interval = bbox.axis_interval(axis);
+            assert!((interval.min + 1.0).abs() < 1e-3 && (interval.max - 1.0).abs() < 1e-3);
+        }
+
+        // Along every axis a ray from outside hits the nearest face at t = 4.
+        let directions = [Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, 0.0, 1.0)];
+        for d in directions {
+            for sign in [1.0, -1.0] {
+                let r = Ray::new(-5.0 * sign * &d + Vec3::new(0.1, 0.2, 0.3), sign * &d);
+                let hr = world.hit(&r, Interval::new(0.001, f64::INFINITY)).unwrap();
+
+                assert!((hr.t - (4.0 - Vec3::dot(&Vec3::new(0.1, 0.2, 0.3), &d) * sign)).abs() < 1e-9);
+                assert_eq!(hr.normal, -sign * &d);
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let data: [(&str, usize, &str); 4] = [
+            ("v 0 0 0\nv 1 0 0\nf 1 2 3", 3, "vertex index 3 out of range, 2 defined so far"),
+            ("v 0 0\n", 1, "'v' expects 3 numbers, found 2"),
+            ("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2", 4, "face needs at least three vertices, found 2"),
+            ("usemtl missing", 1, "undefined material 'missing'"),
+        ];
+
+        for (source, line, message) in data {
+            match parse(source, Path::new("test.obj"), None) {
+                Err(ObjError::Parse { line: l, message: m, .. }) => assert_eq!((l, m.as_str()), (line, message)),
+                _ => panic!("[OBJ] Expected an error for: {}", source),
+            }
+        }
+    }
+
+    #[test]
+    fn test_polygon_triangulation() {
+        let source = "v -1 -1 0\nv 1 -1 0\nv 1 1 0\nv -1 1 0\nvn 0 0 1\nf -4//1 -3//1 -2//1 -1//1\n";
+        let world = world(parse(source, Path::new("quad.obj"), None).unwrap());
+
+        assert_eq!(world.len(), 1);
+
+        for (x, y) in [(-0.9, -0.5), (0.9, 0.5), (0.5, -0.9), (-0.5, 0.9)] {
+            let r = Ray::new(Point3::new(x, y, 1.0), Vec3::new(0.0, 0.0, -1.0));
+            assert!(world.hit(&r, Interval::new(0.001, f64::INFINITY)).is_some());
+        }
+    }
+
+    #[test]
+    fn test_mtl_ke() {
+        let dir = std::env::temp_dir().join(format!("raytracer-test-mtl-ke-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("lamp.mtl"), "newmtl lamp\nKd 0.5 0.5 0.5\nKe 4 2 1\nnewmtl wall\nKd 0.5 0.5 0.5\n").unwrap();
+        fs::write(dir.join("lamp.obj"), "mtllib lamp.mtl\n\
+                                          v -1 -1 0\nv 1 -1 0\nv 1 1 0\nv -1 1 0\n\
+                                          usemtl lamp\nf 1 2 3\nusemtl wall\nf 1 3 4\n").unwrap();
+
+        let meshes = load(&dir.join("lamp.obj"), None);
+        fs::remove_dir_all(&dir).unwrap();
+        let world = world(meshes.unwrap());
+
+        // The lamp triangle is below the diagonal, the wall above it.
+        let emitted = |x: f64, y: f64| {
+            let r = Ray::new(Point3::new(x, y, 1.0), Vec3::new(0.0, 0.0, -1.0));
+            let hr = world.hit(&r, Interval::new(0.001, f64::INFINITY)).unwrap();
+            hr.material.emitted(&hr)
+        };
+        assert_eq!(emitted(0.5, -0.5), Color::new(4.0, 2.0, 1.0));
+        assert_eq!(emitted(-0.5, 0.5), Color::new(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_multiple_mtllib() {
+        let dir = std::env::temp_dir().join(format!("raytracer-test-mtllib-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.mtl"), "newmtl lamp\nKe 1 1 1\nnewmtl wall\nKd 0.5 0.5 0.5\n").unwrap();
+        fs::write(dir.join("b.mtl"), "newmtl lamp\nKe 4 2 1\n").unwrap();
+        fs::write(dir.join("two.obj"), "mtllib a.mtl b.mtl\n\
+                                         v -1 -1 0\nv 1 -1 0\nv 1 1 0\nv -1 1 0\n\
+                                         usemtl lamp\nf 1 2 3\nusemtl wall\nf 1 3 4\n").unwrap();
+
+        let meshes = load(&dir.join("two.obj"), None);
+        fs::remove_dir_all(&dir).unwrap();
+        let world = world(meshes.unwrap());
+
+        // Both libraries are loaded and the later one overrides 'lamp'.
+        let emitted = |x: f64, y: f64| {
+            let r = Ray::new(Point3::new(x, y, 1.0), Vec3::new(0.0, 0.0, -1.0));
+            let hr = world.hit(&r, Interval::new(0.001, f64::INFINITY)).unwrap();
+            hr.material.emitted(&hr)
+        };
+        assert_eq!(emitted(0.5, -0.5), Color::new(4.0, 2.0, 1.0));
+        assert_eq!(emitted(-0.5, 0.5), Color::new(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_map_kd() {
+        let dir = std::env::temp_dir().join(format!("raytracer-test-map-kd-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("quad.mtl"), "newmtl painted\nKd 0.5 0.5 0.5\nmap_Kd -s 1 1 1 paint.png\n").unwrap();
+        fs::write(dir.join("quad.obj"), "mtllib quad.mtl\nusemtl painted\n\
+                                          v -1 -1 0\nv 1 -1 0\nv 1 1 0\nv -1 1 0\n\
+                                          vt 0 0\nvt 1 0\nvt 1 1\nvt 0 1\n\
+                                          f 1/1 2/2 3/3 4/4\n").unwrap();
+        // Red on the left and blue on the right.
+        image::RgbImage::from_fn(2, 1, |x, _| image::Rgb(if x == 0 { [255, 0, 0] } else { [0, 0, 255] }))
+            .save(dir.join("paint.png")).unwrap();
+
+        let meshes = load(&dir.join("quad.obj"), None);
+        fs::remove_dir_all(&dir).unwrap();
+        let world = world(meshes.unwrap());
+
+        let albedo = |x: f64| {
+            let r = Ray::new(Point3::new(x, 0.0, 1.0), Vec3::new(0.0, 0.0, -1.0));
+            let hr = world.hit(&r, Interval::new(0.001, f64::INFINITY)).unwrap();
+            hr.material.albedo(&hr)
+        };
+        assert!((albedo(-0.5) - Color::new(1.0, 0.0, 0.0)).length() < 1e-6);
+        assert!((albedo(0.5) - Color::new(0.0, 0.0, 1.0)).length() < 1e-6);
+    }
+
+}
@@ -0,0 +1,26 @@
+use crate::vec3::Vec3;
+
+// Orthonormal basis with `w` along a given direction.
+pub struct Onb {
+    pub u: Vec3,
+    pub v: Vec3,
+    pub w: Vec3,
+}
+
+impl Onb {
+
+    pub fn new(n: &Vec3) -> Onb {
+        let w = n.unit_vector();
+        let a = if w.x.abs() > 0.9 { Vec3::new(0.0, 1.0, 0.0) } else { Vec3::new(1.0, 0.0, 0.0) };
+        let v = Vec3::cross(&w, &a).unit_vector();
+        let u = Vec3::cross(&w, &v);
+
+        Onb { u, v, w }
+    }
+
+    // Vector with the given coordinates in this basis, in world space.
+    pub fn transform(&self, v: &Vec3) -> Vec3 {
+        (v.x * &self.u) + (v.y * &self.v) + (v.z * &self.w)
+    }
+
+}
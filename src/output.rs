@@ -0,0 +1,134 @@
+use crate::framebuffer::Framebuffer;
+use crate::tonemap::ToneMap;
+
+use image::{ImageError, ImageFormat, ImageResult};
+use clap::ValueEnum;
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum OutputFormat {
+    Png,
+    Jpeg,
+    Bmp,
+    Ppm,
+    Exr,
+    Hdr,
+    Pfm,
+}
+
+impl OutputFormat {
+
+    pub fn from_path(path: &Path) -> Option<OutputFormat> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+
+        match extension.as_str() {
+            "png" => Some(OutputFormat::Png),
+            "jpg" | "jpeg" => Some(OutputFormat::Jpeg),
+            "bmp" => Some(OutputFormat::Bmp),
+            "ppm" => Some(OutputFormat::Ppm),
+            "exr" => Some(OutputFormat::Exr),
+            "hdr" => Some(OutputFormat::Hdr),
+            "pfm" => Some(OutputFormat::Pfm),
+            _ => None,
+        }
+    }
+
+    // Whether the format stores floating point values rather than 8-bit ones.
+    pub fn is_hdr(&self) -> bool {
+        matches!(self, OutputFormat::Exr | OutputFormat::Hdr | OutputFormat::Pfm)
+    }
+
+}
+
+// The tone map only applies to 8-bit formats, HDR formats get the radiance
+// as rendered.
+pub fn save(image: &Framebuffer, path: &Path, format: OutputFormat, tone_map: &ToneMap) -> ImageResult<()> {
+    match format {
+        OutputFormat::Png => image.to_rgb8(tone_map).save_with_format(path, ImageFormat::Png),
+        OutputFormat::Jpeg => image.to_rgb8(tone_map).save_with_format(path, ImageFormat::Jpeg),
+        OutputFormat::Bmp => image.to_rgb8(tone_map).save_with_format(path, ImageFormat::Bmp),
+        OutputFormat::Ppm => image.to_rgb8(tone_map).save_with_format(path, ImageFormat::Pnm),
+        OutputFormat::Exr => image.to_rgb32f().save_with_format(path, ImageFormat::OpenExr),
+        OutputFormat::Hdr => image.to_rgb32f().save_with_format(path, ImageFormat::Hdr),
+        OutputFormat::Pfm => {
+            let mut out = BufWriter::new(File::create(path)?);
+            write_pfm(image, &mut out)?;
+            out.flush().map_err(ImageError::IoError)
+        },
+    }
+}
+
+// Portable float map: a text header followed by little endian (negative
+// scale) 32-bit floats, with the bottom row first.
+fn write_pfm(image: &Framebuffer, out: &mut impl Write) -> std::io::Result<()> {
+    write!(out, "PF\n{} {}\n-1.0\n", image.width, image.height)?;
+
+    for j in (0..image.height).rev() {
+        for i in 0..image.width {
+            let c = image.get(i, j);
+            for component in [c.x, c.y, c.z] {
+                out.write_all(&(component as f32).to_le_bytes())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+
+    #[test]
+    fn test_from_path() {
+        let format = |path: &str| OutputFormat::from_path(Path::new(path));
+
+        assert_eq!(format("out/img.png"), Some(OutputFormat::Png));
+        assert_eq!(format("img.PNG"), Some(OutputFormat::Png));
+        assert_eq!(format("img.jpg"), Some(OutputFormat::Jpeg));
+        assert_eq!(format("img.JPEG"), Some(OutputFormat::Jpeg));
+        assert_eq!(format("img.Exr"), Some(OutputFormat::Exr));
+        assert_eq!(format("img.tiff"), None);
+        assert_eq!(format("img"), None);
+    }
+
+    #[test]
+    fn test_write_pfm() {
+        let mut image = Framebuffer::new(2, 2);
+        image.set(0, 0, Color::new(1.0, 2.0, 3.0));
+        image.set(1, 1, Color::new(100.0, 0.5, 0.0));
+
+        let mut out = vec![];
+        write_pfm(&image, &mut out).unwrap();
+
+        let header = b"PF\n2 2\n-1.0\n";
+        assert_eq!(&out[..header.len()], header);
+
+        let floats: Vec<f32> = out[header.len()..]
+            .chunks(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+
+        // The bottom row comes first.
+        assert_eq!(floats, vec![0.0, 0.0, 0.0, 100.0, 0.5, 0.0,
+                                1.0, 2.0, 3.0, 0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_exr_keeps_radiance() {
+        let mut image = Framebuffer::new(3, 1);
+        image.set(0, 0, Color::new(0.25, 1.0, 4.0));
+        image.set(2, 0, Color::new(1000.0, 0.0, 0.125));
+
+        let path = std::env::temp_dir().join(format!("raytracer-test-{}.exr", std::process::id()));
+        save(&image, &path, OutputFormat::Exr, &ToneMap::default()).unwrap();
+        let read = image::open(&path).unwrap().to_rgb32f();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(read.as_raw(), image.to_rgb32f().as_raw());
+    }
+
+}
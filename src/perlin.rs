@@ -0,0 +1,113 @@
+// Ken Perlin's gradient noise, with random unit gradients on the lattice
+// points and Hermite smoothed trilinear interpolation between them.
+
+use crate::vec3::{Vec3, Point3};
+use crate::sampler::Sampler;
+
+const POINT_COUNT: usize = 256;
+
+pub struct Perlin {
+    gradients: Vec<Vec3>,
+    perm_x: Vec<usize>,
+    perm_y: Vec<usize>,
+    perm_z: Vec<usize>,
+}
+
+impl Perlin {
+
+    // The lattice is drawn from `sampler`, the same seed gives the same noise.
+    pub fn new(sampler: &mut Sampler) -> Perlin {
+        let gradients = (0..POINT_COUNT).map(|_| Vec3::random_unit_vector(sampler)).collect();
+
+        Perlin {
+            gradients,
+            perm_x: Self::permutation(sampler),
+            perm_y: Self::permutation(sampler),
+            perm_z: Self::permutation(sampler),
+        }
+    }
+
+    // Fisher-Yates shuffle of 0..POINT_COUNT.
+    fn permutation(sampler: &mut Sampler) -> Vec<usize> {
+        let mut p: Vec<usize> = (0..POINT_COUNT).collect();
+        for i in (1..POINT_COUNT).rev() {
+            let target = (sampler.random_double() * (i + 1) as f64) as usize;
+            p.swap(i, target);
+        }
+        p
+    }
+
+    // Smooth noise in about [-1, 1], zero on the lattice points.
+    pub fn noise(&self, p: &Point3) -> f64 {
+        let (fx, fy, fz) = (p.x.floor(), p.y.floor(), p.z.floor());
+        let (u, v, w) = (p.x - fx, p.y - fy, p.z - fz);
+        let (i, j, k) = (fx as i64, fy as i64, fz as i64);
+
+        let hermite = |t: f64| t * t * (3.0 - 2.0 * t);
+        let (uu, vv, ww) = (hermite(u), hermite(v), hermite(w));
+
+        let mut accum = 0.0;
+        for di in 0..2 {
+            for dj in 0..2 {
+                for dk in 0..2 {
+                    let index = self.perm_x[((i + di) & 255) as usize]
+                        ^ self.perm_y[((j + dj) & 255) as usize]
+                        ^ self.perm_z[((k + dk) & 255) as usize];
+                    let weight = Vec3::new(u - di as f64, v - dj as f64, w - dk as f64);
+                    let (fi, fj, fk) = (di as f64, dj as f64, dk as f64);
+
+                    accum += (fi * uu + (1.0 - fi) * (1.0 - uu))
+                        * (fj * vv + (1.0 - fj) * (1.0 - vv))
+                        * (fk * ww + (1.0 - fk) * (1.0 - ww))
+                        * Vec3::dot(&self.gradients[index], &weight);
+                }
+            }
+        }
+        accum
+    }
+
+    // Sum of `depth` octaves of noise, each twice the frequency and half the
+    // amplitude of the previous one.
+    pub fn turbulence(&self, p: &Point3, depth: u32) -> f64 {
+        let mut accum = 0.0;
+        let mut p = p.clone();
+        let mut weight = 1.0;
+
+        for _ in 0..depth {
+            accum += weight * self.noise(&p);
+            weight *= 0.5;
+            p = 2.0 * p;
+        }
+        accum.abs()
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_noise() {
+        let perlin = Perlin::new(&mut Sampler::new(1));
+        let same = Perlin::new(&mut Sampler::new(1));
+        let mut sampler = Sampler::new(2);
+
+        assert_eq!(perlin.noise(&Point3::new(3.0, -2.0, 7.0)), 0.0);
+
+        let mut values = vec![];
+        for _ in 0..1000 {
+            let p = 20.0 * Vec3::random(&mut sampler);
+            let n = perlin.noise(&p);
+            assert!((-1.0..=1.0).contains(&n));
+            assert_eq!(n, same.noise(&p));
+            // Continuous: a small step gives a small change.
+            assert!((perlin.noise(&(&p + Vec3::new(1e-6, 0.0, 0.0))) - n).abs() < 1e-5);
+            values.push(n);
+        }
+        let mean = values.iter().sum::<f64>() / values.len() as f64;
+        assert!(mean.abs() < 0.05);
+        assert!(values.iter().any(|&n| n > 0.3) && values.iter().any(|&n| n < -0.3));
+    }
+
+}
@@ -0,0 +1,110 @@
+use crate::hittable::{Hittable, HitRecord, Face};
+use crate::vec3::{Vec3, Point3};
+use crate::ray::Ray;
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::aabb::Aabb;
+use crate::onb::Onb;
+
+use std::sync::Arc;
+
+// Infinite plane through `point`, facing along `normal`. The texture
+// coordinates are the distances from the point along two axes of the plane,
+// so textures repeat at every unit. It can't be sampled as a light.
+pub struct Plane {
+    pub point: Point3,
+    pub material: Arc<dyn Material>,
+    frame: Onb,
+}
+
+impl Plane {
+
+    pub fn new(point: Point3, normal: Vec3, material: Arc<dyn Material>) -> Plane {
+        Plane {
+            point,
+            material,
+            frame: Onb::new(&normal),
+        }
+    }
+
+    pub fn normal(&self) -> &Vec3 {
+        &self.frame.w
+    }
+
+}
+
+impl Hittable for Plane {
+
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord> {
+        let normal = self.normal();
+        let denom = Vec3::dot(normal, &r.direction);
+        if denom.abs() < 1e-8 {
+            return None;
+        }
+
+        let t = Vec3::dot(normal, &(&self.point - &r.origin)) / denom;
+        if !ray_t.surrounds(t) {
+            return None;
+        }
+
+        let p = r.at(t);
+        let offset = &p - &self.point;
+        let u = Vec3::dot(&offset, &self.frame.u);
+        let v = Vec3::dot(&offset, &self.frame.v);
+
+        let mut hr = HitRecord::new(p, normal.clone(), self.material.clone(), t, u, v, Face::Front);
+        hr.set_face_normal(r, normal);
+        hr.set_uv_derivatives(self.frame.u.clone(), self.frame.v.clone());
+        Some(hr)
+    }
+
+    // Unbounded, except along the normal when it is an axis.
+    fn bounding_box(&self) -> Aabb {
+        let n = self.normal();
+        let extent = |c: f64, x: f64| if c.abs() == 1.0 { (x, x) } else { (-f64::INFINITY, f64::INFINITY) };
+        let (x, y, z) = (extent(n.x, self.point.x), extent(n.y, self.point.y), extent(n.z, self.point.z));
+
+        Aabb::from_points(&Point3::new(x.0, y.0, z.0), &Point3::new(x.1, y.1, z.1))
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::color::Color;
+
+    #[test]
+    fn test_plane() {
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let plane = Plane::new(Point3::new(0.0, -1.0, 0.0), Vec3::new(0.0, 3.0, 0.0), material);
+        let ray_t = Interval::new(0.001, f64::INFINITY);
+
+        let bbox = plane.bounding_box();
+        assert!(bbox.y.min < -1.0 && bbox.y.max > -1.0 && bbox.y.size() < 0.001);
+        assert_eq!(bbox.x, Interval::default());
+
+        // Far away hits, from both sides.
+        let r = Ray::new(Point3::new(1000.0, 1.0, -500.0), Vec3::new(0.0, -1.0, 0.0));
+        let hr = plane.hit(&r, ray_t).unwrap();
+        assert_eq!(hr.t, 2.0);
+        assert!(matches!(hr.face, Face::Front));
+        assert_eq!(hr.normal, Vec3::new(0.0, 1.0, 0.0));
+        assert!(bbox.hit(&r, ray_t));
+
+        let hr = plane.hit(&Ray::new(Point3::new(0.0, -2.0, 0.0), Vec3::new(1.0, 1.0, 0.0)), ray_t).unwrap();
+        assert!(matches!(hr.face, Face::Back));
+        assert_eq!(hr.p, Point3::new(1.0, -1.0, 0.0));
+
+        // Texture coordinates follow the distances on the plane.
+        let (a, b) = (Point3::new(2.0, -1.0, 3.0), Point3::new(-1.0, -1.0, 7.0));
+        let down = Vec3::new(0.0, -1.0, 0.0);
+        let ha = plane.hit(&Ray::new(&a - &down, down.clone()), ray_t).unwrap();
+        let hb = plane.hit(&Ray::new(&b - &down, down), ray_t).unwrap();
+        assert!(((ha.u - hb.u).powi(2) + (ha.v - hb.v).powi(2) - 25.0).abs() < 1e-9);
+
+        assert!(plane.hit(&Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0)), ray_t).is_none());
+    }
+
+}
@@ -0,0 +1,212 @@
+use crate::hittable::{Hittable, HitRecord, Face, area_light_pdf};
+use crate::hittable_list::HittableList;
+use crate::vec3::{Vec3, Point3};
+use crate::ray::Ray;
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::aabb::Aabb;
+use crate::sampler::Sampler;
+
+use std::sync::Arc;
+
+// Parallelogram with a corner at `q` and edges `u` and `v`. The outward
+// normal is along u × v, and the texture coordinates of q + a u + b v are
+// (a, b).
+pub struct Quad {
+    pub q: Point3,
+    pub u: Vec3,
+    pub v: Vec3,
+    pub material: Arc<dyn Material>,
+    normal: Vec3,
+    d: f64,
+    w: Vec3,
+    area: f64,
+}
+
+impl Quad {
+
+    pub fn new(q: Point3, u: Vec3, v: Vec3, material: Arc<dyn Material>) -> Quad {
+        let n = Vec3::cross(&u, &v);
+        let normal = n.unit_vector();
+        let d = Vec3::dot(&normal, &q);
+        let w = &n / Vec3::dot(&n, &n);
+        let area = n.length();
+
+        Quad {
+            q,
+            u,
+            v,
+            material,
+            normal,
+            d,
+            w,
+            area,
+        }
+    }
+
+    // Ray parameter and planar coordinates of the hit, inside the
+    // parallelogram or not.
+    fn intersect(&self, r: &Ray, ray_t: Interval) -> Option<(f64, f64, f64)> {
+        let denom = Vec3::dot(&self.normal, &r.direction);
+        if denom.abs() < 1e-8 {
+            return None;
+        }
+
+        let t = (self.d - Vec3::dot(&self.normal, &r.origin)) / denom;
+        if !ray_t.surrounds(t) {
+            return None;
+        }
+
+        let planar = r.at(t) - &self.q;
+        let alpha = Vec3::dot(&self.w, &Vec3::cross(&planar, &self.v));
+        let beta = Vec3::dot(&self.w, &Vec3::cross(&self.u, &planar));
+        Some((t, alpha, beta))
+    }
+
+}
+
+impl Hittable for Quad {
+
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord> {
+        let (t, alpha, beta) = self.intersect(r, ray_t)?;
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return None;
+        }
+
+        let mut hr = HitRecord::new(r.at(t), self.normal.clone(), self.material.clone(), t, alpha, beta, Face::Front);
+        hr.set_face_normal(r, &self.normal);
+        hr.set_uv_derivatives(self.u.clone(), self.v.clone());
+        Some(hr)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let diagonal1 = Aabb::from_points(&self.q, &(&self.q + &self.u + &self.v));
+        let diagonal2 = Aabb::from_points(&(&self.q + &self.u), &(&self.q + &self.v));
+        Aabb::surrounding(&diagonal1, &diagonal2)
+    }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3, time: f64) -> f64 {
+        match self.hit(&Ray::with_time(origin.clone(), direction.clone(), time), Interval::new(0.001, f64::INFINITY)) {
+            Some(hr) => area_light_pdf(hr.t, direction, &self.normal, self.area),
+            None => 0.0,
+        }
+    }
+
+    fn random(&self, origin: &Point3, _time: f64, sampler: &mut Sampler) -> Vec3 {
+        let a = Vec3::random_double(sampler);
+        let b = Vec3::random_double(sampler);
+
+        let p = &self.q + (a * &self.u) + (b * &self.v);
+        p - origin
+    }
+
+}
+
+// Axis aligned box with a and b as opposite corners, in any order, made of
+// six quads facing outwards.
+pub fn make_box(a: &Point3, b: &Point3, material: Arc<dyn Material>) -> HittableList {
+    let min = Point3::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z));
+    let max = Point3::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z));
+
+    let dx = Vec3::new(max.x - min.x, 0.0, 0.0);
+    let dy = Vec3::new(0.0, max.y - min.y, 0.0);
+    let dz = Vec3::new(0.0, 0.0, max.z - min.z);
+
+    let faces = [
+        (Point3::new(min.x, min.y, max.z), dx.clone(), dy.clone()),  // front
+        (Point3::new(max.x, min.y, max.z), -&dz, dy.clone()),        // right
+        (Point3::new(max.x, min.y, min.z), -&dx, dy.clone()),        // back
+        (Point3::new(min.x, min.y, min.z), dz.clone(), dy),          // left
+        (Point3::new(min.x, max.y, max.z), dx.clone(), -&dz),        // top
+        (Point3::new(min.x, min.y, min.z), dx, dz),                  // bottom
+    ];
+
+    faces.into_iter().map(|(q, u, v)| -> Box<dyn Hittable> {
+        Box::new(Quad::new(q, u, v, material.clone()))
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::color::Color;
+
+    fn material() -> Arc<dyn Material> {
+        Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)))
+    }
+
+    #[test]
+    fn test_quad_hit() {
+        let quad = Quad::new(Point3::new(-1.0, -1.0, -2.0), Vec3::new(2.0, 0.0, 0.0), Vec3::new(1.0, 4.0, 0.0), material());
+        let ray_t = Interval::new(0.001, f64::INFINITY);
+
+        let r = Ray::new(Point3::new(0.5, 1.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let hr = quad.hit(&r, ray_t).unwrap();
+        assert_eq!(hr.t, 2.0);
+        assert!(matches!(hr.face, Face::Front));
+        assert_eq!(hr.normal, Vec3::new(0.0, 0.0, 1.0));
+        assert!((hr.u - 0.5).abs() < 1e-12 && (hr.v - 0.5).abs() < 1e-12);
+        assert_eq!(hr.dpdu, quad.u);
+        assert_eq!(hr.dpdv, quad.v);
+
+        // From behind, the normal faces the ray.
+        let r = Ray::new(Point3::new(0.5, 1.0, -4.0), Vec3::new(0.0, 0.0, 1.0));
+        let hr = quad.hit(&r, ray_t).unwrap();
+        assert!(matches!(hr.face, Face::Back));
+        assert_eq!(hr.normal, Vec3::new(0.0, 0.0, -1.0));
+
+        // Inside the bounding box but outside the slanted edge, and parallel.
+        assert!(quad.hit(&Ray::new(Point3::new(-0.9, 2.5, 0.0), Vec3::new(0.0, 0.0, -1.0)), ray_t).is_none());
+        assert!(quad.hit(&Ray::new(Point3::new(0.0, 0.0, -2.0), Vec3::new(1.0, 0.0, 0.0)), ray_t).is_none());
+    }
+
+    #[test]
+    fn test_light_sampling() {
+        let quad = Quad::new(Point3::new(-1.0, 2.0, -1.0), Vec3::new(2.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 2.0), material());
+        let origin = Point3::new(0.0, 1.0, 0.0);
+
+        let mut sampler = Sampler::new(0);
+        let (mut sum, n) = (0.0, 100000);
+        for _ in 0..n {
+            let direction = quad.random(&origin, 0.0, &mut sampler);
+            let pdf = quad.pdf_value(&origin, &direction, 0.0);
+            assert!(pdf > 0.0);
+            sum += 1.0 / pdf;
+        }
+
+        // The average of 1 / pdf is the solid angle of the quad, the one of
+        // a square face of a cube as seen from its center.
+        let solid_angle = 4.0 * std::f64::consts::PI / 6.0;
+        assert!((sum / n as f64 - solid_angle).abs() < 0.01 * solid_angle);
+
+        assert_eq!(quad.pdf_value(&origin, &Vec3::new(0.0, -1.0, 0.0), 0.0), 0.0);
+    }
+
+    #[test]
+    fn test_box() {
+        let faces = make_box(&Point3::new(1.0, 2.0, 3.0), &Point3::new(-1.0, -2.0, -3.0), material());
+        assert_eq!(faces.len(), 6);
+
+        // Up to the padding of the flat faces.
+        let bbox = faces.bounding_box();
+        assert!((bbox.x.min + 1.0).abs() < 1e-3 && (bbox.x.max - 1.0).abs() < 1e-3);
+        assert!((bbox.z.min + 3.0).abs() < 1e-3 && (bbox.z.max - 3.0).abs() < 1e-3);
+
+        // Every face is seen from the front along its axis, from outside.
+        let ray_t = Interval::new(0.001, f64::INFINITY);
+        for axis in [Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, 0.0, 1.0)] {
+            for sign in [-1.0, 1.0] {
+                let direction = sign * &axis;
+                let r = Ray::new(-10.0 * &direction, direction.clone());
+                let hr = faces.hit(&r, ray_t).unwrap();
+                assert!(matches!(hr.face, Face::Front));
+                assert_eq!(hr.normal, -&direction);
+
+                let r = Ray::new(Point3::new(0.0, 0.0, 0.0), direction.clone());
+                assert!(matches!(faces.hit(&r, ray_t).unwrap().face, Face::Back));
+            }
+        }
+    }
+
+}
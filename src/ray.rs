@@ -3,12 +3,18 @@ use crate::vec3::{Vec3, Point3};
 pub struct Ray {
     pub origin: Point3,
     pub direction: Vec3,
+    // Instant the ray is traced at, for motion blur.
+    pub time: f64,
 }
 
 impl Ray {
 
     pub fn new(origin: Point3, direction: Vec3) -> Ray {
-        Ray { origin, direction }
+        Ray::with_time(origin, direction, 0.0)
+    }
+
+    pub fn with_time(origin: Point3, direction: Vec3, time: f64) -> Ray {
+        Ray { origin, direction, time }
     }
 
     pub fn at(&self, t: f64) -> Point3 {
@@ -0,0 +1,120 @@
+// Source of every random number used while rendering. It is a PCG32 (XSH RR
+// 64/32) generator, whose output only depends on its seed so a render can be
+// reproduced bit for bit, and whose whole state is two integers that can be
+// saved and restored.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sampler {
+    state: u64,
+    increment: u64,
+}
+
+const MULTIPLIER: u64 = 6364136223846793005;
+
+impl Sampler {
+
+    pub fn new(seed: u64) -> Sampler {
+        Sampler::with_stream(mix(seed), 0)
+    }
+
+    // Independent stream for one pixel, the same whatever order the pixels
+    // are rendered in or by how many threads.
+    pub fn for_pixel(seed: u64, i: u32, j: u32) -> Sampler {
+        let index = ((j as u64) << 32) | i as u64;
+        Sampler::with_stream(mix(seed ^ mix(index)), index)
+    }
+
+    fn with_stream(state: u64, stream: u64) -> Sampler {
+        let increment = (stream << 1) | 1;
+        let mut sampler = Sampler::from_state(state.wrapping_add(increment), increment);
+        sampler.step();
+        sampler
+    }
+
+    // Restores a generator saved with state().
+    pub fn from_state(state: u64, increment: u64) -> Sampler {
+        Sampler {
+            state,
+            increment: increment | 1,
+        }
+    }
+
+    pub fn state(&self) -> (u64, u64) {
+        (self.state, self.increment)
+    }
+
+    fn step(&mut self) {
+        self.state = self.state.wrapping_mul(MULTIPLIER).wrapping_add(self.increment);
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let state = self.state;
+        self.step();
+
+        let rotation = (state >> 59) as u32;
+        let xorshifted = (((state >> 18) ^ state) >> 27) as u32;
+        xorshifted.rotate_right(rotation)
+    }
+
+    // Uniform in [0, 1), with the 53 bits of precision of an f64.
+    pub fn random_double(&mut self) -> f64 {
+        let low = self.next_u32() as u64;
+        let high = self.next_u32() as u64;
+        let bits = ((high << 32) | low) >> 11;
+
+        bits as f64 * (1.0 / (1u64 << 53) as f64)
+    }
+
+}
+
+// SplitMix64 finalizer, spreads nearby inputs over the whole state space.
+fn mix(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn draw(sampler: &mut Sampler) -> Vec<f64> {
+        (0..16).map(|_| sampler.random_double()).collect()
+    }
+
+    #[test]
+    fn test_streams() {
+        assert_eq!(draw(&mut Sampler::new(7)), draw(&mut Sampler::new(7)));
+        assert_ne!(draw(&mut Sampler::new(7)), draw(&mut Sampler::new(8)));
+
+        assert_eq!(draw(&mut Sampler::for_pixel(1, 3, 4)), draw(&mut Sampler::for_pixel(1, 3, 4)));
+        assert_ne!(draw(&mut Sampler::for_pixel(1, 3, 4)), draw(&mut Sampler::for_pixel(1, 4, 3)));
+        assert_ne!(draw(&mut Sampler::for_pixel(1, 3, 4)), draw(&mut Sampler::for_pixel(2, 3, 4)));
+
+        assert!(draw(&mut Sampler::new(0)).iter().all(|x| (0.0..1.0).contains(x)));
+    }
+
+    #[test]
+    fn test_pcg32_reference() {
+        // First outputs of the pcg32 reference implementation (pcg32-demo)
+        // seeded with state 42 and sequence 54.
+        let mut sampler = Sampler::with_stream(42, 54);
+        let expected: [u32; 6] = [0xa15c02b7, 0x7b47f409, 0xba1d3330, 0x83d2f293, 0xbfa4784b, 0xcbed606e];
+
+        for e in expected {
+            assert_eq!(sampler.next_u32(), e);
+        }
+    }
+
+    #[test]
+    fn test_state_round_trip() {
+        let mut sampler = Sampler::for_pixel(3, 1, 2);
+        draw(&mut sampler);
+
+        let (state, increment) = sampler.state();
+        let mut restored = Sampler::from_state(state, increment);
+
+        assert_eq!(draw(&mut restored), draw(&mut sampler));
+    }
+
+}
@@ -0,0 +1,1178 @@
+// Loader for the plain text scene description format.
+//
+// A scene file is a list of directives, one per line. Each directive starts
+// with a keyword, optionally followed by a name, and then `key=value`
+// parameters. Vectors and colors are written as `x,y,z` and values containing
+// spaces can be quoted. Everything after a `#` is a comment.
+//
+//     camera image_width=400 vfov=20 lookfrom=13,2,3 lookat=0,0,0
+//     texture tiles checker scale=2 even=0.2,0.3,0.1 odd=0.9,0.9,0.9
+//     material ground lambertian albedo=tiles
+//     sphere center=0,-1000,0 radius=1000 material=ground
+
+use crate::vec3::{Vec3, Point3};
+use crate::color::Color;
+use crate::hittable::{Hittable, Identified};
+use crate::hittable_list::HittableList;
+use crate::sphere::Sphere;
+use crate::triangle::Triangle;
+use crate::quad::{self, Quad};
+use crate::disk::Disk;
+use crate::plane::Plane;
+use crate::transform::{Transform, Transformed, Animated, Pose, Quaternion};
+use crate::animation::Keyframes;
+use crate::camera::Camera;
+use crate::material::{Material, Lambertian, Metal, Dielectric, DiffuseLight, Isotropic, HenyeyGreenstein, NormalMapped, NormalPerturbation};
+use crate::medium::{Medium, ConstantMedium, GridMedium, Emission};
+use crate::voxel::VoxelGrid;
+use crate::perlin::Perlin;
+use crate::sampler::Sampler;
+use crate::aabb::Aabb;
+use crate::texture::{Texture, SolidColor, Checker, UvChecker, ImageTexture, Wrap, NoiseTexture, NoisePattern};
+use crate::obj;
+use crate::background::{Background, SolidBackground, GradientBackground, EnvironmentMap};
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+pub struct Scene {
+    pub world: HittableList,
+    // Objects other than planes and meshes with an emissive material,
+    // sampled directly by the camera. They are shared with the world.
+    pub lights: HittableList,
+    pub camera: Camera,
+}
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io(std::io::Error),
+    Parse { line: usize, message: String },
+}
+
+impl fmt::Display for SceneError {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SceneError::Io(e) => write!(f, "{}", e),
+            SceneError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+        }
+    }
+
+}
+
+impl std::error::Error for SceneError {}
+
+impl From<std::io::Error> for SceneError {
+
+    fn from(e: std::io::Error) -> SceneError {
+        SceneError::Io(e)
+    }
+
+}
+
+impl Scene {
+
+    // Files referenced by the scene are looked up relative to its directory.
+    pub fn load(path: &Path) -> Result<Scene, SceneError> {
+        let source = fs::read_to_string(path)?;
+        Scene::parse_in(&source, path.parent().unwrap_or(Path::new("")))
+    }
+
+    pub fn parse(source: &str) -> Result<Scene, SceneError> {
+        Scene::parse_in(source, Path::new(""))
+    }
+
+    fn parse_in(source: &str, dir: &Path) -> Result<Scene, SceneError> {
+        let mut parser = Parser::new(dir);
+
+        for (index, text) in source.lines().enumerate() {
+            if let Some(directive) = Directive::tokenize(index + 1, text)? {
+                parser.directive(directive)?;
+            }
+        }
+
+        Ok(parser.finish())
+    }
+
+}
+
+// Where an object is placed, see Directive::transform.
+#[derive(Clone)]
+enum Placement {
+    Fixed(Box<Transform>),
+    Moving(Keyframes<Pose>),
+}
+
+// Phase function and density filling a closed shape, see Parser::volume.
+struct Volume {
+    phase: Arc<dyn Material>,
+    density: f64,
+}
+
+struct Parser {
+    dir: PathBuf,
+    world: HittableList,
+    lights: HittableList,
+    media: Vec<Box<dyn Medium>>,
+    camera: Option<Camera>,
+    background: Option<Arc<dyn Background>>,
+    textures: HashMap<String, Arc<dyn Texture>>,
+    grids: HashMap<String, Arc<VoxelGrid>>,
+    materials: HashMap<String, Arc<dyn Material>>,
+    emissive: HashSet<String>,
+    phase_functions: HashSet<String>,
+    // Every material in order of definition, OBJ ones included. The material
+    // ID of the AOVs is the position in this list plus one.
+    material_ids: Vec<Arc<dyn Material>>,
+}
+
+impl Parser {
+
+    fn new(dir: &Path) -> Parser {
+        Parser {
+            dir: dir.to_path_buf(),
+            world: vec![],
+            lights: vec![],
+            media: vec![],
+            camera: None,
+            background: None,
+            textures: HashMap::new(),
+            grids: HashMap::new(),
+            materials: HashMap::new(),
+            emissive: HashSet::new(),
+            phase_functions: HashSet::new(),
+            material_ids: vec![],
+        }
+    }
+
+    fn directive(&mut self, mut d: Directive) -> Result<(), SceneError> {
+        let placement = match d.keyword.as_str() {
+            "sphere" | "triangle" | "quad" | "disk" | "plane" | "box" | "obj" => d.transform()?,
+            _ => None,
+        };
+
+        match d.keyword.as_str() {
+            "camera" => {
+                if self.camera.is_some() {
+                    return Err(d.error("camera is already defined"));
+                }
+                let vfov = d.f64_or("vfov", 90.0)?;
+                if vfov <= 0.0 || vfov >= 180.0 {
+                    return Err(d.error(&format!("'vfov' must be between 0 and 180, found {}", vfov)));
+                }
+                let mut camera = Camera::new(
+                    d.positive_or("aspect_ratio", 1.0)?,
+                    d.positive_u32_or("image_width", 100)?,
+                    d.positive_u32_or("samples_per_pixel", 10)?,
+                    d.u32_or("max_depth", 10)?,
+                    vfov,
+                    d.vec3_or("lookfrom", Point3::new(0.0, 0.0, 0.0))?,
+                    d.vec3_or("lookat", Point3::new(0.0, 0.0, -1.0))?,
+                    d.vec3_or("vup", Vec3::new(0.0, 1.0, 0.0))?,
+                    d.f64_or("defocus_angle", 0.0)?,
+                    d.positive_or("focus_dist", 10.0)?,
+                );
+                let view = &camera.lookfrom - &camera.lookat;
+                if view.near_zero() {
+                    return Err(d.error("'lookfrom' and 'lookat' must differ"));
+                }
+                if camera.vup.near_zero() || Vec3::cross(&camera.vup.unit_vector(), &view.unit_vector()).near_zero() {
+                    return Err(d.error("'vup' must not be zero or parallel to the view direction"));
+                }
+                camera.shutter_open = d.f64_or("shutter_open", 0.0)?;
+                camera.shutter_close = d.f64_or("shutter_close", camera.shutter_open)?;
+                if camera.shutter_close < camera.shutter_open {
+                    return Err(d.error("'shutter_close' must not be before 'shutter_open'"));
+                }
+                d.finish()?;
+                self.camera = Some(camera);
+            },
+            "texture" => {
+                let name = d.name()?;
+                if self.textures.contains_key(&name) {
+                    return Err(d.error(&format!("texture '{}' is already defined", name)));
+                }
+                let kind = d.name()?;
+                let texture: Arc<dyn Texture> = match kind.as_str() {
+                    "solid" => Arc::new(SolidColor::new(d.color("color")?)),
+                    "checker" => Arc::new(Checker::new(d.positive_or("scale", 1.0)?, self.texture(&mut d, "even")?, self.texture(&mut d, "odd")?)),
+                    "uv_checker" => Arc::new(UvChecker::new(d.positive_or("squares", 8.0)?, self.texture(&mut d, "even")?, self.texture(&mut d, "odd")?)),
+                    "image" => {
+                        let path = self.dir.join(d.string("path")?);
+                        let wrap = match d.take("wrap").as_deref() {
+                            None | Some("repeat") => Wrap::Repeat,
+                            Some("clamp") => Wrap::Clamp,
+                            Some("mirror") => Wrap::Mirror,
+                            Some(wrap) => return Err(d.error(&format!("unknown wrap mode '{}'", wrap))),
+                        };
+                        let srgb = match d.take("encoding").as_deref() {
+                            None | Some("srgb") => true,
+                            Some("linear") => false,
+                            Some(encoding) => return Err(d.error(&format!("unknown encoding '{}'", encoding))),
+                        };
+                        let image = ImageTexture::load(&path, wrap, srgb)
+                            .map_err(|e| d.error(&format!("can't read '{}': {}", path.display(), e)))?;
+                        Arc::new(image)
+                    },
+                    "noise" | "turbulence" | "marble" => {
+                        let pattern = match kind.as_str() {
+                            "noise" => NoisePattern::Noise,
+                            "turbulence" => NoisePattern::Turbulence,
+                            _ => NoisePattern::Marble,
+                        };
+                        Arc::new(NoiseTexture::new(pattern,
+                                                   d.positive_or("scale", 1.0)?,
+                                                   d.vec3_or("color", Color::new(1.0, 1.0, 1.0))?,
+                                                   d.u32_or("seed", 0)? as u64))
+                    },
+                    _ => return Err(d.error(&format!("unknown texture type '{}'", kind))),
+                };
+                d.finish()?;
+                self.textures.insert(name, texture);
+            },
+            "grid" => {
+                let name = d.name()?;
+                if self.grids.contains_key(&name) {
+                    return Err(d.error(&format!("grid '{}' is already defined", name)));
+                }
+                let kind = d.name()?;
+                let grid = match kind.as_str() {
+                    "file" => {
+                        let path = self.dir.join(d.string("path")?);
+                        VoxelGrid::load(&path)
+                            .map_err(|e| d.error(&format!("can't read '{}': {}", path.display(), e)))?
+                    },
+                    "noise" | "turbulence" | "cloud" => {
+                        let n = match d.u32_or("resolution", 32)? {
+                            0 => return Err(d.error("'resolution' must be positive, found 0")),
+                            n => n as usize,
+                        };
+                        let scale = d.positive_or("scale", 4.0)?;
+                        let perlin = Perlin::new(&mut Sampler::new(d.u32_or("seed", 0)? as u64));
+                        VoxelGrid::from_fn(n, n, n, |p| {
+                            let q = scale * p;
+                            match kind.as_str() {
+                                "noise" => 0.5 * (1.0 + perlin.noise(&q)),
+                                "turbulence" => perlin.turbulence(&q, 7),
+                                // Turbulence fading out towards the sphere
+                                // inscribed in the grid.
+                                _ => {
+                                    let r = 2.0 * (p - Point3::new(0.5, 0.5, 0.5)).length();
+                                    (2.0 * (1.0 - r) * perlin.turbulence(&q, 7)).clamp(0.0, 1.0)
+                                },
+                            }
+                        })
+                    },
+                    _ => return Err(d.error(&format!("unknown grid type '{}'", kind))),
+                };
+                d.finish()?;
+                self.grids.insert(name, Arc::new(grid));
+            },
+            "material" => {
+                let name = d.name()?;
+                if self.materials.contains_key(&name) {
+                    return Err(d.error(&format!("material '{}' is already defined", name)));
+                }
+                let kind = d.name()?;
+                let material: Arc<dyn Material> = match kind.as_str() {
+                    "lambertian" => Arc::new(Lambertian::textured(self.texture(&mut d, "albedo")?)),
+                    "metal" => Arc::new(Metal::textured(self.texture(&mut d, "albedo")?, self.texture_or(&mut d, "fuzz", 0.0)?)),
+                    "dielectric" => Arc::new(Dielectric::textured(self.texture(&mut d, "refraction_index")?)),
+                    "diffuse_light" => Arc::new(DiffuseLight::textured(self.texture(&mut d, "emit")?)),
+                    "isotropic" => Arc::new(Isotropic::textured(self.texture(&mut d, "albedo")?)),
+                    "henyey_greenstein" => {
+                        let albedo = self.texture(&mut d, "albedo")?;
+                        let g = d.f64_or("g", 0.0)?;
+                        if g <= -1.0 || g >= 1.0 {
+                            return Err(d.error(&format!("'g' must be between -1 and 1, found {}", g)));
+                        }
+                        Arc::new(HenyeyGreenstein::textured(albedo, g))
+                    },
+                    _ => return Err(d.error(&format!("unknown material type '{}'", kind))),
+                };
+                let material = self.perturbed(&mut d, material)?;
+                d.finish()?;
+                if kind == "diffuse_light" {
+                    self.emissive.insert(name.clone());
+                }
+                if kind == "isotropic" || kind == "henyey_greenstein" {
+                    self.phase_functions.insert(name.clone());
+                }
+                self.material_id(&material);
+                self.materials.insert(name, material);
+            },
+            "background" => {
+                if self.background.is_some() {
+                    return Err(d.error("background is already defined"));
+                }
+                let kind = d.name()?;
+                let background: Arc<dyn Background> = match kind.as_str() {
+                    "solid" => Arc::new(SolidBackground::new(d.color("color")?)),
+                    "gradient" => Arc::new(GradientBackground::new(
+                        d.vec3_or("bottom", Color::new(1.0, 1.0, 1.0))?,
+                        d.vec3_or("top", Color::new(0.5, 0.7, 1.0))?,
+                    )),
+                    "envmap" => {
+                        let path = self.dir.join(d.string("path")?);
+                        let rotation = d.f64_or("rotation", 0.0)?;
+                        let intensity = d.f64_or("intensity", 1.0)?;
+                        let map = EnvironmentMap::load(&path, rotation, intensity)
+                            .map_err(|e| d.error(&format!("can't read '{}': {}", path.display(), e)))?;
+                        Arc::new(map)
+                    },
+                    _ => return Err(d.error(&format!("unknown background type '{}'", kind))),
+                };
+                d.finish()?;
+                self.background = Some(background);
+            },
+            "sphere" => {
+                let volume = self.volume(&mut d)?;
+                let (material, emissive) = match &volume {
+                    Some(volume) => (volume.phase.clone(), false),
+                    None => self.material(&mut d)?,
+                };
+                let sphere = Sphere::moving(d.path()?, d.positive("radius")?, material.clone());
+                d.finish()?;
+                match volume {
+                    Some(volume) => self.add_medium(sphere, placement, volume),
+                    None => self.add(sphere, placement, &material, emissive),
+                }
+            },
+            "triangle" => {
+                let vertices = [d.vec3("v0")?, d.vec3("v1")?, d.vec3("v2")?];
+                let (material, emissive) = self.material(&mut d)?;
+                let mut triangle = Triangle::new(vertices, material.clone());
+                triangle.normals = match (d.take("n0"), d.take("n1"), d.take("n2")) {
+                    (None, None, None) => None,
+                    (Some(n0), Some(n1), Some(n2)) => Some([d.parse_vec3("n0", &n0)?, d.parse_vec3("n1", &n1)?, d.parse_vec3("n2", &n2)?]),
+                    _ => return Err(d.error("triangle needs all of n0, n1 and n2 or none of them")),
+                };
+                d.finish()?;
+                self.add(triangle, placement, &material, emissive);
+            },
+            "quad" => {
+                let (q, u, v) = (d.vec3("q")?, d.vec3("u")?, d.vec3("v")?);
+                if Vec3::cross(&u, &v).near_zero() {
+                    return Err(d.error("quad edges 'u' and 'v' must not be parallel"));
+                }
+                let (material, emissive) = self.material(&mut d)?;
+                d.finish()?;
+                self.add(Quad::new(q, u, v, material.clone()), placement, &material, emissive);
+            },
+            "disk" => {
+                let (center, normal) = (d.vec3("center")?, d.direction("normal")?);
+                let radius = d.positive("radius")?;
+                let (material, emissive) = self.material(&mut d)?;
+                d.finish()?;
+                self.add(Disk::new(center, normal, radius, material.clone()), placement, &material, emissive);
+            },
+            "plane" => {
+                let (point, normal) = (d.vec3("point")?, d.direction("normal")?);
+                let (material, _) = self.material(&mut d)?;
+                d.finish()?;
+                // Unbounded, so never sampled as a light.
+                self.add(Plane::new(point, normal, material.clone()), placement, &material, false);
+            },
+            "box" => {
+                let (a, b) = (d.vec3("a")?, d.vec3("b")?);
+                let volume = self.volume(&mut d)?;
+                let (material, emissive) = match &volume {
+                    Some(volume) => (volume.phase.clone(), false),
+                    None => self.material(&mut d)?,
+                };
+                d.finish()?;
+                let faces = quad::make_box(&a, &b, material.clone());
+                match volume {
+                    Some(volume) => self.add_medium(faces, placement, volume),
+                    None => self.add(faces, placement, &material, emissive),
+                }
+            },
+            "volume" => {
+                let (a, b) = (d.vec3("a")?, d.vec3("b")?);
+                if a.x == b.x || a.y == b.y || a.z == b.z {
+                    return Err(d.error("volume corners 'a' and 'b' must differ along every axis"));
+                }
+                let density = self.grid(&mut d, "density")?;
+                let scale = d.positive_or("density_scale", 1.0)?;
+                let emission = match (d.take("emission"), d.take("temperature")) {
+                    (None, None) => None,
+                    (Some(emission), None) => Some(Emission::Scaled {
+                        grid: self.lookup_grid(&d, &emission)?,
+                        color: d.vec3_or("emit", Color::new(1.0, 1.0, 1.0))?,
+                    }),
+                    (None, Some(temperature)) => Some(Emission::Blackbody {
+                        grid: self.lookup_grid(&d, &temperature)?,
+                        scale: d.positive_or("temperature_scale", 1.0)?,
+                        intensity: d.positive_or("intensity", 1.0)?,
+                    }),
+                    (Some(_), Some(_)) => return Err(d.error("volume can't have both an emission and a temperature")),
+                };
+                let phase = self.phase(&mut d)?;
+                d.finish()?;
+                self.media.push(Box::new(GridMedium::new(Aabb::from_points(&a, &b), density, scale, phase, emission)));
+            },
+            "obj" => {
+                let path = self.dir.join(d.string("path")?);
+                let material = match d.take("material") {
+                    Some(name) => Some(self.lookup(&d, &name)?),
+                    None => None,
+                };
+                d.finish()?;
+                let meshes = obj::load(&path, material).map_err(|e| d.error(&e.to_string()))?;
+                for mesh in meshes {
+                    let material = mesh.material().clone();
+                    self.add(mesh, placement.clone(), &material, false);
+                }
+            },
+            keyword => return Err(d.error(&format!("unknown directive '{}'", keyword))),
+        }
+        Ok(())
+    }
+
+    // The material named by the directive and whether it emits light.
+    fn material(&self, d: &mut Directive) -> Result<(Arc<dyn Material>, bool), SceneError> {
+        let name = d.string("material")?;
+        let material = self.lookup(d, &name)?;
+        if self.phase_functions.contains(&name) {
+            return Err(d.error(&format!("material '{}' is a phase function, only volumes with a 'density' can use it", name)));
+        }
+        Ok((material, self.emissive.contains(&name)))
+    }
+
+    // Closed shapes given a `density` are filled with a homogeneous medium
+    // instead of having a surface, their material is then the phase function.
+    // None for a plain surface.
+    fn volume(&self, d: &mut Directive) -> Result<Option<Volume>, SceneError> {
+        let density = match d.take("density") {
+            Some(value) => match d.parse_f64("density", &value)? {
+                x if x > 0.0 => x,
+                x => return Err(d.error(&format!("'density' must be positive, found {}", x))),
+            },
+            None => return Ok(None),
+        };
+        Ok(Some(Volume { phase: self.phase(d)?, density }))
+    }
+
+    // The phase function named by the `material` of a volume.
+    fn phase(&self, d: &mut Directive) -> Result<Arc<dyn Material>, SceneError> {
+        let name = d.string("material")?;
+        let phase = self.lookup(d, &name)?;
+        if !self.phase_functions.contains(&name) {
+            return Err(d.error(&format!("volume material '{}' must be isotropic or henyey_greenstein", name)));
+        }
+        Ok(phase)
+    }
+
+    fn grid(&self, d: &mut Directive, key: &str) -> Result<Arc<VoxelGrid>, SceneError> {
+        let name = d.string(key)?;
+        self.lookup_grid(d, &name)
+    }
+
+    fn lookup_grid(&self, d: &Directive, name: &str) -> Result<Arc<VoxelGrid>, SceneError> {
+        match self.grids.get(name) {
+            Some(grid) => Ok(grid.clone()),
+            None => Err(d.error(&format!("undefined grid '{}'", name))),
+        }
+    }
+
+    // Color and scalar parameters of textures and materials are either a
+    // constant, `r,g,b` or a single number for a grey level, or the name of a
+    // texture.
+    fn texture(&self, d: &mut Directive, key: &str) -> Result<Arc<dyn Texture>, SceneError> {
+        let value = d.string(key)?;
+        self.parse_texture(d, key, &value)
+    }
+
+    fn texture_or(&self, d: &mut Directive, key: &str, default: f64) -> Result<Arc<dyn Texture>, SceneError> {
+        match d.take(key) {
+            Some(value) => self.parse_texture(d, key, &value),
+            None => Ok(Arc::new(SolidColor::new(Color::new(default, default, default)))),
+        }
+    }
+
+    fn parse_texture(&self, d: &Directive, key: &str, value: &str) -> Result<Arc<dyn Texture>, SceneError> {
+        if value.contains(',') {
+            return Ok(Arc::new(SolidColor::new(d.parse_vec3(key, value)?)));
+        }
+        if let Ok(x) = d.parse_f64(key, value) {
+            return Ok(Arc::new(SolidColor::new(Color::new(x, x, x))));
+        }
+        match self.textures.get(value) {
+            Some(texture) => Ok(texture.clone()),
+            None => Err(d.error(&format!("undefined texture '{}'", value))),
+        }
+    }
+
+    // Any material can take a normal map or a bump map.
+    fn perturbed(&self, d: &mut Directive, material: Arc<dyn Material>) -> Result<Arc<dyn Material>, SceneError> {
+        let perturbation = match (d.take("normal_map"), d.take("bump")) {
+            (None, None) => return Ok(material),
+            (Some(normal_map), None) => NormalPerturbation::NormalMap(self.parse_texture(d, "normal_map", &normal_map)?),
+            (None, Some(bump)) => NormalPerturbation::Bump {
+                height: self.parse_texture(d, "bump", &bump)?,
+                scale: d.f64_or("bump_scale", 1.0)?,
+            },
+            (Some(_), Some(_)) => return Err(d.error("material can't have both a normal_map and a bump")),
+        };
+        Ok(Arc::new(NormalMapped::new(material, perturbation)))
+    }
+
+    fn add<H: Hittable + 'static>(&mut self, object: H, placement: Option<Placement>, material: &Arc<dyn Material>, emissive: bool) {
+        match placement {
+            Some(Placement::Fixed(transform)) => self.push(Transformed::new(object, *transform), material, emissive),
+            Some(Placement::Moving(poses)) => self.push(Animated::new(object, poses), material, emissive),
+            None => self.push(object, material, emissive),
+        }
+    }
+
+    fn add_medium<H: Hittable + 'static>(&mut self, boundary: H, placement: Option<Placement>, volume: Volume) {
+        let Volume { phase, density } = volume;
+        let medium: Box<dyn Medium> = match placement {
+            Some(Placement::Fixed(transform)) => Box::new(ConstantMedium::new(Transformed::new(boundary, *transform), density, phase)),
+            Some(Placement::Moving(poses)) => Box::new(ConstantMedium::new(Animated::new(boundary, poses), density, phase)),
+            None => Box::new(ConstantMedium::new(boundary, density, phase)),
+        };
+        self.media.push(medium);
+    }
+
+    // Objects are numbered in the order they are added, from one. The camera
+    // relies on these IDs being unique to tell whether a hit is on a light.
+    fn push<H: Hittable + 'static>(&mut self, object: H, material: &Arc<dyn Material>, emissive: bool) {
+        let object = Identified::new(object, self.world.len() as u32 + 1, self.material_id(material));
+
+        if emissive {
+            let object = Arc::new(object);
+            self.lights.push(Box::new(object.clone()));
+            self.world.push(Box::new(object));
+        } else {
+            self.world.push(Box::new(object));
+        }
+    }
+
+    fn material_id(&mut self, material: &Arc<dyn Material>) -> u32 {
+        // Compares the data pointers only, vtable pointers of the same type
+        // are not guaranteed to be unique.
+        let same = |m: &Arc<dyn Material>| std::ptr::eq(Arc::as_ptr(m) as *const (), Arc::as_ptr(material) as *const ());
+
+        let index = match self.material_ids.iter().position(same) {
+            Some(index) => index,
+            None => {
+                self.material_ids.push(material.clone());
+                self.material_ids.len() - 1
+            },
+        };
+        index as u32 + 1
+    }
+
+    fn lookup(&self, d: &Directive, name: &str) -> Result<Arc<dyn Material>, SceneError> {
+        match self.materials.get(name) {
+            Some(material) => Ok(material.clone()),
+            None => Err(d.error(&format!("undefined material '{}'", name))),
+        }
+    }
+
+    fn finish(self) -> Scene {
+        let mut camera = self.camera.unwrap_or_else(|| {
+            Camera::new(1.0, 100, 10, 10, 90.0,
+                        Point3::new(0.0, 0.0, 0.0), Point3::new(0.0, 0.0, -1.0), Vec3::new(0.0, 1.0, 0.0),
+                        0.0, 10.0)
+        });
+        if let Some(background) = self.background {
+            camera.background = background;
+        }
+        camera.media = self.media;
+
+        Scene {
+            world: self.world,
+            lights: self.lights,
+            camera,
+        }
+    }
+
+}
+
+// A single tokenized line of the scene file. Parameters are removed as they
+// are read so that finish() can report any the directive does not know about.
+struct Directive {
+    line: usize,
+    keyword: String,
+    names: Vec<String>,
+    params: Vec<(String, String)>,
+}
+
+impl Directive {
+
+    fn tokenize(line: usize, text: &str) -> Result<Option<Directive>, SceneError> {
+        let mut tokens: Vec<String> = vec![];
+        let mut current = String::new();
+        let mut in_token = false;
+        let mut quoted = false;
+
+        for c in text.chars() {
+            match c {
+                '"' => {
+                    quoted = !quoted;
+                    in_token = true;
+                },
+                '#' if !quoted => break,
+                c if c.is_whitespace() && !quoted => {
+                    if in_token {
+                        tokens.push(std::mem::take(&mut current));
+                        in_token = false;
+                    }
+                },
+                c => {
+                    current.push(c);
+                    in_token = true;
+                },
+            }
+        }
+
+        if quoted {
+            return Err(SceneError::Parse { line, message: String::from("unterminated quoted value") });
+        }
+        if in_token {
+            tokens.push(current);
+        }
+
+        let mut tokens = tokens.into_iter();
+        let keyword = match tokens.next() {
+            Some(keyword) => keyword,
+            None => return Ok(None),
+        };
+
+        let mut names = vec![];
+        let mut params: Vec<(String, String)> = vec![];
+
+        for token in tokens {
+            match token.split_once('=') {
+                Some((key, value)) => {
+                    if params.iter().any(|(k, _)| k == key) {
+                        return Err(SceneError::Parse { line, message: format!("parameter '{}' is given more than once", key) });
+                    }
+                    params.push((key.to_string(), value.to_string()));
+                },
+                None if params.is_empty() => names.push(token),
+                None => return Err(SceneError::Parse { line, message: format!("expected key=value, found '{}'", token) }),
+            }
+        }
+        names.reverse();
+
+        Ok(Some(Directive { line, keyword, names, params }))
+    }
+
+    fn error(&self, message: &str) -> SceneError {
+        SceneError::Parse { line: self.line, message: message.to_string() }
+    }
+
+    // Next positional word after the keyword.
+    fn name(&mut self) -> Result<String, SceneError> {
+        match self.names.pop() {
+            Some(name) => Ok(name),
+            None => Err(self.error(&format!("{} is missing a name", self.keyword))),
+        }
+    }
+
+    fn take(&mut self, key: &str) -> Option<String> {
+        let index = self.params.iter().position(|(k, _)| k == key)?;
+        Some(self.params.remove(index).1)
+    }
+
+    fn string(&mut self, key: &str) -> Result<String, SceneError> {
+        match self.take(key) {
+            Some(value) => Ok(value),
+            None => Err(self.error(&format!("{} is missing '{}'", self.keyword, key))),
+        }
+    }
+
+    fn f64(&mut self, key: &str) -> Result<f64, SceneError> {
+        let value = self.string(key)?;
+        self.parse_f64(key, &value)
+    }
+
+    fn f64_or(&mut self, key: &str, default: f64) -> Result<f64, SceneError> {
+        match self.take(key) {
+            Some(value) => self.parse_f64(key, &value),
+            None => Ok(default),
+        }
+    }
+
+    fn positive(&mut self, key: &str) -> Result<f64, SceneError> {
+        let value = self.f64(key)?;
+        if value > 0.0 {
+            Ok(value)
+        } else {
+            Err(self.error(&format!("'{}' must be positive, found {}", key, value)))
+        }
+    }
+
+    fn positive_or(&mut self, key: &str, default: f64) -> Result<f64, SceneError> {
+        match self.take(key) {
+            Some(value) => match self.parse_f64(key, &value)? {
+                x if x > 0.0 => Ok(x),
+                x => Err(self.error(&format!("'{}' must be positive, found {}", key, x))),
+            },
+            None => Ok(default),
+        }
+    }
+
+    fn u32_or(&mut self, key: &str, default: u32) -> Result<u32, SceneError> {
+        match self.take(key) {
+            Some(value) => value.parse::<u32>()
+                .map_err(|_| self.error(&format!("'{}' expects a non-negative integer, found '{}'", key, value))),
+            None => Ok(default),
+        }
+    }
+
+    fn positive_u32_or(&mut self, key: &str, default: u32) -> Result<u32, SceneError> {
+        match self.u32_or(key, default)? {
+            0 => Err(self.error(&format!("'{}' must be positive, found 0", key))),
+            n => Ok(n),
+        }
+    }
+
+    fn vec3(&mut self, key: &str) -> Result<Vec3, SceneError> {
+        let value = self.string(key)?;
+        self.parse_vec3(key, &value)
+    }
+
+    fn vec3_or(&mut self, key: &str, default: Vec3) -> Result<Vec3, SceneError> {
+        match self.take(key) {
+            Some(value) => self.parse_vec3(key, &value),
+            None => Ok(default),
+        }
+    }
+
+    // Optional placement of an object: scaled by `scale`, a factor or one
+    // per axis, rotated by `rotate` degrees around X, Y and then Z, and moved
+    // by `translate`. The same keys suffixed with 1 give the pose at time 1
+    // of an object moving while the shutter is open, they default to the
+    // pose at time 0.
+    fn transform(&mut self) -> Result<Option<Placement>, SceneError> {
+        let start = self.pose_params("")?;
+        let end = self.pose_params("1")?;
+        if start.iter().chain(&end).all(Option::is_none) {
+            return Ok(None);
+        }
+
+        let [scale, rotate, translate] = start;
+        let start = Pose::new(scale.unwrap_or(Vec3::new(1.0, 1.0, 1.0)),
+                              Quaternion::from_euler(&rotate.unwrap_or(Vec3::new(0.0, 0.0, 0.0))),
+                              translate.unwrap_or(Vec3::new(0.0, 0.0, 0.0)));
+        let transform = start.transform().ok_or_else(|| self.error("'scale' must not be zero along any axis"))?;
+
+        if end.iter().all(Option::is_none) {
+            return Ok(Some(Placement::Fixed(Box::new(transform))));
+        }
+
+        let [scale, rotate, translate] = end;
+        let end = Pose::new(scale.unwrap_or(start.scale.clone()),
+                            rotate.map_or(start.rotation, |rotate| Quaternion::from_euler(&rotate)),
+                            translate.unwrap_or(start.translation.clone()));
+        if end.transform().is_none() {
+            return Err(self.error("'scale1' must not be zero along any axis"));
+        }
+        Ok(Some(Placement::Moving(Keyframes::linear(start, end))))
+    }
+
+    // The scale, rotate and translate parameters with the given suffix.
+    fn pose_params(&mut self, suffix: &str) -> Result<[Option<Vec3>; 3], SceneError> {
+        let key = format!("scale{}", suffix);
+        let scale = match self.take(&key) {
+            Some(value) if value.contains(',') => Some(self.parse_vec3(&key, &value)?),
+            Some(value) => {
+                let x = self.parse_f64(&key, &value)?;
+                Some(Vec3::new(x, x, x))
+            },
+            None => None,
+        };
+
+        let mut vec3 = |key: String| match self.take(&key) {
+            Some(value) => self.parse_vec3(&key, &value).map(Some),
+            None => Ok(None),
+        };
+        Ok([scale, vec3(format!("rotate{}", suffix))?, vec3(format!("translate{}", suffix))?])
+    }
+
+    // Path of a moving object: either a fixed `center`, a `center` moving in
+    // a straight line to `center1` at time 1, or a `path` of keyframes
+    // written as `time:x,y,z` separated by spaces.
+    fn path(&mut self) -> Result<Keyframes<Point3>, SceneError> {
+        let path = match self.take("path") {
+            Some(path) => path,
+            None => {
+                let center = self.vec3("center")?;
+                return Ok(match self.take("center1") {
+                    Some(value) => Keyframes::linear(center, self.parse_vec3("center1", &value)?),
+                    None => Keyframes::constant(center),
+                });
+            },
+        };
+        if self.take("center").is_some() || self.take("center1").is_some() {
+            return Err(self.error(&format!("{} can't have both a center and a path", self.keyword)));
+        }
+
+        let keys = path.split_whitespace().map(|key| {
+            let (time, point) = key.split_once(':')
+                .ok_or_else(|| self.error(&format!("'path' expects keyframes as time:x,y,z, found '{}'", key)))?;
+            Ok((self.parse_f64("path", time)?, self.parse_vec3("path", point)?))
+        }).collect::<Result<Vec<_>, SceneError>>()?;
+
+        Keyframes::new(keys).ok_or_else(|| self.error("'path' needs at least one keyframe"))
+    }
+
+    fn direction(&mut self, key: &str) -> Result<Vec3, SceneError> {
+        let v = self.vec3(key)?;
+        if v.near_zero() {
+            return Err(self.error(&format!("'{}' must not be zero", key)));
+        }
+        Ok(v)
+    }
+
+    fn color(&mut self, key: &str) -> Result<Color, SceneError> {
+        self.vec3(key)
+    }
+
+    fn parse_f64(&self, key: &str, value: &str) -> Result<f64, SceneError> {
+        match value.parse::<f64>() {
+            Ok(x) if x.is_finite() => Ok(x),
+            _ => Err(self.error(&format!("'{}' expects a number, found '{}'", key, value))),
+        }
+    }
+
+    fn parse_vec3(&self, key: &str, value: &str) -> Result<Vec3, SceneError> {
+        let components: Vec<&str> = value.split(',').collect();
+        if components.len() != 3 {
+            return Err(self.error(&format!("'{}' expects three comma separated numbers, found '{}'", key, value)));
+        }
+        Ok(Vec3::new(self.parse_f64(key, components[0])?,
+                     self.parse_f64(key, components[1])?,
+                     self.parse_f64(key, components[2])?))
+    }
+
+    // Fails on any parameter or name that was not consumed by the directive.
+    fn finish(&self) -> Result<(), SceneError> {
+        if let Some((key, _)) = self.params.first() {
+            return Err(self.error(&format!("unknown parameter '{}' for {}", key, self.keyword)));
+        }
+        if let Some(name) = self.names.last() {
+            return Err(self.error(&format!("unexpected '{}' for {}", name, self.keyword)));
+        }
+        Ok(())
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ray::Ray;
+    use crate::interval::Interval;
+    use crate::sampler::Sampler;
+
+    fn parse_error(source: &str) -> (usize, String) {
+        match Scene::parse(source) {
+            Err(SceneError::Parse { line, message }) => (line, message),
+            Err(e) => panic!("[Scene] Unexpected error: {}", e),
+            Ok(_) => panic!("[Scene] Expected an error for: {}", source),
+        }
+    }
+
+    #[test]
+    fn test_parse_scene() {
+        let source = "
+            # Two spheres
+            camera aspect_ratio=2.0 image_width=200 lookfrom=0,0,1 lookat=0,0,-1
+            material ground lambertian albedo=0.8,0.8,0.0
+            material glass dielectric refraction_index=1.5   # trailing comment
+            sphere center=0,-100.5,-1 radius=100 material=ground
+            sphere center=0,0,-1 radius=0.5 material=\"glass\"
+        ";
+
+        let scene = Scene::parse(source).unwrap();
+
+        assert_eq!(scene.world.len(), 2);
+        assert_eq!(scene.camera.image_width, 200);
+        assert_eq!(scene.camera.aspect_ratio, 2.0);
+        assert_eq!(scene.camera.lookfrom, Point3::new(0.0, 0.0, 1.0));
+        assert_eq!(scene.camera.samples_per_pixel, 10);
+        assert!(scene.lights.is_empty());
+    }
+
+    #[test]
+    fn test_parse_lights() {
+        let source = "
+            material lamp diffuse_light emit=4,4,4
+            material ground lambertian albedo=0.5,0.5,0.5
+            sphere center=0,-100,0 radius=100 material=ground
+            sphere center=0,5,0 radius=1 material=lamp
+            triangle v0=0,4,0 v1=1,4,0 v2=0,4,1 material=lamp
+        ";
+
+        let scene = Scene::parse(source).unwrap();
+
+        assert_eq!(scene.world.len(), 3);
+        assert_eq!(scene.lights.len(), 2);
+
+        // Looking up at the lamp sphere, next to the triangle.
+        let r = Ray::new(Point3::new(-0.2, 0.0, -0.2), Vec3::new(0.0, 1.0, 0.0));
+        let hr = scene.world.hit(&r, Interval::new(0.001, f64::INFINITY)).unwrap();
+        assert!(hr.t > 4.0 && hr.t < 4.1);
+        assert_eq!(hr.material.emitted(&hr), Color::new(4.0, 4.0, 4.0));
+        assert!(hr.material.scatter(&r, &hr, &mut Sampler::new(0)).is_none());
+    }
+
+    #[test]
+    fn test_parse_planar_primitives() {
+        let source = "
+            material lamp diffuse_light emit=4,4,4
+            material white lambertian albedo=0.7
+            plane point=0,0,0 normal=0,1,0 material=white
+            plane point=0,0,-10 normal=0,0,1 material=lamp
+            quad q=-1,5,-1 u=2,0,0 v=0,0,2 material=lamp
+            disk center=3,5,0 normal=0,-1,0 radius=0.5 material=lamp
+            box a=-1,0,-1 b=1,2,1 material=white
+        ";
+
+        let scene = Scene::parse(source).unwrap();
+
+        // The emissive plane is left out of the lights.
+        assert_eq!(scene.world.len(), 5);
+        assert_eq!(scene.lights.len(), 2);
+
+        // Straight down onto the box, then onto the floor next to it.
+        let down = Vec3::new(0.0, -1.0, 0.0);
+        let hr = scene.world.hit(&Ray::new(Point3::new(0.0, 4.0, 0.0), down.clone()), Interval::new(0.001, f64::INFINITY)).unwrap();
+        assert_eq!(hr.p, Point3::new(0.0, 2.0, 0.0));
+        let hr = scene.world.hit(&Ray::new(Point3::new(2.0, 4.0, 0.0), down), Interval::new(0.001, f64::INFINITY)).unwrap();
+        assert_eq!(hr.p, Point3::new(2.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_parse_transforms() {
+        let source = "
+            material white lambertian albedo=0.7
+            sphere center=0,0,0 radius=1 scale=1,1,2 translate=0,0,-5 material=white
+            box a=-1,-1,-1 b=1,1,1 rotate=0,45,0 translate=5,0,0 material=white
+            sphere center=0,0,0 radius=1 scale=0.5 translate=0,5,0 material=white
+        ";
+
+        let scene = Scene::parse(source).unwrap();
+        let hit = |direction: Vec3| {
+            scene.world.hit(&Ray::new(Point3::new(0.0, 0.0, 0.0), direction), Interval::new(0.001, f64::INFINITY)).unwrap()
+        };
+
+        assert!((hit(Vec3::new(0.0, 0.0, -1.0)).t - 3.0).abs() < 1e-9);
+        assert!((hit(Vec3::new(1.0, 0.0, 0.0)).t - (5.0 - 2.0_f64.sqrt())).abs() < 1e-9);
+        assert!((hit(Vec3::new(0.0, 1.0, 0.0)).t - 4.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_parse_motion() {
+        let source = "
+            camera shutter_close=1
+            material white lambertian albedo=0.7
+            sphere center=0,0,-5 center1=0,2,-5 radius=1 material=white
+            sphere path=\"0:5,0,0 0.5:5,0,2 1:5,0,-2\" radius=1 material=white
+            box a=-1,-1,-1 b=1,1,1 translate=0,-5,0 translate1=0,-5,4 material=white
+        ";
+
+        let scene = Scene::parse(source).unwrap();
+        assert_eq!((scene.camera.shutter_open, scene.camera.shutter_close), (0.0, 1.0));
+
+        let hit = |direction: Vec3, time: f64| {
+            scene.world.hit(&Ray::with_time(Point3::new(0.0, 0.0, 0.0), direction, time), Interval::new(0.001, f64::INFINITY))
+        };
+        let forward = Vec3::new(0.0, 0.0, -1.0);
+        assert_eq!(hit(forward.clone(), 0.0).unwrap().t, 4.0);
+        assert!(hit(forward, 1.0).is_none());
+
+        let right = Vec3::new(1.0, 0.0, 0.0);
+        assert_eq!(hit(right.clone(), 0.0).unwrap().t, 4.0);
+        assert!(hit(right.clone(), 0.5).is_none());
+        assert!((hit(right, 0.75).unwrap().t - 4.0).abs() < 1e-9);
+
+        let down = Vec3::new(0.0, -1.0, 0.0);
+        assert!((hit(down.clone(), 0.0).unwrap().t - 4.0).abs() < 1e-9);
+        assert!(hit(down, 1.0).is_none());
+    }
+
+    #[test]
+    fn test_object_and_material_ids() {
+        let source = "
+            material red lambertian albedo=1,0,0
+            material blue lambertian albedo=0,0,1
+            sphere center=0,0,-2 radius=0.5 material=blue
+            sphere center=0,0,-4 radius=0.5 material=red
+            sphere center=0,0,-6 radius=0.5 material=blue
+        ";
+
+        let scene = Scene::parse(source).unwrap();
+        let ray = |z: f64| Ray::new(Point3::new(0.0, 0.0, z), Vec3::new(0.0, 0.0, -1.0));
+        let ids = |z: f64| {
+            let hr = scene.world.hit(&ray(z), Interval::new(0.001, f64::INFINITY)).unwrap();
+            (hr.object_id, hr.material_id)
+        };
+
+        assert_eq!(ids(0.0), (1, 2));
+        assert_eq!(ids(-3.0), (2, 1));
+        assert_eq!(ids(-5.0), (3, 2));
+    }
+
+    #[test]
+    fn test_parse_textures() {
+        let source = "
+            texture white solid color=0.9,0.9,0.9
+            texture tiles checker scale=1 even=white odd=0.1
+            texture rough marble scale=4 seed=7
+            material floor lambertian albedo=tiles
+            material brushed metal albedo=0.8,0.8,0.8 fuzz=rough
+            texture uneven uv_checker squares=8 even=1.5 odd=1.45
+            material glass dielectric refraction_index=uneven
+            sphere center=0,0,-2 radius=0.5 material=floor
+            sphere center=0,0,-4 radius=0.5 material=brushed
+        ";
+
+        let scene = Scene::parse(source).unwrap();
+        let albedo = |x: f64, z: f64| {
+            let r = Ray::new(Point3::new(x, 0.25, z), Vec3::new(0.0, 0.0, -1.0));
+            let hr = scene.world.hit(&r, Interval::new(0.001, f64::INFINITY)).unwrap();
+            hr.material.albedo(&hr)
+        };
+
+        // The front of the first sphere straddles two squares.
+        assert_eq!(albedo(-0.25, 0.0), Color::new(0.1, 0.1, 0.1));
+        assert_eq!(albedo(0.25, 0.0), Color::new(0.9, 0.9, 0.9));
+        assert_eq!(albedo(0.0, -3.0), Color::new(0.8, 0.8, 0.8));
+    }
+
+    #[test]
+    fn test_parse_media() {
+        let source = "
+            material fog isotropic albedo=0.9
+            material smoke henyey_greenstein albedo=0.5 g=0.6
+            material white lambertian albedo=0.8
+            sphere center=0,0,-2 radius=1 density=0.5 material=fog
+            box a=-1,-1,-1 b=1,1,1 translate=0,0,-6 density=2 material=smoke
+            sphere center=0,0,-10 radius=1 material=white
+        ";
+
+        let scene = Scene::parse(source).unwrap();
+        assert_eq!(scene.world.len(), 1);
+        assert_eq!(scene.camera.media.len(), 2);
+
+        // Two units through the sphere and the translated box each.
+        let mut sampler = Sampler::new(0);
+        let transmittance = |x: f64, z: f64, sampler: &mut Sampler| {
+            let r = Ray::new(Point3::new(x, 0.0, z), Vec3::new(0.0, 0.0, -1.0));
+            scene.camera.media.iter().map(|medium| medium.transmittance(&r, Interval::new(0.001, f64::INFINITY), sampler)).product::<f64>()
+        };
+        assert!((transmittance(0.0, 0.0, &mut sampler) - (-5.0_f64).exp()).abs() < 1e-6);
+        assert_eq!(transmittance(1.5, 0.0, &mut sampler), 1.0);
+    }
+
+    #[test]
+    fn test_parse_grid_volumes() {
+        let path = std::env::temp_dir().join(format!("raytracer-scene-test-{}.vox", std::process::id()));
+        let mut bytes = vec![];
+        VoxelGrid::new(2, 1, 1, vec![0.0, 1.0]).unwrap().write(&mut bytes).unwrap();
+        fs::write(&path, bytes).unwrap();
+
+        let source = format!("
+            grid smoke file path=\"{}\"
+            grid puff cloud resolution=8 seed=3
+            material dust isotropic albedo=0.7
+            volume a=-1,-1,-1 b=1,1,1 density=smoke density_scale=0.5 material=dust
+            volume a=2,0,0 b=3,1,1 density=puff temperature=puff temperature_scale=1500 material=dust
+        ", path.display());
+        let scene = Scene::parse(&source);
+        fs::remove_file(&path).unwrap();
+        let scene = scene.unwrap();
+        assert_eq!(scene.camera.media.len(), 2);
+
+        // One unit of fog on the +x side of the first volume, none on the
+        // other.
+        let mut sampler = Sampler::new(0);
+        let ray_t = Interval::new(0.001, f64::INFINITY);
+        let r = Ray::new(Point3::new(-0.75, -5.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!(scene.camera.media[0].transmittance(&r, ray_t, &mut sampler), 1.0);
+        let r = Ray::new(Point3::new(0.75, -5.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        let n = 10000;
+        let transmittance: f64 = (0..n).map(|_| scene.camera.media[0].transmittance(&r, ray_t, &mut sampler)).sum();
+        assert!((transmittance / n as f64 - (-1.0_f64).exp()).abs() < 0.02);
+    }
+
+    #[test]
+    fn test_parse_camera_errors() {
+        let data = [
+            ("camera image_width=0", "'image_width' must be positive, found 0"),
+            ("camera image_width=-5", "'image_width' expects a non-negative integer, found '-5'"),
+            ("camera samples_per_pixel=0", "'samples_per_pixel' must be positive, found 0"),
+            ("camera aspect_ratio=0", "'aspect_ratio' must be positive, found 0"),
+            ("camera aspect_ratio=-1.5", "'aspect_ratio' must be positive, found -1.5"),
+            ("camera aspect_ratio=inf", "'aspect_ratio' expects a number, found 'inf'"),
+            ("camera focus_dist=0", "'focus_dist' must be positive, found 0"),
+            ("camera focus_dist=NaN", "'focus_dist' expects a number, found 'NaN'"),
+            ("camera vfov=0", "'vfov' must be between 0 and 180, found 0"),
+            ("camera vfov=180", "'vfov' must be between 0 and 180, found 180"),
+            ("camera vfov=-30", "'vfov' must be between 0 and 180, found -30"),
+        ];
+
+        for (source, message) in data {
+            assert_eq!(parse_error(source), (1, message.to_string()));
+        }
+
+        let scene = Scene::parse("camera image_width=1 samples_per_pixel=1 aspect_ratio=0.5 vfov=179.5 focus_dist=0.1").unwrap();
+        assert_eq!((scene.camera.image_width, scene.camera.samples_per_pixel), (1, 1));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let data: [(&str, usize, &str); 36] = [
+            ("sphere center=0,0,0 radius=1 material=missing", 1, "undefined material 'missing'"),
+            ("\n\nteapot", 3, "unknown directive 'teapot'"),
+            ("material a lambertian albedo=1,1", 1, "'albedo' expects three comma separated numbers, found '1,1'"),
+            ("material a lambertian\n", 1, "material is missing 'albedo'"),
+            ("material a plastic albedo=1,1,1", 1, "unknown material type 'plastic'"),
+            ("material a metal albedo=1,1,1\nmaterial a metal albedo=1,1,1", 2, "material 'a' is already defined"),
+            ("material a metal albedo=1,1,1 shiny=yes", 1, "unknown parameter 'shiny' for material"),
+            ("material a metal albedo=1,1,1\nsphere center=0,0,0 radius=-1 material=a", 2, "'radius' must be positive, found -1"),
+            ("material a lambertian albedo=wood", 1, "undefined texture 'wood'"),
+            ("texture a checker even=1 odd=0\ntexture a solid color=1,1,1", 2, "texture 'a' is already defined"),
+            ("texture a image path=a.png wrap=tile", 1, "unknown wrap mode 'tile'"),
+            ("texture a plaid", 1, "unknown texture type 'plaid'"),
+            ("material a metal albedo=1,1,1\nquad q=0,0,0 u=1,0,0 v=2,0,0 material=a", 2, "quad edges 'u' and 'v' must not be parallel"),
+            ("material a metal albedo=1,1,1\nplane point=0,0,0 normal=0,0,0 material=a", 2, "'normal' must not be zero"),
+            ("material a metal albedo=1,1,1\nsphere center=0,0,0 radius=1 scale=1,0,1 material=a", 2, "'scale' must not be zero along any axis"),
+            ("material a metal albedo=1,1,1\nbox a=0,0,0 b=1,1,1 rotate=45 material=a", 2, "'rotate' expects three comma separated numbers, found '45'"),
+            ("camera shutter_open=1 shutter_close=0.5", 1, "'shutter_close' must not be before 'shutter_open'"),
+            ("camera lookfrom=1,2,3 lookat=1,2,3", 1, "'lookfrom' and 'lookat' must differ"),
+            ("camera lookfrom=0,5,0 lookat=0,0,0", 1, "'vup' must not be zero or parallel to the view direction"),
+            ("camera lookfrom=0,0,0 lookat=0,0,-1 vup=0,0,2", 1, "'vup' must not be zero or parallel to the view direction"),
+            ("camera vup=0,0,0", 1, "'vup' must not be zero or parallel to the view direction"),
+            ("material a metal albedo=1,1,1\nsphere center=0,0,0 path=0:1,1,1 radius=1 material=a", 2, "sphere can't have both a center and a path"),
+            ("material a metal albedo=1,1,1\nsphere path=\"0:1,1,1 1,2,3\" radius=1 material=a", 2, "'path' expects keyframes as time:x,y,z, found '1,2,3'"),
+            ("texture a image path=a.png encoding=gamma", 1, "unknown encoding 'gamma'"),
+            ("material a metal albedo=1,1,1 normal_map=0.5,0.5,1 bump=1", 1, "material can't have both a normal_map and a bump"),
+            ("material a henyey_greenstein albedo=1 g=1", 1, "'g' must be between -1 and 1, found 1"),
+            ("material a isotropic albedo=1\nsphere center=0,0,0 radius=1 material=a", 2, "material 'a' is a phase function, only volumes with a 'density' can use it"),
+            ("material a metal albedo=1,1,1\nsphere center=0,0,0 radius=1 density=1 material=a", 2, "volume material 'a' must be isotropic or henyey_greenstein"),
+            ("material a isotropic albedo=1\nbox a=0,0,0 b=1,1,1 density=0 material=a", 2, "'density' must be positive, found 0"),
+            ("material a isotropic albedo=1\nquad q=0,0,0 u=1,0,0 v=0,1,0 density=1 material=a", 2, "material 'a' is a phase function, only volumes with a 'density' can use it"),
+            ("grid a noise\ngrid a cloud", 2, "grid 'a' is already defined"),
+            ("grid a voronoi", 1, "unknown grid type 'voronoi'"),
+            ("grid a noise resolution=0", 1, "'resolution' must be positive, found 0"),
+            ("material a isotropic albedo=1\nvolume a=0,0,0 b=1,1,1 density=fog material=a", 2, "undefined grid 'fog'"),
+            ("material a isotropic albedo=1\nvolume a=0,0,0 b=1,0,1 density=fog material=a", 2, "volume corners 'a' and 'b' must differ along every axis"),
+            ("grid g noise\nmaterial a isotropic albedo=1\nvolume a=0,0,0 b=1,1,1 density=g emission=g temperature=g material=a", 3, "volume can't have both an emission and a temperature"),
+        ];
+
+        for (source, line, message) in data {
+            assert_eq!(parse_error(source), (line, message.to_string()));
+        }
+    }
+
+}
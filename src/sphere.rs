@@ -3,30 +3,76 @@ use crate::vec3::{Vec3, Point3};
 use crate::ray::Ray;
 use crate::interval::Interval;
 use crate::material::Material;
+use crate::aabb::Aabb;
+use crate::onb::Onb;
+use crate::sampler::Sampler;
+use crate::animation::Keyframes;
 
 use std::sync::Arc;
 
 pub struct Sphere {
-    pub center: Point3,
+    // Path of the center over time, constant unless the sphere moves.
+    pub center: Keyframes<Point3>,
     pub radius: f64,
     pub material: Arc<dyn Material>,
 }
 
 impl Sphere {
     pub fn new(center: Point3, radius: f64, material: Arc<dyn Material>) -> Sphere {
+        Sphere::moving(Keyframes::constant(center), radius, material)
+    }
+
+    pub fn moving(center: Keyframes<Point3>, radius: f64, material: Arc<dyn Material>) -> Sphere {
         Sphere {
             center,
             radius,
             material,
         }
     }
+
+    // Uniform direction within the cone around +Z that a sphere of the given
+    // radius subtends at the given squared distance.
+    fn random_to_sphere(radius: f64, distance_squared: f64, sampler: &mut Sampler) -> Vec3 {
+        let r1 = Vec3::random_double(sampler);
+        let r2 = Vec3::random_double(sampler);
+        let z = 1.0 + r2 * ((1.0 - radius * radius / distance_squared).max(0.0).sqrt() - 1.0);
+
+        let phi = 2.0 * std::f64::consts::PI * r1;
+        let x = phi.cos() * (1.0 - z * z).sqrt();
+        let y = phi.sin() * (1.0 - z * z).sqrt();
+
+        Vec3::new(x, y, z)
+    }
+
+    // Maps a point on the unit sphere to (u, v) in [0, 1], u is the angle
+    // around the Y axis from X=-1 and v the angle from Y=-1 to Y=+1.
+    fn get_sphere_uv(p: &Point3) -> (f64, f64) {
+        let theta = (-p.y).acos();
+        let phi = (-p.z).atan2(p.x) + std::f64::consts::PI;
+
+        (phi / (2.0 * std::f64::consts::PI), theta / std::f64::consts::PI)
+    }
+
+    // Derivatives of the point at `offset` from the center with respect to
+    // the (u, v) of get_sphere_uv. Both vanish at the poles.
+    fn uv_derivatives(offset: &Vec3) -> (Vec3, Vec3) {
+        let pi = std::f64::consts::PI;
+        let rho = (offset.x * offset.x + offset.z * offset.z).sqrt();
+        if rho == 0.0 {
+            return (Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 0.0));
+        }
+
+        let dpdu = 2.0 * pi * Vec3::new(offset.z, 0.0, -offset.x);
+        let dpdv = pi * Vec3::new(-offset.x * offset.y / rho, rho, -offset.y * offset.z / rho);
+        (dpdu, dpdv)
+    }
 }
 
 impl Hittable for Sphere {
 
     fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord> {
-        
-        let oc = &self.center - &r.origin;
+        let center = self.center.at(r.time);
+        let oc = &center - &r.origin;
         let a = r.direction.length_squared();
         let h = Vec3::dot(&r.direction, &oc);
         let c = oc.length_squared() - self.radius*self.radius;
@@ -48,9 +94,133 @@ impl Hittable for Sphere {
         }
 
         let p = r.at(root);
-        let mut hr = HitRecord::new(p.clone(), (&p - &self.center) / self.radius, self.material.clone(), root, Face::Front);
-        let outward_normal = (p - &self.center) / self.radius; 
-        hr.set_face_normal(&r, &outward_normal);
+        let outward_normal = (&p - &center) / self.radius;
+        let (u, v) = Self::get_sphere_uv(&outward_normal);
+        let (dpdu, dpdv) = Self::uv_derivatives(&(&p - &center));
+        let mut hr = HitRecord::new(p, outward_normal.clone(), self.material.clone(), root, u, v, Face::Front);
+        hr.set_face_normal(r, &outward_normal);
+        hr.set_uv_derivatives(dpdu, dpdv);
         Some(hr)
     }
+
+    // The center moves in straight lines between keyframes, the boxes around
+    // them cover the whole motion.
+    fn bounding_box(&self) -> Aabb {
+        let rvec = Vec3::new(self.radius, self.radius, self.radius);
+        self.center.values().fold(Aabb::empty(), |bbox, center| {
+            Aabb::surrounding(&bbox, &Aabb::from_points(&(center - &rvec), &(center + &rvec)))
+        })
+    }
+
+    // Samples the cone of directions the sphere subtends as seen from the
+    // origin, which must lie outside of it.
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3, time: f64) -> f64 {
+        if self.hit(&Ray::with_time(origin.clone(), direction.clone(), time), Interval::new(0.001, f64::INFINITY)).is_none() {
+            return 0.0;
+        }
+
+        let distance_squared = (&self.center.at(time) - origin).length_squared();
+        if distance_squared <= self.radius * self.radius {
+            return 0.0;
+        }
+        let cos_theta_max = (1.0 - self.radius * self.radius / distance_squared).sqrt();
+        let solid_angle = 2.0 * std::f64::consts::PI * (1.0 - cos_theta_max);
+
+        1.0 / solid_angle
+    }
+
+    fn random(&self, origin: &Point3, time: f64, sampler: &mut Sampler) -> Vec3 {
+        let direction = &self.center.at(time) - origin;
+        let distance_squared = direction.length_squared();
+        let uvw = Onb::new(&direction);
+
+        uvw.transform(&Self::random_to_sphere(self.radius, distance_squared, sampler))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::color::Color;
+
+    #[test]
+    fn test_light_sampling() {
+        let sphere = Sphere::new(Point3::new(0.0, 0.0, -4.0), 2.0, Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))));
+        let origin = Point3::new(0.0, 0.0, 0.0);
+
+        // The sphere subtends a cone with a half angle of 30 degrees.
+        let expected = 1.0 / (2.0 * std::f64::consts::PI * (1.0 - 3.0_f64.sqrt() / 2.0));
+
+        let mut sampler = Sampler::new(0);
+        for _ in 0..100 {
+            let direction = sphere.random(&origin, 0.0, &mut sampler);
+            assert!((sphere.pdf_value(&origin, &direction, 0.0) - expected).abs() < 1e-9);
+        }
+
+        assert_eq!(sphere.pdf_value(&origin, &Vec3::new(0.0, 0.0, 1.0), 0.0), 0.0);
+        assert_eq!(sphere.pdf_value(&Point3::new(0.0, 0.0, -4.0), &Vec3::new(0.0, 0.0, 1.0), 0.0), 0.0);
+    }
+
+    #[test]
+    fn test_moving_sphere() {
+        let path = Keyframes::linear(Point3::new(0.0, 0.0, -5.0), Point3::new(2.0, 0.0, -5.0));
+        let sphere = Sphere::moving(path, 1.0, Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))));
+        let ray_t = Interval::new(0.001, f64::INFINITY);
+        let origin = Point3::new(0.0, 0.0, 0.0);
+        let at = |time| Ray::with_time(origin.clone(), Vec3::new(0.0, 0.0, -1.0), time);
+
+        let bbox = sphere.bounding_box();
+        assert_eq!((bbox.x.min, bbox.x.max), (-1.0, 3.0));
+
+        assert_eq!(sphere.hit(&at(0.0), ray_t).unwrap().t, 4.0);
+        let hr = sphere.hit(&at(0.25), ray_t).unwrap();
+        assert!((hr.t - (5.0 - 0.75_f64.sqrt())).abs() < 1e-12);
+        assert!((&hr.normal - Vec3::new(-0.5, 0.0, 0.75_f64.sqrt())).length() < 1e-12);
+        assert!(sphere.hit(&at(1.0), ray_t).is_none());
+
+        // Lights are sampled where the sphere is at the given time.
+        let mut sampler = Sampler::new(0);
+        for _ in 0..100 {
+            let direction = sphere.random(&origin, 1.0, &mut sampler);
+            assert!(sphere.hit(&Ray::with_time(origin.clone(), direction.clone(), 1.0), ray_t).is_some());
+            assert!(sphere.pdf_value(&origin, &direction, 1.0) > 0.0);
+        }
+    }
+
+    #[test]
+    fn test_uv_frame() {
+        let pi = std::f64::consts::PI;
+        let center = Point3::new(1.0, 2.0, 3.0);
+        let sphere = Sphere::new(center.clone(), 2.0, Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))));
+
+        // Inverse of get_sphere_uv, scaled to the sphere.
+        let point = |u: f64, v: f64| {
+            let (phi, theta) = (2.0 * pi * u, pi * v);
+            &center + 2.0 * Vec3::new(-phi.cos() * theta.sin(), -theta.cos(), phi.sin() * theta.sin())
+        };
+
+        let mut sampler = Sampler::new(1);
+        for _ in 0..100 {
+            let (u, v) = (sampler.random_double(), 0.05 + 0.9 * sampler.random_double());
+            let p = point(u, v);
+
+            // Shoot at the point from outside along the normal.
+            let normal = (&p - &center) / 2.0;
+            let r = Ray::new(&p + 3.0 * &normal, -&normal);
+            let hr = sphere.hit(&r, Interval::new(0.001, f64::INFINITY)).unwrap();
+            assert!((hr.u - u).abs() < 1e-9 && (hr.v - v).abs() < 1e-9);
+
+            let h = 1e-6;
+            let dpdu = (point(u + h, v) - point(u - h, v)) / (2.0 * h);
+            let dpdv = (point(u, v + h) - point(u, v - h)) / (2.0 * h);
+            assert!((&hr.dpdu - &dpdu).length() < 1e-6 * dpdu.length());
+            assert!((&hr.dpdv - &dpdv).length() < 1e-6 * dpdv.length());
+
+            assert!((&hr.tangent - dpdu.unit_vector()).length() < 1e-6);
+            assert!((&hr.bitangent - dpdv.unit_vector()).length() < 1e-6);
+            assert!(Vec3::dot(&hr.tangent, &hr.normal).abs() < 1e-9);
+        }
+    }
+
 }
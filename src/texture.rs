@@ -0,0 +1,333 @@
+// Textures give material parameters a value at every point of a surface,
+// from its (u, v) coordinates or its position in space.
+
+use crate::vec3::Point3;
+use crate::color::Color;
+use crate::perlin::Perlin;
+use crate::sampler::Sampler;
+
+use image::{DynamicImage, ImageResult, Rgb32FImage};
+
+use std::path::Path;
+use std::sync::Arc;
+
+pub trait Texture: Send + Sync {
+    fn value(&self, u: f64, v: f64, p: &Point3) -> Color;
+
+    // Value of scalar parameters such as the fuzz of a metal, the luminance
+    // so that grey textures read as their grey level.
+    fn scalar(&self, u: f64, v: f64, p: &Point3) -> f64 {
+        self.value(u, v, p).luminance()
+    }
+}
+
+pub struct SolidColor {
+    albedo: Color,
+}
+
+impl SolidColor {
+
+    pub fn new(albedo: Color) -> SolidColor {
+        SolidColor {
+            albedo,
+        }
+    }
+
+}
+
+impl Texture for SolidColor {
+
+    fn value(&self, _u: f64, _v: f64, _p: &Point3) -> Color {
+        self.albedo.clone()
+    }
+
+}
+
+// Checkerboard of unit cubes `scale` wide in space, so that it wraps around
+// any object without stretching.
+pub struct Checker {
+    inv_scale: f64,
+    even: Arc<dyn Texture>,
+    odd: Arc<dyn Texture>,
+}
+
+impl Checker {
+
+    pub fn new(scale: f64, even: Arc<dyn Texture>, odd: Arc<dyn Texture>) -> Checker {
+        Checker {
+            inv_scale: 1.0 / scale,
+            even,
+            odd,
+        }
+    }
+
+}
+
+impl Texture for Checker {
+
+    fn value(&self, u: f64, v: f64, p: &Point3) -> Color {
+        let x = (self.inv_scale * p.x).floor() as i64;
+        let y = (self.inv_scale * p.y).floor() as i64;
+        let z = (self.inv_scale * p.z).floor() as i64;
+
+        if (x + y + z).rem_euclid(2) == 0 {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+
+}
+
+// Checkerboard in texture space, with `squares` squares along u and v.
+pub struct UvChecker {
+    squares: f64,
+    even: Arc<dyn Texture>,
+    odd: Arc<dyn Texture>,
+}
+
+impl UvChecker {
+
+    pub fn new(squares: f64, even: Arc<dyn Texture>, odd: Arc<dyn Texture>) -> UvChecker {
+        UvChecker {
+            squares,
+            even,
+            odd,
+        }
+    }
+
+}
+
+impl Texture for UvChecker {
+
+    fn value(&self, u: f64, v: f64, p: &Point3) -> Color {
+        let i = (self.squares * u).floor() as i64;
+        let j = (self.squares * v).floor() as i64;
+
+        if (i + j).rem_euclid(2) == 0 {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+
+}
+
+// How an image texture is extended outside [0, 1].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Wrap {
+    Repeat,
+    Clamp,
+    Mirror,
+}
+
+impl Wrap {
+
+    fn texel(&self, x: i64, size: u32) -> u32 {
+        let size = size as i64;
+        let x = match self {
+            Wrap::Repeat => x.rem_euclid(size),
+            Wrap::Clamp => x.clamp(0, size - 1),
+            Wrap::Mirror => {
+                let x = x.rem_euclid(2 * size);
+                if x < size { x } else { 2 * size - 1 - x }
+            },
+        };
+        x as u32
+    }
+
+}
+
+// Image mapped onto [0, 1] in u and v, with v = 0 at the bottom row, and
+// filtered bilinearly.
+pub struct ImageTexture {
+    image: Rgb32FImage,
+    wrap: Wrap,
+}
+
+impl ImageTexture {
+
+    // `image` holds linear values.
+    pub fn new(image: Rgb32FImage, wrap: Wrap) -> ImageTexture {
+        ImageTexture {
+            image,
+            wrap,
+        }
+    }
+
+    // 8 and 16-bit images are taken as sRGB encoded and converted to linear
+    // values when `srgb` is set, floating point ones such as EXR files are
+    // always linear. Data such as normal maps must not be decoded.
+    pub fn load(path: &Path, wrap: Wrap, srgb: bool) -> ImageResult<ImageTexture> {
+        let image = image::open(path)?;
+        let linear = matches!(image, DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_));
+
+        let mut image = image.to_rgb32f();
+        if srgb && !linear {
+            for p in image.pixels_mut() {
+                for c in p.0.iter_mut() {
+                    *c = Color::srgb_to_linear(*c as f64) as f32;
+                }
+            }
+        }
+        Ok(ImageTexture::new(image, wrap))
+    }
+
+    fn texel(&self, x: i64, y: i64) -> Color {
+        let x = self.wrap.texel(x, self.image.width());
+        let y = self.wrap.texel(y, self.image.height());
+
+        let p = self.image.get_pixel(x, y);
+        Color::new(p[0] as f64, p[1] as f64, p[2] as f64)
+    }
+
+}
+
+impl Texture for ImageTexture {
+
+    fn value(&self, u: f64, v: f64, _p: &Point3) -> Color {
+        if self.image.width() == 0 || self.image.height() == 0 {
+            return Color::new(0.0, 1.0, 1.0);
+        }
+
+        let x = u * self.image.width() as f64 - 0.5;
+        let y = (1.0 - v) * self.image.height() as f64 - 0.5;
+
+        let x0 = x.floor();
+        let y0 = y.floor();
+        let tx = x - x0;
+        let ty = y - y0;
+        let (x0, y0) = (x0 as i64, y0 as i64);
+
+        let top = (1.0 - tx) * self.texel(x0, y0) + tx * self.texel(x0 + 1, y0);
+        let bottom = (1.0 - tx) * self.texel(x0, y0 + 1) + tx * self.texel(x0 + 1, y0 + 1);
+
+        (1.0 - ty) * top + ty * bottom
+    }
+
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NoisePattern {
+    // Plain Perlin noise, soft blobs.
+    Noise,
+    // Several octaves of noise, like smoke or clouds.
+    Turbulence,
+    // Stripes along z perturbed by turbulence.
+    Marble,
+}
+
+// Procedural texture of `color` modulated by Perlin noise, with features
+// about 1 / `scale` wide.
+pub struct NoiseTexture {
+    perlin: Perlin,
+    pattern: NoisePattern,
+    scale: f64,
+    color: Color,
+}
+
+impl NoiseTexture {
+
+    pub fn new(pattern: NoisePattern, scale: f64, color: Color, seed: u64) -> NoiseTexture {
+        NoiseTexture {
+            perlin: Perlin::new(&mut Sampler::new(seed)),
+            pattern,
+            scale,
+            color,
+        }
+    }
+
+}
+
+impl Texture for NoiseTexture {
+
+    fn value(&self, _u: f64, _v: f64, p: &Point3) -> Color {
+        let p = self.scale * p;
+        let t = match self.pattern {
+            NoisePattern::Noise => 0.5 * (1.0 + self.perlin.noise(&p)),
+            NoisePattern::Turbulence => self.perlin.turbulence(&p, 7),
+            NoisePattern::Marble => 0.5 * (1.0 + (p.z + 10.0 * self.perlin.turbulence(&p, 7)).sin()),
+        };
+        t.clamp(0.0, 1.0) * &self.color
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vec3::Vec3;
+
+    fn solid(x: f64) -> Arc<dyn Texture> {
+        Arc::new(SolidColor::new(Color::new(x, x, x)))
+    }
+
+    #[test]
+    fn test_checkers() {
+        let checker = Checker::new(0.5, solid(1.0), solid(0.0));
+        let at = |x, y, z| checker.scalar(0.0, 0.0, &Point3::new(x, y, z));
+
+        assert_eq!(at(0.25, 0.25, 0.25), 1.0);
+        assert_eq!(at(0.75, 0.25, 0.25), 0.0);
+        assert_eq!(at(0.75, 0.75, 0.25), 1.0);
+        assert_eq!(at(-0.25, 0.25, 0.25), 0.0);
+
+        let checker = UvChecker::new(4.0, solid(1.0), solid(0.0));
+        let at = |u, v| checker.scalar(u, v, &Point3::new(0.0, 0.0, 0.0));
+
+        assert_eq!(at(0.1, 0.1), 1.0);
+        assert_eq!(at(0.3, 0.1), 0.0);
+        assert_eq!(at(0.3, 0.3), 1.0);
+        assert_eq!(at(0.9, 0.1), 0.0);
+    }
+
+    #[test]
+    fn test_image_texture() {
+        // Black and white columns over a bottom row twice as bright.
+        let image = Rgb32FImage::from_fn(2, 2, |x, y| {
+            let c = x as f32 * (y + 1) as f32;
+            image::Rgb([c, c, c])
+        });
+        let origin = Point3::new(0.0, 0.0, 0.0);
+        let at = |texture: &ImageTexture, u, v| texture.value(u, v, &origin).x;
+
+        let repeat = ImageTexture::new(image.clone(), Wrap::Repeat);
+        // Texel centers, v = 0 being the bottom row.
+        assert_eq!(at(&repeat, 0.75, 0.75), 1.0);
+        assert_eq!(at(&repeat, 0.75, 0.25), 2.0);
+        // Halfway between the columns, and across the seam.
+        assert!((at(&repeat, 0.5, 0.75) - 0.5).abs() < 1e-9);
+        assert!((at(&repeat, 1.0, 0.75) - 0.5).abs() < 1e-9);
+        assert!((at(&repeat, 1.25, 0.25) - 0.0).abs() < 1e-9);
+
+        let clamp = ImageTexture::new(image.clone(), Wrap::Clamp);
+        assert_eq!(at(&clamp, 1.0, 0.75), 1.0);
+        assert_eq!(at(&clamp, 3.0, -2.0), 2.0);
+
+        let mirror = ImageTexture::new(image, Wrap::Mirror);
+        assert_eq!(at(&mirror, 1.25, 0.75), 1.0);
+        assert_eq!(at(&mirror, 1.75, 0.75), 0.0);
+        assert_eq!(at(&mirror, -0.25, 0.75), 0.0);
+    }
+
+    #[test]
+    fn test_noise_textures() {
+        let mut sampler = Sampler::new(3);
+        let color = Color::new(1.0, 0.5, 0.25);
+
+        for pattern in [NoisePattern::Noise, NoisePattern::Turbulence, NoisePattern::Marble] {
+            let texture = NoiseTexture::new(pattern, 4.0, color.clone(), 1);
+            let same = NoiseTexture::new(pattern, 4.0, color.clone(), 1);
+
+            for _ in 0..100 {
+                let p = 10.0 * Vec3::random(&mut sampler);
+                let c = texture.value(0.0, 0.0, &p);
+                let t = c.x;
+                assert!((0.0..=1.0).contains(&t));
+                assert!((&c - t * &color).length() < 1e-12);
+                assert_eq!(c, same.value(0.0, 0.0, &p));
+            }
+        }
+    }
+
+}
@@ -0,0 +1,169 @@
+// Tone mapping from the linear radiance of the framebuffer to linear display
+// values in [0, 1], which Color::write_color then encodes as sRGB.
+
+use crate::color::Color;
+
+use clap::ValueEnum;
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum ToneMapOperator {
+    // Everything above 1 clips.
+    Clamp,
+    Reinhard,
+    // Reinhard reaching 1 at the white point instead of at infinity.
+    ExtendedReinhard,
+    // Krzysztof Narkowicz's fit of the ACES filmic curve.
+    Aces,
+    // Troy Sobotka's AgX, after Benjamin Wrensch's minimal version.
+    Agx,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToneMap {
+    pub operator: ToneMapOperator,
+    // Exposure adjustment in stops, each one doubles the radiance.
+    pub exposure: f64,
+    // Radiance mapped to 1 by the extended Reinhard operator.
+    pub white_point: f64,
+}
+
+impl Default for ToneMap {
+
+    fn default() -> ToneMap {
+        ToneMap::new(ToneMapOperator::Clamp, 0.0, 4.0)
+    }
+
+}
+
+impl ToneMap {
+
+    pub fn new(operator: ToneMapOperator, exposure: f64, white_point: f64) -> ToneMap {
+        ToneMap {
+            operator,
+            exposure,
+            white_point,
+        }
+    }
+
+    pub fn apply(&self, c: &Color) -> Color {
+        let c = 2.0_f64.powf(self.exposure) * c;
+
+        match self.operator {
+            ToneMapOperator::Clamp => Self::per_channel(&c, |x| x),
+            ToneMapOperator::Reinhard => Self::per_channel(&c, |x| x / (1.0 + x)),
+            ToneMapOperator::ExtendedReinhard => {
+                let w2 = self.white_point * self.white_point;
+                Self::per_channel(&c, |x| x * (1.0 + x / w2) / (1.0 + x))
+            },
+            ToneMapOperator::Aces => Self::per_channel(&c, |x| {
+                (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14)
+            }),
+            ToneMapOperator::Agx => Self::agx(&c),
+        }
+    }
+
+    // Applies `f` to the non negative part of every channel and clamps the
+    // result to the displayable range.
+    fn per_channel(c: &Color, f: impl Fn(f64) -> f64) -> Color {
+        let g = |x: f64| f(x.max(0.0)).clamp(0.0, 1.0);
+        Color::new(g(c.x), g(c.y), g(c.z))
+    }
+
+    fn agx(c: &Color) -> Color {
+        const INSET: [[f64; 3]; 3] = [
+            [0.842479062253094, 0.0784335999999992, 0.0792237451477643],
+            [0.0423282422610123, 0.878468636469772, 0.0791661274605434],
+            [0.0423756549057051, 0.0784336, 0.879142973793104],
+        ];
+        const OUTSET: [[f64; 3]; 3] = [
+            [1.19687900512017, -0.0980208811401368, -0.0990297440797205],
+            [-0.0528968517574562, 1.15190312990417, -0.0989611768448433],
+            [-0.0529716355144438, -0.0980434501171241, 1.15107367264116],
+        ];
+        const MIN_EV: f64 = -12.47393;
+        const MAX_EV: f64 = 4.026069;
+
+        // Log encoding of the inset color, followed by the sigmoid contrast
+        // curve approximated by a polynomial.
+        let encode = |x: f64| {
+            let x = (x.max(1e-10).log2().clamp(MIN_EV, MAX_EV) - MIN_EV) / (MAX_EV - MIN_EV);
+            let x2 = x * x;
+            let x4 = x2 * x2;
+            15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x - 0.00232
+        };
+
+        let inset = Self::transform(&INSET, c);
+        let curve = Color::new(encode(inset.x), encode(inset.y), encode(inset.z));
+        let outset = Self::transform(&OUTSET, &curve);
+
+        // The curve produces display encoded values, decode them back to
+        // linear with the 2.2 reference display EOTF.
+        Self::per_channel(&outset, |x| x.powf(2.2))
+    }
+
+    fn transform(m: &[[f64; 3]; 3], c: &Color) -> Color {
+        Color::new(m[0][0] * c.x + m[0][1] * c.y + m[0][2] * c.z,
+                   m[1][0] * c.x + m[1][1] * c.y + m[1][2] * c.z,
+                   m[2][0] * c.x + m[2][1] * c.y + m[2][2] * c.z)
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_gray(operator: ToneMapOperator, exposure: f64, x: f64, e: f64) {
+        let c = ToneMap::new(operator, exposure, 4.0).apply(&Color::new(x, x, x));
+        for y in [c.x, c.y, c.z] {
+            assert!((y - e).abs() < 1e-4, "{:?}({}) = {} expected {}", operator, x, y, e);
+        }
+    }
+
+    #[test]
+    fn test_operators() {
+        let data: [(ToneMapOperator, f64, f64); 12] = [
+            (ToneMapOperator::Clamp, 0.5, 0.5),
+            (ToneMapOperator::Clamp, 3.0, 1.0),
+            (ToneMapOperator::Clamp, -1.0, 0.0),
+            (ToneMapOperator::Reinhard, 1.0, 0.5),
+            (ToneMapOperator::Reinhard, 3.0, 0.75),
+            (ToneMapOperator::ExtendedReinhard, 1.0, 0.53125),
+            (ToneMapOperator::ExtendedReinhard, 4.0, 1.0),
+            (ToneMapOperator::ExtendedReinhard, 8.0, 1.0),
+            (ToneMapOperator::Aces, 0.18, 0.26689892),
+            (ToneMapOperator::Aces, 1.0, 0.80379747),
+            (ToneMapOperator::Aces, 100.0, 1.0),
+            (ToneMapOperator::Agx, 0.0, 0.0),
+        ];
+        for (operator, x, e) in data {
+            assert_gray(operator, 0.0, x, e);
+        }
+    }
+
+    #[test]
+    fn test_agx() {
+        // Pure red keeps its hue but bleeds into the other channels, and
+        // grays pick up the slight tint of the AgX matrices.
+        let data: [(Color, Color); 3] = [
+            (Color::new(1.0, 0.0, 0.0), Color::new(0.719417, 0.039488, 0.039550)),
+            (Color::new(0.18, 0.18, 0.18), Color::new(0.214467, 0.214533, 0.214537)),
+            (Color::new(100.0, 100.0, 100.0), Color::new(0.996502, 0.996978, 0.997007)),
+        ];
+        let tone_map = ToneMap::new(ToneMapOperator::Agx, 0.0, 4.0);
+
+        for (x, e) in data {
+            let c = tone_map.apply(&x);
+            assert!((&c - &e).length() < 1e-5, "Agx({:?}) = {:?} expected {:?}", x, c, e);
+        }
+    }
+
+    #[test]
+    fn test_exposure() {
+        // Each stop doubles the radiance before the operator.
+        assert_gray(ToneMapOperator::Clamp, 1.0, 0.25, 0.5);
+        assert_gray(ToneMapOperator::Clamp, -2.0, 2.0, 0.5);
+        assert_gray(ToneMapOperator::Reinhard, 2.0, 0.25, 0.5);
+    }
+
+}
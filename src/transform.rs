@@ -0,0 +1,706 @@
+// Affine transforms as 4x4 matrices, and the Transformed and Animated
+// wrappers that place an object in the world through them. Points and vectors are columns, so
+// `a * b` applies b first.
+
+use crate::hittable::{Hittable, HitRecord};
+use crate::vec3::{Vec3, Point3};
+use crate::ray::Ray;
+use crate::interval::Interval;
+use crate::aabb::Aabb;
+use crate::sampler::Sampler;
+use crate::animation::{Interpolate, Keyframes};
+
+use std::ops::Mul;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Matrix4 {
+    // Row major, m[row][column].
+    pub m: [[f64; 4]; 4],
+}
+
+impl Matrix4 {
+
+    pub fn new(m: [[f64; 4]; 4]) -> Matrix4 {
+        Matrix4 { m }
+    }
+
+    pub fn identity() -> Matrix4 {
+        Matrix4::scaling(&Vec3::new(1.0, 1.0, 1.0))
+    }
+
+    pub fn translation(offset: &Vec3) -> Matrix4 {
+        Matrix4::new([
+            [1.0, 0.0, 0.0, offset.x],
+            [0.0, 1.0, 0.0, offset.y],
+            [0.0, 0.0, 1.0, offset.z],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn scaling(factors: &Vec3) -> Matrix4 {
+        Matrix4::new([
+            [factors.x, 0.0, 0.0, 0.0],
+            [0.0, factors.y, 0.0, 0.0],
+            [0.0, 0.0, factors.z, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn transpose(&self) -> Matrix4 {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, x) in row.iter_mut().enumerate() {
+                *x = self.m[j][i];
+            }
+        }
+        Matrix4::new(m)
+    }
+
+    // Gauss-Jordan elimination with partial pivoting, None when the matrix
+    // is singular.
+    pub fn inverse(&self) -> Option<Matrix4> {
+        let mut a = self.m;
+        let mut inv = Matrix4::identity().m;
+
+        for column in 0..4 {
+            let pivot = (column..4).max_by(|&i, &j| a[i][column].abs().total_cmp(&a[j][column].abs()))?;
+            if a[pivot][column].abs() < 1e-12 {
+                return None;
+            }
+            a.swap(column, pivot);
+            inv.swap(column, pivot);
+
+            let scale = 1.0 / a[column][column];
+            for j in 0..4 {
+                a[column][j] *= scale;
+                inv[column][j] *= scale;
+            }
+
+            for i in 0..4 {
+                if i != column {
+                    let factor = a[i][column];
+                    for j in 0..4 {
+                        a[i][j] -= factor * a[column][j];
+                        inv[i][j] -= factor * inv[column][j];
+                    }
+                }
+            }
+        }
+        Some(Matrix4::new(inv))
+    }
+
+    // Determinant of the upper left 3x3 block, the linear part of an affine
+    // transform.
+    pub fn determinant3(&self) -> f64 {
+        let m = &self.m;
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+
+    pub fn transform_point(&self, p: &Point3) -> Point3 {
+        let m = &self.m;
+        Point3::new(m[0][0] * p.x + m[0][1] * p.y + m[0][2] * p.z + m[0][3],
+                    m[1][0] * p.x + m[1][1] * p.y + m[1][2] * p.z + m[1][3],
+                    m[2][0] * p.x + m[2][1] * p.y + m[2][2] * p.z + m[2][3])
+    }
+
+    // Vectors are not affected by the translation.
+    pub fn transform_vector(&self, v: &Vec3) -> Vec3 {
+        let m = &self.m;
+        Vec3::new(m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z,
+                  m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z,
+                  m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z)
+    }
+
+}
+
+impl Mul for Matrix4 {
+    type Output = Matrix4;
+
+    fn mul(self, other: Matrix4) -> Matrix4 {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, x) in row.iter_mut().enumerate() {
+                *x = (0..4).map(|k| self.m[i][k] * other.m[k][j]).sum();
+            }
+        }
+        Matrix4::new(m)
+    }
+}
+
+// Unit quaternions represent rotations without the gimbal lock of Euler
+// angles.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quaternion {
+    pub w: f64,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl Quaternion {
+
+    pub fn new(w: f64, x: f64, y: f64, z: f64) -> Quaternion {
+        Quaternion { w, x, y, z }
+    }
+
+    pub fn identity() -> Quaternion {
+        Quaternion::new(1.0, 0.0, 0.0, 0.0)
+    }
+
+    // Rotation by `degrees` counterclockwise around `axis`, looking down the
+    // axis towards the origin.
+    pub fn from_axis_angle(axis: &Vec3, degrees: f64) -> Quaternion {
+        let half = 0.5 * degrees.to_radians();
+        let axis = axis.unit_vector();
+        let s = half.sin();
+        Quaternion::new(half.cos(), s * axis.x, s * axis.y, s * axis.z)
+    }
+
+    // Rotation around X, then Y, then Z, by the given angles in degrees.
+    pub fn from_euler(degrees: &Vec3) -> Quaternion {
+        Quaternion::from_axis_angle(&Vec3::new(0.0, 0.0, 1.0), degrees.z)
+            * Quaternion::from_axis_angle(&Vec3::new(0.0, 1.0, 0.0), degrees.y)
+            * Quaternion::from_axis_angle(&Vec3::new(1.0, 0.0, 0.0), degrees.x)
+    }
+
+    pub fn dot(a: &Quaternion, b: &Quaternion) -> f64 {
+        a.w * b.w + a.x * b.x + a.y * b.y + a.z * b.z
+    }
+
+    pub fn normalized(&self) -> Quaternion {
+        let length = (self.w * self.w + self.x * self.x + self.y * self.y + self.z * self.z).sqrt();
+        Quaternion::new(self.w / length, self.x / length, self.y / length, self.z / length)
+    }
+
+    // Spherical linear interpolation, turning at a constant rate along the
+    // shortest arc from `a` to `b`.
+    pub fn slerp(a: &Quaternion, b: &Quaternion, t: f64) -> Quaternion {
+        let (a, mut b) = (a.normalized(), b.normalized());
+        let mut cos = Quaternion::dot(&a, &b);
+        if cos < 0.0 {
+            b = Quaternion::new(-b.w, -b.x, -b.y, -b.z);
+            cos = -cos;
+        }
+
+        // Nearly the same rotation, where the formula below is unstable.
+        let (wa, wb) = if cos > 0.9995 {
+            (1.0 - t, t)
+        } else {
+            let theta = cos.acos();
+            (((1.0 - t) * theta).sin() / theta.sin(), (t * theta).sin() / theta.sin())
+        };
+        Quaternion::new(wa * a.w + wb * b.w, wa * a.x + wb * b.x, wa * a.y + wb * b.y, wa * a.z + wb * b.z).normalized()
+    }
+
+    pub fn to_matrix(&self) -> Matrix4 {
+        let Quaternion { w, x, y, z } = self.normalized();
+        Matrix4::new([
+            [1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y - w * z), 2.0 * (x * z + w * y), 0.0],
+            [2.0 * (x * y + w * z), 1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z - w * x), 0.0],
+            [2.0 * (x * z - w * y), 2.0 * (y * z + w * x), 1.0 - 2.0 * (x * x + y * y), 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+}
+
+// Hamilton product, `a * b` rotates by b first.
+impl Mul for Quaternion {
+    type Output = Quaternion;
+
+    fn mul(self, o: Quaternion) -> Quaternion {
+        Quaternion::new(self.w * o.w - self.x * o.x - self.y * o.y - self.z * o.z,
+                        self.w * o.x + self.x * o.w + self.y * o.z - self.z * o.y,
+                        self.w * o.y - self.x * o.z + self.y * o.w + self.z * o.x,
+                        self.w * o.z + self.x * o.y - self.y * o.x + self.z * o.w)
+    }
+}
+
+// An invertible affine transform along with its inverse.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    pub matrix: Matrix4,
+    pub inverse: Matrix4,
+}
+
+impl Transform {
+
+    // None when the matrix can't be inverted.
+    pub fn new(matrix: Matrix4) -> Option<Transform> {
+        Some(Transform { matrix, inverse: matrix.inverse()? })
+    }
+
+    pub fn identity() -> Transform {
+        Transform { matrix: Matrix4::identity(), inverse: Matrix4::identity() }
+    }
+
+    pub fn translate(offset: &Vec3) -> Transform {
+        Transform { matrix: Matrix4::translation(offset), inverse: Matrix4::translation(&-offset) }
+    }
+
+    // None when one of the factors is zero.
+    pub fn scale(factors: &Vec3) -> Option<Transform> {
+        Transform::new(Matrix4::scaling(factors))
+    }
+
+    pub fn rotate(rotation: &Quaternion) -> Transform {
+        let matrix = rotation.to_matrix();
+        Transform { matrix, inverse: matrix.transpose() }
+    }
+
+    // This transform followed by `next`.
+    pub fn then(&self, next: &Transform) -> Transform {
+        Transform { matrix: next.matrix * self.matrix, inverse: self.inverse * next.inverse }
+    }
+
+    pub fn inverted(&self) -> Transform {
+        Transform { matrix: self.inverse, inverse: self.matrix }
+    }
+
+    pub fn point(&self, p: &Point3) -> Point3 {
+        self.matrix.transform_point(p)
+    }
+
+    pub fn vector(&self, v: &Vec3) -> Vec3 {
+        self.matrix.transform_vector(v)
+    }
+
+    // Normals go through the inverse transpose to stay perpendicular to the
+    // transformed surface. The result is not normalized.
+    pub fn normal(&self, n: &Vec3) -> Vec3 {
+        self.inverse.transpose().transform_vector(n)
+    }
+
+    pub fn ray(&self, r: &Ray) -> Ray {
+        Ray::with_time(self.point(&r.origin), self.vector(&r.direction), r.time)
+    }
+
+    // Box around the eight transformed corners, unbounded if the box was.
+    pub fn bounding_box(&self, bbox: &Aabb) -> Aabb {
+        let (x, y, z) = (bbox.x, bbox.y, bbox.z);
+        if [x.min, x.max, y.min, y.max, z.min, z.max].iter().any(|c| !c.is_finite()) {
+            return Aabb::new(Interval::default(), Interval::default(), Interval::default());
+        }
+
+        let mut min = Point3::new(f64::INFINITY, f64::INFINITY, f64::INFINITY);
+        let mut max = -&min;
+        for px in [x.min, x.max] {
+            for py in [y.min, y.max] {
+                for pz in [z.min, z.max] {
+                    let p = self.point(&Point3::new(px, py, pz));
+                    min = Point3::new(min.x.min(p.x), min.y.min(p.y), min.z.min(p.z));
+                    max = Point3::new(max.x.max(p.x), max.y.max(p.y), max.z.max(p.z));
+                }
+            }
+        }
+        Aabb::from_points(&min, &max)
+    }
+
+    // Hit of `object` placed by this transform. The inverse transpose keeps
+    // the sign of the dot products with the ray direction, so the face found
+    // in object space is unchanged.
+    fn hit<H: Hittable + ?Sized>(&self, object: &H, r: &Ray, ray_t: Interval) -> Option<HitRecord> {
+        let mut hr = object.hit(&self.inverted().ray(r), ray_t)?;
+
+        hr.p = self.point(&hr.p);
+        hr.normal = self.normal(&hr.normal).unit_vector();
+        hr.geometric_normal = self.normal(&hr.geometric_normal).unit_vector();
+        hr.dpdu = self.vector(&hr.dpdu);
+        hr.dpdv = self.vector(&hr.dpdv);
+        hr.update_tangent_frame();
+        Some(hr)
+    }
+
+    // The object space density is converted with the Jacobian of the
+    // direction mapping, |A w|^3 / |det A| for a unit object space
+    // direction w and the linear part A of the transform.
+    fn pdf_value<H: Hittable + ?Sized>(&self, object: &H, origin: &Point3, direction: &Vec3, time: f64) -> f64 {
+        let inverse = self.inverted();
+        let object_direction = inverse.vector(direction);
+        let pdf = object.pdf_value(&inverse.point(origin), &object_direction, time);
+        if pdf == 0.0 {
+            return 0.0;
+        }
+
+        let stretch = self.vector(&object_direction.unit_vector()).length();
+        pdf * stretch.powi(3) / self.matrix.determinant3().abs()
+    }
+
+    fn random<H: Hittable + ?Sized>(&self, object: &H, origin: &Point3, time: f64, sampler: &mut Sampler) -> Vec3 {
+        let object_origin = self.inverted().point(origin);
+        self.vector(&object.random(&object_origin, time, sampler))
+    }
+
+}
+
+// An object placed in the world by a transform. Rays are brought into object
+// space, and the hits back into world space. The ray direction is not
+// normalized, so the ray parameters match in both spaces.
+pub struct Transformed<H> {
+    object: H,
+    transform: Transform,
+    bbox: Aabb,
+}
+
+impl<H: Hittable> Transformed<H> {
+
+    pub fn new(object: H, transform: Transform) -> Transformed<H> {
+        let bbox = transform.bounding_box(&object.bounding_box());
+        Transformed {
+            object,
+            transform,
+            bbox,
+        }
+    }
+
+}
+
+impl<H: Hittable> Hittable for Transformed<H> {
+
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord> {
+        self.transform.hit(&self.object, r, ray_t)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3, time: f64) -> f64 {
+        self.transform.pdf_value(&self.object, origin, direction, time)
+    }
+
+    fn random(&self, origin: &Point3, time: f64, sampler: &mut Sampler) -> Vec3 {
+        self.transform.random(&self.object, origin, time, sampler)
+    }
+
+}
+
+// Scale, rotation and translation applied in this order. Animated objects
+// interpolate each of them separately, so that rotations stay rigid.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pose {
+    pub scale: Vec3,
+    pub rotation: Quaternion,
+    pub translation: Vec3,
+}
+
+impl Pose {
+
+    pub fn new(scale: Vec3, rotation: Quaternion, translation: Vec3) -> Pose {
+        Pose {
+            scale,
+            rotation,
+            translation,
+        }
+    }
+
+    // None when the scale is zero along some axis.
+    pub fn transform(&self) -> Option<Transform> {
+        Some(Transform::scale(&self.scale)?
+            .then(&Transform::rotate(&self.rotation))
+            .then(&Transform::translate(&self.translation)))
+    }
+
+}
+
+impl Interpolate for Pose {
+
+    fn interpolate(a: &Pose, b: &Pose, t: f64) -> Pose {
+        Pose::new(Vec3::interpolate(&a.scale, &b.scale, t),
+                  Quaternion::slerp(&a.rotation, &b.rotation, t),
+                  Vec3::interpolate(&a.translation, &b.translation, t))
+    }
+
+}
+
+// Intervals between keyframes are sampled this many times to bound the
+// motion of animated objects.
+const MOTION_STEPS: u32 = 64;
+
+// Largest distance between a point of `bbox` moved from pose `a` to pose `b`
+// and the segment joining its two positions. The rotation turns by an angle
+// θ around a fixed axis while the scaled point moves linearly from y0 to y1,
+// so it strays by at most |y|(1 - cos(θ/2)), the sagitta of its arc, plus
+// |y1 - y0| sin(θ/2) / 2 for the scaling done while turning. Both are
+// largest at a corner.
+fn motion_deviation(bbox: &Aabb, a: &Pose, b: &Pose) -> f64 {
+    let cos = Quaternion::dot(&a.rotation.normalized(), &b.rotation.normalized()).abs().min(1.0);
+    let half_angle = cos.acos();
+    if half_angle == 0.0 {
+        return 0.0;
+    }
+
+    let mut reach: f64 = 0.0;
+    let mut spread: f64 = 0.0;
+    for x in [bbox.x.min, bbox.x.max] {
+        for y in [bbox.y.min, bbox.y.max] {
+            for z in [bbox.z.min, bbox.z.max] {
+                let corner = Vec3::new(x, y, z);
+                let (y0, y1) = (&a.scale * &corner, &b.scale * &corner);
+                reach = reach.max(y0.length()).max(y1.length());
+                spread = spread.max((y1 - y0).length());
+            }
+        }
+    }
+    // Boxes placed from unbounded ones are unbounded already.
+    if !reach.is_finite() {
+        return 0.0;
+    }
+    reach * (1.0 - half_angle.cos()) + 0.5 * spread * half_angle.sin()
+}
+
+// An object moved along keyframed poses, each ray sees it at its own time.
+pub struct Animated<H> {
+    object: H,
+    poses: Keyframes<Pose>,
+    bbox: Aabb,
+}
+
+impl<H: Hittable> Animated<H> {
+
+    // The bounding box covers the object at the keyframes and at many times
+    // in between, rotations make the path curved. Between two of those times
+    // the points of the object stray from the straight line joining their
+    // positions at both ends, so the boxes are padded by as much.
+    pub fn new(object: H, poses: Keyframes<Pose>) -> Animated<H> {
+        let object_bbox = object.bounding_box();
+        let placed = |pose: &Pose| match pose.transform() {
+            Some(transform) => transform.bounding_box(&object_bbox),
+            None => Aabb::empty(),
+        };
+
+        let times = poses.sample_times(MOTION_STEPS);
+        let mut bbox = placed(&poses.at(times[0]));
+        for pair in times.windows(2) {
+            let (a, b) = (poses.at(pair[0]), poses.at(pair[1]));
+            let step = Aabb::surrounding(&placed(&a), &placed(&b));
+            bbox = Aabb::surrounding(&bbox, &step.expand(2.0 * motion_deviation(&object_bbox, &a, &b)));
+        }
+
+        Animated {
+            object,
+            poses,
+            bbox,
+        }
+    }
+
+}
+
+impl<H: Hittable> Hittable for Animated<H> {
+
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord> {
+        self.poses.at(r.time).transform()?.hit(&self.object, r, ray_t)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3, time: f64) -> f64 {
+        match self.poses.at(time).transform() {
+            Some(transform) => transform.pdf_value(&self.object, origin, direction, time),
+            None => 0.0,
+        }
+    }
+
+    fn random(&self, origin: &Point3, time: f64, sampler: &mut Sampler) -> Vec3 {
+        match self.poses.at(time).transform() {
+            Some(transform) => transform.random(&self.object, origin, time, sampler),
+            None => Vec3::new(1.0, 0.0, 0.0),
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sphere::Sphere;
+    use crate::material::{Material, Lambertian};
+    use crate::color::Color;
+    use crate::hittable::Face;
+
+    use std::sync::Arc;
+
+    fn close(a: &Vec3, b: &Vec3) -> bool {
+        (a - b).length() < 1e-9
+    }
+
+    #[test]
+    fn test_matrices() {
+        let mut sampler = Sampler::new(0);
+        let rotation = Quaternion::from_euler(&Vec3::new(30.0, -45.0, 100.0));
+        let matrix = Matrix4::translation(&Vec3::new(1.0, -2.0, 3.0))
+            * rotation.to_matrix()
+            * Matrix4::scaling(&Vec3::new(2.0, 0.5, -3.0));
+
+        let inverse = matrix.inverse().unwrap();
+        let identity = matrix * inverse;
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((identity.m[i][j] - Matrix4::identity().m[i][j]).abs() < 1e-12);
+            }
+        }
+        assert!((matrix.determinant3() + 3.0).abs() < 1e-12);
+        assert!(Matrix4::scaling(&Vec3::new(1.0, 0.0, 1.0)).inverse().is_none());
+
+        // A quarter turn around Y takes X to -Z.
+        let quarter = Quaternion::from_axis_angle(&Vec3::new(0.0, 2.0, 0.0), 90.0).to_matrix();
+        assert!(close(&quarter.transform_vector(&Vec3::new(1.0, 0.0, 0.0)), &Vec3::new(0.0, 0.0, -1.0)));
+
+        // Rotations keep lengths, and the euler angles apply X first.
+        let euler = rotation.to_matrix();
+        let separate = Quaternion::from_axis_angle(&Vec3::new(0.0, 0.0, 1.0), 100.0).to_matrix()
+            * Quaternion::from_axis_angle(&Vec3::new(0.0, 1.0, 0.0), -45.0).to_matrix()
+            * Quaternion::from_axis_angle(&Vec3::new(1.0, 0.0, 0.0), 30.0).to_matrix();
+        for _ in 0..10 {
+            let v = Vec3::random_unit_vector(&mut sampler);
+            let rotated = euler.transform_vector(&v);
+            assert!((rotated.length() - 1.0).abs() < 1e-12);
+            assert!(close(&rotated, &separate.transform_vector(&v)));
+        }
+
+        // Normals stay perpendicular to transformed tangents.
+        let transform = Transform::new(matrix).unwrap();
+        for _ in 0..10 {
+            let n = Vec3::random_unit_vector(&mut sampler);
+            let tangent = Vec3::cross(&n, &Vec3::random_unit_vector(&mut sampler));
+            assert!(Vec3::dot(&transform.normal(&n), &transform.vector(&tangent)).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_transformed_sphere() {
+        // A unit sphere stretched into an ellipsoid 4 wide along X and
+        // moved to (10, 0, 0).
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let sphere = Sphere::new(Point3::new(0.0, 0.0, 0.0), 1.0, material);
+        let transform = Transform::scale(&Vec3::new(4.0, 1.0, 1.0)).unwrap()
+            .then(&Transform::translate(&Vec3::new(10.0, 0.0, 0.0)));
+        let ellipsoid = Transformed::new(sphere, transform);
+        let ray_t = Interval::new(0.001, f64::INFINITY);
+
+        let bbox = ellipsoid.bounding_box();
+        assert!((bbox.x.min - 6.0).abs() < 1e-9 && (bbox.x.max - 14.0).abs() < 1e-9);
+        assert!((bbox.y.min + 1.0).abs() < 1e-9);
+
+        let hr = ellipsoid.hit(&Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0)), ray_t).unwrap();
+        assert!((hr.t - 6.0).abs() < 1e-9);
+        assert!(close(&hr.p, &Point3::new(6.0, 0.0, 0.0)));
+        assert!(close(&hr.normal, &Vec3::new(-1.0, 0.0, 0.0)));
+        assert!(matches!(hr.face, Face::Front));
+
+        // Off axis the normal is the gradient of the ellipsoid, not the
+        // direction from its center.
+        let p = Point3::new(10.0 + 4.0 * 0.6, 0.8, 0.0);
+        let hr = ellipsoid.hit(&Ray::new(Point3::new(p.x, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0)), ray_t).unwrap();
+        assert!(close(&hr.p, &p));
+        assert!(close(&hr.normal, &Vec3::new(0.6 / 4.0, 0.8, 0.0).unit_vector()));
+        assert!(Vec3::dot(&hr.tangent, &hr.normal).abs() < 1e-9);
+
+        // Inside, the ray meets the back face.
+        let hr = ellipsoid.hit(&Ray::new(Point3::new(10.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0)), ray_t).unwrap();
+        assert!((hr.t - 4.0).abs() < 1e-9);
+        assert!(matches!(hr.face, Face::Back));
+    }
+
+    #[test]
+    fn test_animated() {
+        let y = Vec3::new(0.0, 1.0, 0.0);
+        let half = Quaternion::slerp(&Quaternion::identity(), &Quaternion::from_axis_angle(&y, 90.0), 0.5);
+        let expected = Quaternion::from_axis_angle(&y, 45.0);
+        assert!((half.w - expected.w).abs() < 1e-12 && (half.y - expected.y).abs() < 1e-12);
+
+        // A plank 4 long along X turning a quarter around Y while moving up.
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let plank = crate::quad::make_box(&Point3::new(-2.0, -0.1, -0.1), &Point3::new(2.0, 0.1, 0.1), material);
+        let poses = Keyframes::linear(
+            Pose::new(Vec3::new(1.0, 1.0, 1.0), Quaternion::identity(), Vec3::new(0.0, 0.0, 0.0)),
+            Pose::new(Vec3::new(1.0, 1.0, 1.0), Quaternion::from_axis_angle(&y, 90.0), Vec3::new(0.0, 2.0, 0.0)),
+        );
+        let animated = Animated::new(plank, poses);
+        let ray_t = Interval::new(0.001, f64::INFINITY);
+
+        // Along the plank at first, across it at the end.
+        let down = |x: f64, z: f64, time: f64| Ray::with_time(Point3::new(x, 10.0, z), Vec3::new(0.0, -1.0, 0.0), time);
+        assert!((animated.hit(&down(1.5, 0.0, 0.0), ray_t).unwrap().p.y - 0.1).abs() < 1e-9);
+        assert!(animated.hit(&down(0.0, 1.5, 0.0), ray_t).is_none());
+        assert!(animated.hit(&down(1.5, 0.0, 1.0), ray_t).is_none());
+        assert!((animated.hit(&down(0.0, 1.5, 1.0), ray_t).unwrap().p.y - 2.1).abs() < 1e-9);
+
+        // Halfway, along the diagonal, and the box covers the corners swept
+        // in between.
+        let d = 1.5 / 2.0_f64.sqrt();
+        assert!((animated.hit(&down(d, -d, 0.5), ray_t).unwrap().p.y - 1.1).abs() < 1e-9);
+
+        let bbox = animated.bounding_box();
+        assert!(bbox.x.max >= 2.0 && bbox.z.min <= -2.0 && bbox.y.max >= 2.1);
+        let mut sampler = Sampler::new(3);
+        for _ in 0..100 {
+            let time = sampler.random_double();
+            let tip = animated.poses.at(time).transform().unwrap().point(&Point3::new(2.0, 0.1, 0.1));
+            assert!(bbox.x.surrounds(tip.x) && bbox.y.surrounds(tip.y) && bbox.z.surrounds(tip.z));
+        }
+    }
+
+    #[test]
+    fn test_motion_bounds() {
+        // A thin plank turning half around Y, its far ends pointing along Z
+        // halfway between two of the sampled times. Then again while growing.
+        let y = Vec3::new(0.0, 1.0, 0.0);
+        let material: Arc<dyn Material> = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        for growth in [1.0, 2.0] {
+            let plank = crate::quad::make_box(&Point3::new(-20.0, -1.0, -0.01), &Point3::new(20.0, 1.0, 0.01), material.clone());
+            let poses = Keyframes::linear(
+                Pose::new(Vec3::new(1.0, 1.0, 1.0), Quaternion::identity(), Vec3::new(0.0, 0.0, 0.0)),
+                Pose::new(Vec3::new(growth, 1.0, 1.0), Quaternion::from_axis_angle(&y, 180.0), Vec3::new(0.0, 0.0, 0.0)),
+            );
+            let animated = Animated::new(plank, poses);
+            let bbox = animated.bounding_box();
+
+            let inside = |p: &Point3| bbox.x.surrounds(p.x) && bbox.y.surrounds(p.y) && bbox.z.surrounds(p.z);
+            let steps = 100 * (MOTION_STEPS + 1);
+            for i in 0..=steps {
+                let time = i as f64 / steps as f64;
+                let transform = animated.poses.at(time).transform().unwrap();
+                for x in [-20.0, 20.0] {
+                    for z in [-0.01, 0.01] {
+                        let corner = transform.point(&Point3::new(x, 1.0, z));
+                        assert!(inside(&corner), "{:?} at time {} is outside {:?}", corner, time, bbox);
+                    }
+                }
+            }
+
+            // Still tight around the disk swept by the plank.
+            assert!(bbox.z.max < 20.0 * growth + 0.1);
+        }
+    }
+
+    #[test]
+    fn test_light_sampling() {
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let sphere = Sphere::new(Point3::new(0.0, 0.0, 0.0), 1.0, material);
+        let transform = Transform::scale(&Vec3::new(3.0, 1.0, 0.5)).unwrap()
+            .then(&Transform::rotate(&Quaternion::from_euler(&Vec3::new(20.0, 40.0, 0.0))))
+            .then(&Transform::translate(&Vec3::new(0.0, 0.0, -5.0)));
+        let ellipsoid = Transformed::new(sphere, transform);
+        let origin = Point3::new(0.0, 0.0, 0.0);
+        let mut sampler = Sampler::new(2);
+
+        // Sampled directions hit the object, and the density integrates to
+        // one over the sphere of directions.
+        for _ in 0..100 {
+            let direction = ellipsoid.random(&origin, 0.0, &mut sampler);
+            assert!(ellipsoid.hit(&Ray::new(origin.clone(), direction.clone()), Interval::new(0.001, f64::INFINITY)).is_some());
+            assert!(ellipsoid.pdf_value(&origin, &direction, 0.0) > 0.0);
+        }
+
+        let n = 200000;
+        let sum: f64 = (0..n).map(|_| ellipsoid.pdf_value(&origin, &Vec3::random_unit_vector(&mut sampler), 0.0)).sum();
+        let integral = 4.0 * std::f64::consts::PI * sum / n as f64;
+        assert!((integral - 1.0).abs() < 0.03);
+    }
+
+}
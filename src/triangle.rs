@@ -0,0 +1,150 @@
+use crate::hittable::{Hittable, HitRecord, Face, area_light_pdf};
+use crate::vec3::{Vec3, Point3};
+use crate::ray::Ray;
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::aabb::Aabb;
+use crate::sampler::Sampler;
+
+use std::sync::Arc;
+
+pub struct Triangle {
+    pub vertices: [Point3; 3],
+    // Per-vertex shading normals, the flat geometric normal is used without them.
+    pub normals: Option<[Vec3; 3]>,
+    pub uvs: [(f64, f64); 3],
+    pub material: Arc<dyn Material>,
+}
+
+impl Triangle {
+
+    pub fn new(vertices: [Point3; 3], material: Arc<dyn Material>) -> Triangle {
+        Triangle {
+            vertices,
+            normals: None,
+            uvs: [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)],
+            material,
+        }
+    }
+
+}
+
+impl Hittable for Triangle {
+
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord> {
+        let [p0, p1, p2] = &self.vertices;
+        let normals = self.normals.as_ref().map(|[n0, n1, n2]| [n0, n1, n2]);
+
+        hit_triangle(r, ray_t, [p0, p1, p2], normals, self.uvs, &self.material)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        triangle_bounding_box([&self.vertices[0], &self.vertices[1], &self.vertices[2]])
+    }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3, time: f64) -> f64 {
+        let [p0, p1, p2] = &self.vertices;
+        let r = Ray::with_time(origin.clone(), direction.clone(), time);
+
+        match intersect_triangle(&r, Interval::new(0.001, f64::INFINITY), [p0, p1, p2]) {
+            Some((t, _, _)) => {
+                let n = Vec3::cross(&(p1 - p0), &(p2 - p0));
+                area_light_pdf(t, direction, &n, 0.5 * n.length())
+            },
+            None => 0.0,
+        }
+    }
+
+    fn random(&self, origin: &Point3, _time: f64, sampler: &mut Sampler) -> Vec3 {
+        let [p0, p1, p2] = &self.vertices;
+        let mut a = Vec3::random_double(sampler);
+        let mut b = Vec3::random_double(sampler);
+
+        // Fold the far half of the parallelogram back onto the triangle.
+        if a + b > 1.0 {
+            a = 1.0 - a;
+            b = 1.0 - b;
+        }
+
+        let p = p0 + (a * (p1 - p0)) + (b * (p2 - p0));
+        p - origin
+    }
+
+}
+
+pub(crate) fn triangle_bounding_box(p: [&Point3; 3]) -> Aabb {
+    Aabb::surrounding(&Aabb::from_points(p[0], p[1]), &Aabb::from_points(p[0], p[2]))
+}
+
+// Möller–Trumbore intersection, shared by Triangle and Mesh. Returns the ray
+// parameter and the barycentric coordinates of the hit for vertices 1 and 2.
+pub(crate) fn intersect_triangle(r: &Ray, ray_t: Interval, p: [&Point3; 3]) -> Option<(f64, f64, f64)> {
+    let e1 = p[1] - p[0];
+    let e2 = p[2] - p[0];
+
+    let pvec = Vec3::cross(&r.direction, &e2);
+    let det = Vec3::dot(&e1, &pvec);
+
+    if det.abs() < 1e-12 {
+        return None;
+    }
+    let inv_det = 1.0 / det;
+
+    let tvec = &r.origin - p[0];
+    let b1 = Vec3::dot(&tvec, &pvec) * inv_det;
+    if !(0.0..=1.0).contains(&b1) {
+        return None;
+    }
+
+    let qvec = Vec3::cross(&tvec, &e1);
+    let b2 = Vec3::dot(&r.direction, &qvec) * inv_det;
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return None;
+    }
+
+    let t = Vec3::dot(&e2, &qvec) * inv_det;
+    if !ray_t.surrounds(t) {
+        return None;
+    }
+
+    Some((t, b1, b2))
+}
+
+pub(crate) fn hit_triangle(r: &Ray, ray_t: Interval, p: [&Point3; 3], normals: Option<[&Vec3; 3]>, uvs: [(f64, f64); 3], material: &Arc<dyn Material>) -> Option<HitRecord> {
+    let (t, b1, b2) = intersect_triangle(r, ray_t, p)?;
+    let b0 = 1.0 - b1 - b2;
+
+    let outward_normal = Vec3::cross(&(p[1] - p[0]), &(p[2] - p[0])).unit_vector();
+    let u = b0 * uvs[0].0 + b1 * uvs[1].0 + b2 * uvs[2].0;
+    let v = b0 * uvs[0].1 + b1 * uvs[1].1 + b2 * uvs[2].1;
+
+    let mut hr = HitRecord::new(r.at(t), outward_normal.clone(), material.clone(), t, u, v, Face::Front);
+    hr.set_face_normal(r, &outward_normal);
+
+    // The face is decided by the geometric normal, the interpolated normal is
+    // only flipped to the same side so smooth shading can't leak through.
+    if let Some(n) = normals {
+        hr.set_shading_normal((b0 * n[0] + b1 * n[1] + b2 * n[2]).unit_vector());
+    }
+
+    let (dpdu, dpdv) = uv_derivatives(p, uvs);
+    hr.set_uv_derivatives(dpdu, dpdv);
+
+    Some(hr)
+}
+
+// The derivatives are constant over a triangle, found by solving for the
+// edges as combinations of dpdu and dpdv. Zero when the UVs are degenerate.
+fn uv_derivatives(p: [&Point3; 3], uvs: [(f64, f64); 3]) -> (Vec3, Vec3) {
+    let (dp02, dp12) = (p[0] - p[2], p[1] - p[2]);
+    let (du02, dv02) = (uvs[0].0 - uvs[2].0, uvs[0].1 - uvs[2].1);
+    let (du12, dv12) = (uvs[1].0 - uvs[2].0, uvs[1].1 - uvs[2].1);
+
+    let determinant = du02 * dv12 - dv02 * du12;
+    if determinant.abs() < 1e-12 {
+        return (Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 0.0));
+    }
+    let inv = 1.0 / determinant;
+
+    ((dv12 * &dp02 - dv02 * &dp12) * inv, (du02 * &dp12 - du12 * &dp02) * inv)
+}
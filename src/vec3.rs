@@ -1,7 +1,6 @@
 use std::ops::{Add, Sub, Mul, Div, Neg};
-use rand::prelude::*;
-
 use crate::interval::Interval;
+use crate::sampler::Sampler;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Vec3 {
@@ -41,29 +40,29 @@ impl Vec3 {
         self / self.length()
     }
 
-    pub fn random_double() -> f64 {
-        random::<f64>()
+    pub fn random_double(sampler: &mut Sampler) -> f64 {
+        sampler.random_double()
     }
 
-    pub fn random_double_interval(interval: &Interval) -> f64 {
-        interval.min + (interval.max - interval.min) * Self::random_double()
+    pub fn random_double_interval(interval: &Interval, sampler: &mut Sampler) -> f64 {
+        interval.min + (interval.max - interval.min) * Self::random_double(sampler)
     }
 
-    pub fn random() -> Vec3 {
-        Vec3::new(Self::random_double(),
-                  Self::random_double(),
-                  Self::random_double())
+    pub fn random(sampler: &mut Sampler) -> Vec3 {
+        Vec3::new(Self::random_double(sampler),
+                  Self::random_double(sampler),
+                  Self::random_double(sampler))
     }
 
-    pub fn random_interval(interval: Interval) -> Vec3 {
-        Vec3::new(Self::random_double_interval(&interval), 
-                  Self::random_double_interval(&interval),
-                  Self::random_double_interval(&interval))
+    pub fn random_interval(interval: Interval, sampler: &mut Sampler) -> Vec3 {
+        Vec3::new(Self::random_double_interval(&interval, sampler), 
+                  Self::random_double_interval(&interval, sampler),
+                  Self::random_double_interval(&interval, sampler))
     }
 
-    pub fn random_unit_vector() -> Vec3 {
+    pub fn random_unit_vector(sampler: &mut Sampler) -> Vec3 {
         loop {
-            let p = Self::random_interval(Interval::new(-1.0, 1.0));
+            let p = Self::random_interval(Interval::new(-1.0, 1.0), sampler);
 
             if p.length_squared() <= 1.0 {
                 return p.unit_vector();
@@ -71,8 +70,8 @@ impl Vec3 {
         }
     }
 
-    pub fn random_on_hemisphere(normal: &Vec3) -> Vec3 {
-        let on_unit_sphere = Vec3::random_unit_vector();
+    pub fn random_on_hemisphere(normal: &Vec3, sampler: &mut Sampler) -> Vec3 {
+        let on_unit_sphere = Vec3::random_unit_vector(sampler);
 
         if Vec3::dot(&on_unit_sphere, normal) > 0.0 {
             on_unit_sphere
@@ -88,23 +87,23 @@ impl Vec3 {
     }
 
     pub fn reflect(v: &Vec3, n: &Vec3) -> Vec3 {
-        v - (2.0 * n * Vec3::dot(&v, &n))
+        v - (2.0 * n * Vec3::dot(v, n))
     }
 
     pub fn refract(uv: &Vec3, n: &Vec3, etai_over_etat: f64) -> Vec3 {
-        let cos_theta = Vec3::dot(&(-uv), &n).min(1.0);
+        let cos_theta = Vec3::dot(&(-uv), n).min(1.0);
         let r_out_perp = etai_over_etat * (uv + cos_theta * n);
         let r_out_parallel = -(1.0 - r_out_perp.length_squared()).abs().sqrt() * n;
 
         r_out_perp + r_out_parallel
     }
 
-    pub fn random_in_unit_disk() -> Vec3 {
+    pub fn random_in_unit_disk(sampler: &mut Sampler) -> Vec3 {
         let interval = Interval::new(-1.0, 1.0);
 
         loop {
-            let p = Self::new(Self::random_double_interval(&interval),
-                              Self::random_double_interval(&interval),
+            let p = Self::new(Self::random_double_interval(&interval, sampler),
+                              Self::random_double_interval(&interval, sampler),
                               0.0);
             if p.length_squared() < 1.0 {
                 return p;
@@ -0,0 +1,182 @@
+// Dense grids of scalar values over the unit cube, for the density and the
+// emission of heterogeneous media. Grid files are little endian binary:
+//
+//     "VOXG", nx: u32, ny: u32, nz: u32
+//     nx * ny * nz values as f32, x varying fastest, then y, then z
+
+use crate::vec3::Point3;
+
+use std::fs::File;
+use std::io::{self, BufReader, Read, Write};
+use std::path::Path;
+
+const MAGIC: &[u8; 4] = b"VOXG";
+
+// One value per voxel, held at the voxel centers and interpolated
+// trilinearly in between. Beyond the outer centers the values are clamped.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VoxelGrid {
+    nx: usize,
+    ny: usize,
+    nz: usize,
+    values: Vec<f32>,
+    max: f64,
+}
+
+impl VoxelGrid {
+
+    // None when a dimension is zero or the number of values doesn't match.
+    pub fn new(nx: usize, ny: usize, nz: usize, values: Vec<f32>) -> Option<VoxelGrid> {
+        if nx == 0 || ny == 0 || nz == 0 || values.len() != nx * ny * nz {
+            return None;
+        }
+        let max = values.iter().fold(0.0_f64, |max, &v| max.max(v as f64));
+        Some(VoxelGrid { nx, ny, nz, values, max })
+    }
+
+    // Grid of `f` evaluated at the voxel centers, in unit cube coordinates.
+    pub fn from_fn(nx: usize, ny: usize, nz: usize, f: impl Fn(&Point3) -> f64) -> VoxelGrid {
+        let mut values = Vec::with_capacity(nx * ny * nz);
+        for k in 0..nz {
+            for j in 0..ny {
+                for i in 0..nx {
+                    let p = Point3::new((i as f64 + 0.5) / nx as f64, (j as f64 + 0.5) / ny as f64, (k as f64 + 0.5) / nz as f64);
+                    values.push(f(&p) as f32);
+                }
+            }
+        }
+        VoxelGrid::new(nx, ny, nz, values).expect("grid dimensions must not be zero")
+    }
+
+    pub fn load(path: &Path) -> io::Result<VoxelGrid> {
+        VoxelGrid::read(&mut BufReader::new(File::open(path)?))
+    }
+
+    pub fn read(input: &mut impl Read) -> io::Result<VoxelGrid> {
+        let mut magic = [0u8; 4];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not a voxel grid"));
+        }
+
+        let mut dimensions = [0usize; 3];
+        for n in dimensions.iter_mut() {
+            let mut bytes = [0u8; 4];
+            input.read_exact(&mut bytes)?;
+            *n = u32::from_le_bytes(bytes) as usize;
+        }
+        let [nx, ny, nz] = dimensions;
+        let count = nx.checked_mul(ny).and_then(|n| n.checked_mul(nz))
+            .filter(|&n| n > 0)
+            .ok_or_else(|| invalid(&format!("invalid grid dimensions {}x{}x{}", nx, ny, nz)))?;
+
+        // Not preallocated, a corrupted header could ask for any size.
+        let mut values = vec![];
+        let mut bytes = [0u8; 4];
+        for _ in 0..count {
+            input.read_exact(&mut bytes)?;
+            values.push(f32::from_le_bytes(bytes));
+        }
+        if values.iter().any(|v| !v.is_finite()) {
+            return Err(invalid("grid values must be finite"));
+        }
+
+        Ok(VoxelGrid::new(nx, ny, nz, values).expect("dimensions and values were checked"))
+    }
+
+    pub fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        out.write_all(MAGIC)?;
+        for n in [self.nx, self.ny, self.nz] {
+            out.write_all(&(n as u32).to_le_bytes())?;
+        }
+        for value in &self.values {
+            out.write_all(&value.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    pub fn dimensions(&self) -> (usize, usize, usize) {
+        (self.nx, self.ny, self.nz)
+    }
+
+    // Largest value, or zero when they are all negative.
+    pub fn max(&self) -> f64 {
+        self.max
+    }
+
+    fn at(&self, i: usize, j: usize, k: usize) -> f64 {
+        self.values[(k * self.ny + j) * self.nx + i] as f64
+    }
+
+    // Interpolated value at `p`, in unit cube coordinates.
+    pub fn lookup(&self, p: &Point3) -> f64 {
+        // Index of the voxel center below the point along one axis, and the
+        // weight of the next one.
+        let axis = |x: f64, n: usize| {
+            let x = (x * n as f64 - 0.5).clamp(0.0, (n - 1) as f64);
+            let i = (x as usize).min(n.saturating_sub(2));
+            (i, (i + 1).min(n - 1), x - i as f64)
+        };
+        let (i0, i1, u) = axis(p.x, self.nx);
+        let (j0, j1, v) = axis(p.y, self.ny);
+        let (k0, k1, w) = axis(p.z, self.nz);
+
+        let lerp = |a: f64, b: f64, t: f64| a + t * (b - a);
+        let x00 = lerp(self.at(i0, j0, k0), self.at(i1, j0, k0), u);
+        let x10 = lerp(self.at(i0, j1, k0), self.at(i1, j1, k0), u);
+        let x01 = lerp(self.at(i0, j0, k1), self.at(i1, j0, k1), u);
+        let x11 = lerp(self.at(i0, j1, k1), self.at(i1, j1, k1), u);
+        lerp(lerp(x00, x10, v), lerp(x01, x11, v), w)
+    }
+
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup() {
+        // Two voxels along x, one along y and z: values at x = 0.25 and 0.75.
+        let grid = VoxelGrid::new(2, 1, 1, vec![1.0, 3.0]).unwrap();
+        let at = |x: f64| grid.lookup(&Point3::new(x, 0.3, 0.9));
+
+        assert_eq!(at(0.25), 1.0);
+        assert_eq!(at(0.5), 2.0);
+        assert_eq!(at(0.75), 3.0);
+        assert_eq!(at(0.0), 1.0);
+        assert_eq!(at(1.0), 3.0);
+        assert_eq!(grid.max(), 3.0);
+
+        // Linear functions are reproduced exactly between the centers.
+        let grid = VoxelGrid::from_fn(4, 5, 6, |p| p.x + 2.0 * p.y - p.z);
+        assert_eq!(grid.dimensions(), (4, 5, 6));
+        let p = Point3::new(0.4, 0.55, 0.3);
+        assert!((grid.lookup(&p) - (0.4 + 1.1 - 0.3)).abs() < 1e-6);
+
+        assert!(VoxelGrid::new(2, 2, 2, vec![0.0; 7]).is_none());
+        assert!(VoxelGrid::new(0, 2, 2, vec![]).is_none());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let grid = VoxelGrid::from_fn(3, 2, 4, |p| p.x * p.y + p.z);
+
+        let mut bytes = vec![];
+        grid.write(&mut bytes).unwrap();
+        assert_eq!(bytes.len(), 16 + 4 * 24);
+        assert_eq!(VoxelGrid::read(&mut bytes.as_slice()).unwrap(), grid);
+
+        assert!(VoxelGrid::read(&mut &bytes[..bytes.len() - 1]).is_err());
+        assert!(VoxelGrid::read(&mut &b"PNG?"[..]).is_err());
+
+        let mut empty = MAGIC.to_vec();
+        empty.extend([0u8; 12]);
+        assert!(VoxelGrid::read(&mut empty.as_slice()).is_err());
+    }
+
+}